@@ -1,12 +1,22 @@
 extern crate pkg_config;
 
-const LIBSODIUM_VERSION: &str = "1.0.16";
+/// The supported libsodium-versions (`MIN <= version < MAX`)
+#[cfg(not(feature = "pure-rust"))] const LIBSODIUM_VERSION_MIN: &str = "1.0.16";
+#[cfg(not(feature = "pure-rust"))] const LIBSODIUM_VERSION_MAX: &str = "2";
 
+#[cfg(not(feature = "pure-rust"))]
 fn link_libsodium() {
-	let libsodium = pkg_config::Config::new().statik(true).probe("libsodium").unwrap();
-	assert_eq!(libsodium.version, LIBSODIUM_VERSION, "Invalid libsodium-version ({} vs. {})", libsodium.version, LIBSODIUM_VERSION);
+	// Link statically because the Argon2i-implementation is not exported by the shared library
+	pkg_config::Config::new()
+		.statik(true)
+		.range_version(LIBSODIUM_VERSION_MIN .. LIBSODIUM_VERSION_MAX)
+		.probe("libsodium")
+		.unwrap_or_else(|e| panic!("Failed to find libsodium (>= {}, < {}): {}", LIBSODIUM_VERSION_MIN, LIBSODIUM_VERSION_MAX, e));
 }
 
+#[cfg(feature = "pure-rust")]
+fn link_libsodium() {}
+
 fn main() {
 	link_libsodium()
 }
\ No newline at end of file
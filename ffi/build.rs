@@ -0,0 +1,16 @@
+extern crate cbindgen;
+
+use std::env;
+use std::path::PathBuf;
+
+/// Generates the C-header into `OUT_DIR` (the header in `include/` is compared against it by
+/// `tests/ffi.rs`)
+fn main() {
+	let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+	let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+	let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml")).unwrap();
+	cbindgen::generate_with_config(&crate_dir, config).expect("Failed to generate the C-header")
+		.write_to_file(out_dir.join("crypto_pipe.h"));
+	println!("cargo:rerun-if-changed=src/lib.rs");
+	println!("cargo:rerun-if-changed=cbindgen.toml");
+}
\ No newline at end of file
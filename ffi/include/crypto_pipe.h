@@ -0,0 +1,154 @@
+#ifndef CRYPTO_PIPE_H
+#define CRYPTO_PIPE_H
+
+/* This file is generated from `ffi/src/lib.rs` by cbindgen; do not edit it manually */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * The operation was successful
+ */
+#define CP_OK 0
+
+/**
+ * Invalid data (invalid encoding, integrity error etc.)
+ */
+#define CP_ERR_INVALID_DATA 1
+
+/**
+ * Not enough resources to process data
+ */
+#define CP_ERR_RESOURCE 2
+
+/**
+ * An IO-error occurred
+ */
+#define CP_ERR_IO 3
+
+/**
+ * Invalid parameter (not in range, does not make sense, `NULL`-pointer etc.)
+ */
+#define CP_ERR_INVALID_PARAMETER 4
+
+/**
+ * The parameter might be valid but is unsupported
+ */
+#define CP_ERR_UNSUPPORTED 5
+
+/**
+ * Any other error
+ */
+#define CP_ERR_OTHER 6
+
+/**
+ * An internal error (a panic) occurred; the handle must not be used anymore
+ */
+#define CP_ERR_INTERNAL 7
+
+/**
+ * The password does not match the stream
+ */
+#define CP_ERR_WRONG_PASSWORD 8
+
+/**
+ * An opening-handle
+ */
+typedef struct CpOpener CpOpener;
+
+/**
+ * A sealing-handle
+ */
+typedef struct CpSealer CpSealer;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Creates a new sealing-handle that uses Argon2i with the given parameters, HMAC-SHA2-512 and
+ * ChaCha20+Poly1305
+ *
+ * The key-derivation happens immediately, so this call takes as long as the PBKDF. The stream-
+ * header is available for reading afterwards.
+ */
+int cp_sealer_new(const uint8_t *password_ptr,
+                  size_t password_len,
+                  uint32_t time_cost,
+                  uint32_t memory_cost_mib,
+                  uint32_t parallelism,
+                  CpSealer **sealer);
+
+/**
+ * Seals `data`
+ */
+int cp_sealer_update(CpSealer *sealer, const uint8_t *data, size_t data_len);
+
+/**
+ * Seals the last chunk; afterwards only `cp_sealer_pending`, `cp_sealer_read` and
+ * `cp_sealer_free` may be called
+ */
+int cp_sealer_finish(CpSealer *sealer);
+
+/**
+ * Returns the amount of sealed bytes that are available for reading
+ */
+size_t cp_sealer_pending(CpSealer *sealer);
+
+/**
+ * Moves up to `buffer_len` sealed bytes into `buffer` and stores the amount of bytes in
+ * `bytes_read`
+ */
+int cp_sealer_read(CpSealer *sealer, uint8_t *buffer, size_t buffer_len, size_t *bytes_read);
+
+/**
+ * Frees a sealing-handle (`NULL` is ignored)
+ */
+void cp_sealer_free(CpSealer *sealer);
+
+/**
+ * Creates a new opening-handle; the key is derived as soon as the stream-header has been passed
+ * to `cp_opener_update`
+ */
+int cp_opener_new(const uint8_t *password_ptr, size_t password_len, CpOpener **opener);
+
+/**
+ * Opens the sealed `data`; the plaintext of a chunk only becomes available after the chunk has
+ * been verified
+ */
+int cp_opener_update(CpOpener *opener, const uint8_t *data, size_t data_len);
+
+/**
+ * Opens the last chunk; the stream is only complete and authentic if this call succeeds.
+ * Afterwards only `cp_opener_pending`, `cp_opener_read` and `cp_opener_free` may be called
+ */
+int cp_opener_finish(CpOpener *opener);
+
+/**
+ * Returns the amount of opened bytes that are available for reading
+ */
+size_t cp_opener_pending(CpOpener *opener);
+
+/**
+ * Moves up to `buffer_len` opened bytes into `buffer` and stores the amount of bytes in
+ * `bytes_read`
+ */
+int cp_opener_read(CpOpener *opener, uint8_t *buffer, size_t buffer_len, size_t *bytes_read);
+
+/**
+ * Frees an opening-handle (`NULL` is ignored)
+ */
+void cp_opener_free(CpOpener *opener);
+
+/**
+ * Returns a static, human-readable description for a `CP_*`-code
+ */
+const char *cp_error_description(int code);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* CRYPTO_PIPE_H */
\ No newline at end of file
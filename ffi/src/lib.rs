@@ -0,0 +1,262 @@
+//! A C-API to seal and open buffers incrementally
+//!
+//! All functions return one of the `CP_*`-codes; the codes are stable and will not be reassigned.
+//! The sealed/opened data is buffered inside the handle until it is fetched with
+//! `cp_sealer_read`/`cp_opener_read`.
+//!
+//! This crate is built as `cdylib` and `staticlib`; the C-header is located at
+//! `include/crypto_pipe.h`.
+
+#[macro_use] extern crate etrace;
+extern crate crypto_pipe;
+
+use std::os::raw::{ c_int, c_char };
+use std::io::Write;
+use crypto_pipe::{ Error, CpError, crypto };
+use crypto_pipe::adapters::{ SealingWriter, OpeningWriter };
+
+
+/// The operation was successful
+pub const CP_OK: c_int = 0;
+/// Invalid data (invalid encoding, integrity error etc.)
+pub const CP_ERR_INVALID_DATA: c_int = 1;
+/// Not enough resources to process data
+pub const CP_ERR_RESOURCE: c_int = 2;
+/// An IO-error occurred
+pub const CP_ERR_IO: c_int = 3;
+/// Invalid parameter (not in range, does not make sense, `NULL`-pointer etc.)
+pub const CP_ERR_INVALID_PARAMETER: c_int = 4;
+/// The parameter might be valid but is unsupported
+pub const CP_ERR_UNSUPPORTED: c_int = 5;
+/// Any other error
+pub const CP_ERR_OTHER: c_int = 6;
+/// An internal error (a panic) occurred; the handle must not be used anymore
+pub const CP_ERR_INTERNAL: c_int = 7;
+/// The password does not match the stream
+pub const CP_ERR_WRONG_PASSWORD: c_int = 8;
+
+
+/// Maps `error` to a stable `CP_*`-code
+pub fn error_code(error: &CpError) -> c_int {
+	match *error {
+		CpError::InvalidData => CP_ERR_INVALID_DATA,
+		CpError::WrongPassword => CP_ERR_WRONG_PASSWORD,
+		CpError::ResourceError => CP_ERR_RESOURCE,
+		CpError::IOError(_, _) => CP_ERR_IO,
+		CpError::InvalidParameter => CP_ERR_INVALID_PARAMETER,
+		CpError::Unsupported => CP_ERR_UNSUPPORTED,
+		CpError::CliError | CpError::Other(_) => CP_ERR_OTHER
+	}
+}
+
+/// Executes `function`, catches all panics and maps the result to a `CP_*`-code
+fn guarded<F: FnOnce() -> Result<(), Error<CpError>>>(function: F) -> c_int {
+	match std::panic::catch_unwind(std::panic::AssertUnwindSafe(function)) {
+		Ok(Ok(())) => CP_OK,
+		Ok(Err(error)) => error_code(&error.kind),
+		Err(_) => CP_ERR_INTERNAL
+	}
+}
+
+/// Creates a slice over `data` (which may be `NULL` if `data_len` is `0`)
+unsafe fn slice<'a>(data: *const u8, data_len: usize) -> Result<&'a[u8], Error<CpError>> {
+	if data_len == 0 { return Ok(&[]) }
+	if data.is_null() { throw_err!(CpError::InvalidParameter, "`NULL`-pointer") }
+	Ok(std::slice::from_raw_parts(data, data_len))
+}
+
+/// Copies a UTF-8-password into guarded memory
+unsafe fn password(password: *const u8, password_len: usize) -> Result<crypto::Password, Error<CpError>> {
+	crypto::Password::from_utf8(try_err!(slice(password, password_len)))
+}
+
+/// Moves up to `buffer_len` bytes from `output` into `buffer`
+unsafe fn read_output(output: &mut Vec<u8>, buffer: *mut u8, buffer_len: usize, bytes_read: *mut usize) -> Result<(), Error<CpError>> {
+	if (buffer.is_null() && buffer_len > 0) || bytes_read.is_null() { throw_err!(CpError::InvalidParameter, "`NULL`-pointer") }
+	
+	let to_copy = std::cmp::min(buffer_len, output.len());
+	if to_copy > 0 { std::ptr::copy_nonoverlapping(output.as_ptr(), buffer, to_copy) }
+	output.drain(.. to_copy);
+	*bytes_read = to_copy;
+	Ok(())
+}
+
+
+
+/// A sealing-handle
+pub struct CpSealer {
+	writer: Option<SealingWriter<Vec<u8>>>,
+	output: Vec<u8>
+}
+impl CpSealer {
+	fn output(&mut self) -> &mut Vec<u8> {
+		match self.writer {
+			Some(ref mut writer) => writer.get_mut(),
+			None => &mut self.output
+		}
+	}
+}
+
+/// Creates a new sealing-handle that uses Argon2i with the given parameters, HMAC-SHA2-512 and
+/// ChaCha20+Poly1305
+///
+/// The key-derivation happens immediately, so this call takes as long as the PBKDF. The stream-
+/// header is available for reading afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn cp_sealer_new(password_ptr: *const u8, password_len: usize, time_cost: u32, memory_cost_mib: u32, parallelism: u32, sealer: *mut *mut CpSealer) -> c_int {
+	guarded(|| {
+		if sealer.is_null() { throw_err!(CpError::InvalidParameter, "`NULL`-pointer") }
+		let stream_instance = crypto::StreamInstance::new(
+			crypto::pbkdf::Argon2i::new(time_cost, memory_cost_mib, parallelism),
+			crypto::kdf::HmacSha2512::new(),
+			crypto::auth_enc::ChaCha20Poly1305::new()
+		);
+		let writer = try_err!(SealingWriter::new(try_err!(password(password_ptr, password_len)), Vec::new(), stream_instance));
+		*sealer = Box::into_raw(Box::new(CpSealer{ writer: Some(writer), output: Vec::new() }));
+		Ok(())
+	})
+}
+
+/// Seals `data`
+#[no_mangle]
+pub unsafe extern "C" fn cp_sealer_update(sealer: *mut CpSealer, data: *const u8, data_len: usize) -> c_int {
+	guarded(|| {
+		let sealer = match sealer.as_mut() { Some(sealer) => sealer, None => throw_err!(CpError::InvalidParameter, "`NULL`-pointer") };
+		let writer = match sealer.writer.as_mut() { Some(writer) => writer, None => throw_err!(CpError::InvalidParameter, "The sealer is already finished") };
+		try_err_from!(writer.write_all(try_err!(slice(data, data_len))));
+		Ok(())
+	})
+}
+
+/// Seals the last chunk; afterwards only `cp_sealer_pending`, `cp_sealer_read` and
+/// `cp_sealer_free` may be called
+#[no_mangle]
+pub unsafe extern "C" fn cp_sealer_finish(sealer: *mut CpSealer) -> c_int {
+	guarded(|| {
+		let sealer = match sealer.as_mut() { Some(sealer) => sealer, None => throw_err!(CpError::InvalidParameter, "`NULL`-pointer") };
+		let writer = match sealer.writer.take() { Some(writer) => writer, None => throw_err!(CpError::InvalidParameter, "The sealer is already finished") };
+		sealer.output = try_err!(writer.finish());
+		Ok(())
+	})
+}
+
+/// Returns the amount of sealed bytes that are available for reading
+#[no_mangle]
+pub unsafe extern "C" fn cp_sealer_pending(sealer: *mut CpSealer) -> usize {
+	match sealer.as_mut() {
+		Some(sealer) => sealer.output().len(),
+		None => 0
+	}
+}
+
+/// Moves up to `buffer_len` sealed bytes into `buffer` and stores the amount of bytes in
+/// `bytes_read`
+#[no_mangle]
+pub unsafe extern "C" fn cp_sealer_read(sealer: *mut CpSealer, buffer: *mut u8, buffer_len: usize, bytes_read: *mut usize) -> c_int {
+	guarded(|| {
+		let sealer = match sealer.as_mut() { Some(sealer) => sealer, None => throw_err!(CpError::InvalidParameter, "`NULL`-pointer") };
+		read_output(sealer.output(), buffer, buffer_len, bytes_read)
+	})
+}
+
+/// Frees a sealing-handle (`NULL` is ignored)
+#[no_mangle]
+pub unsafe extern "C" fn cp_sealer_free(sealer: *mut CpSealer) {
+	if !sealer.is_null() { drop(Box::from_raw(sealer)) }
+}
+
+
+
+/// An opening-handle
+pub struct CpOpener {
+	writer: Option<OpeningWriter<Vec<u8>>>,
+	output: Vec<u8>
+}
+impl CpOpener {
+	fn output(&mut self) -> &mut Vec<u8> {
+		match self.writer {
+			Some(ref mut writer) => writer.get_mut(),
+			None => &mut self.output
+		}
+	}
+}
+
+/// Creates a new opening-handle; the key is derived as soon as the stream-header has been passed
+/// to `cp_opener_update`
+#[no_mangle]
+pub unsafe extern "C" fn cp_opener_new(password_ptr: *const u8, password_len: usize, opener: *mut *mut CpOpener) -> c_int {
+	guarded(|| {
+		if opener.is_null() { throw_err!(CpError::InvalidParameter, "`NULL`-pointer") }
+		let writer = OpeningWriter::new(try_err!(password(password_ptr, password_len)), Vec::new());
+		*opener = Box::into_raw(Box::new(CpOpener{ writer: Some(writer), output: Vec::new() }));
+		Ok(())
+	})
+}
+
+/// Opens the sealed `data`; the plaintext of a chunk only becomes available after the chunk has
+/// been verified
+#[no_mangle]
+pub unsafe extern "C" fn cp_opener_update(opener: *mut CpOpener, data: *const u8, data_len: usize) -> c_int {
+	guarded(|| {
+		let opener = match opener.as_mut() { Some(opener) => opener, None => throw_err!(CpError::InvalidParameter, "`NULL`-pointer") };
+		let writer = match opener.writer.as_mut() { Some(writer) => writer, None => throw_err!(CpError::InvalidParameter, "The opener is already finished") };
+		try_err_from!(writer.write_all(try_err!(slice(data, data_len))));
+		Ok(())
+	})
+}
+
+/// Opens the last chunk; the stream is only complete and authentic if this call succeeds.
+/// Afterwards only `cp_opener_pending`, `cp_opener_read` and `cp_opener_free` may be called
+#[no_mangle]
+pub unsafe extern "C" fn cp_opener_finish(opener: *mut CpOpener) -> c_int {
+	guarded(|| {
+		let opener = match opener.as_mut() { Some(opener) => opener, None => throw_err!(CpError::InvalidParameter, "`NULL`-pointer") };
+		let writer = match opener.writer.take() { Some(writer) => writer, None => throw_err!(CpError::InvalidParameter, "The opener is already finished") };
+		opener.output = try_err!(writer.finish());
+		Ok(())
+	})
+}
+
+/// Returns the amount of opened bytes that are available for reading
+#[no_mangle]
+pub unsafe extern "C" fn cp_opener_pending(opener: *mut CpOpener) -> usize {
+	match opener.as_mut() {
+		Some(opener) => opener.output().len(),
+		None => 0
+	}
+}
+
+/// Moves up to `buffer_len` opened bytes into `buffer` and stores the amount of bytes in
+/// `bytes_read`
+#[no_mangle]
+pub unsafe extern "C" fn cp_opener_read(opener: *mut CpOpener, buffer: *mut u8, buffer_len: usize, bytes_read: *mut usize) -> c_int {
+	guarded(|| {
+		let opener = match opener.as_mut() { Some(opener) => opener, None => throw_err!(CpError::InvalidParameter, "`NULL`-pointer") };
+		read_output(opener.output(), buffer, buffer_len, bytes_read)
+	})
+}
+
+/// Frees an opening-handle (`NULL` is ignored)
+#[no_mangle]
+pub unsafe extern "C" fn cp_opener_free(opener: *mut CpOpener) {
+	if !opener.is_null() { drop(Box::from_raw(opener)) }
+}
+
+
+
+/// Returns a static, human-readable description for a `CP_*`-code
+#[no_mangle]
+pub extern "C" fn cp_error_description(code: c_int) -> *const c_char {
+	let description: &'static [u8] = match code {
+		CP_OK => b"Success\0",
+		CP_ERR_INVALID_DATA => b"Invalid data (invalid encoding, integrity error etc.)\0",
+		CP_ERR_RESOURCE => b"Not enough resources to process data\0",
+		CP_ERR_IO => b"IO-error\0",
+		CP_ERR_INVALID_PARAMETER => b"Invalid parameter\0",
+		CP_ERR_UNSUPPORTED => b"Unsupported parameter\0",
+		CP_ERR_INTERNAL => b"Internal error\0",
+		CP_ERR_WRONG_PASSWORD => b"Wrong password\0",
+		_ => b"Other error\0"
+	};
+	description.as_ptr() as *const c_char
+}
\ No newline at end of file
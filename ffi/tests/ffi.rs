@@ -0,0 +1,43 @@
+#![cfg(unix)]
+//! Compiles `tests/ffi/harness.c` against the C-header and the shared library and executes it
+//! (requires a C-compiler which can be specified with the `CC`-environment-variable)
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+
+
+#[test]
+fn test_header_is_up_to_date() {
+	// `build.rs` generates the header into `OUT_DIR`; the committed header must match it
+	let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	let generated = fs::read_to_string(PathBuf::from(env!("OUT_DIR")).join("crypto_pipe.h")).unwrap();
+	let committed = fs::read_to_string(manifest_dir.join("include").join("crypto_pipe.h")).unwrap();
+	assert!(generated == committed, "The C-header is outdated; copy it from \"{}\" to \"include/\"", env!("OUT_DIR"))
+}
+
+#[test]
+fn test_c_harness() {
+	// The shared library is located in the parent-directory of the test-binary's `deps`-directory
+	let target_dir = env::current_exe().unwrap().parent().unwrap().parent().unwrap().to_path_buf();
+	let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+	let harness = target_dir.join("crypto_pipe_ffi_harness");
+	
+	// Compile the harness
+	let status = Command::new(env::var("CC").unwrap_or("cc".to_string()))
+		.arg(manifest_dir.join("tests").join("ffi").join("harness.c"))
+		.arg("-std=c99").arg("-I").arg(manifest_dir.join("include"))
+		.arg("-L").arg(&target_dir).arg("-lcrypto_pipe_ffi")
+		.arg("-o").arg(&harness)
+		.status().expect("Failed to start the C-compiler");
+	assert!(status.success(), "Failed to compile the C-harness");
+	
+	// Execute the harness
+	let status = Command::new(&harness)
+		.env("LD_LIBRARY_PATH", &target_dir)
+		.env("DYLD_LIBRARY_PATH", &target_dir)
+		.status().expect("Failed to start the C-harness");
+	assert!(status.success(), "The C-harness failed")
+}
\ No newline at end of file
@@ -0,0 +1,105 @@
+/* A small C-harness that exercises the C-API; it is compiled and executed by `tests/ffi.rs` */
+#include <stdio.h>
+#include <string.h>
+#include "crypto_pipe.h"
+
+#define PASSWORD "FFI password"
+#define PLAIN_SIZE (3 * 1024 * 1024 + 4099)
+
+#define CHECK(expr) do { \
+	int code = (expr); \
+	if (code != CP_OK) { fprintf(stderr, "`%s` failed: %s\n", #expr, cp_error_description(code)); return 1; } \
+} while (0)
+
+typedef struct {
+	uint8_t* data;
+	size_t len;
+} buffer_t;
+
+/* Appends `pending` bytes fetched by `read_fn` from `handle` to `buffer` */
+static int collect(buffer_t* buffer, size_t pending, int (*read_fn)(void*, uint8_t*, size_t, size_t*), void* handle) {
+	size_t bytes_read = 0;
+	if (pending == 0) return CP_OK;
+	
+	buffer->data = realloc(buffer->data, buffer->len + pending);
+	if (buffer->data == NULL) return CP_ERR_RESOURCE;
+	
+	int code = read_fn(handle, buffer->data + buffer->len, pending, &bytes_read);
+	buffer->len += bytes_read;
+	return code;
+}
+static int read_sealer(void* handle, uint8_t* buffer, size_t buffer_len, size_t* bytes_read) {
+	return cp_sealer_read((CpSealer*)handle, buffer, buffer_len, bytes_read);
+}
+static int read_opener(void* handle, uint8_t* buffer, size_t buffer_len, size_t* bytes_read) {
+	return cp_opener_read((CpOpener*)handle, buffer, buffer_len, bytes_read);
+}
+
+/* Seals `plain` in `step`-sized increments */
+static int seal_buffer(buffer_t* sealed, const buffer_t* plain, size_t step) {
+	CpSealer* sealer = NULL;
+	CHECK(cp_sealer_new((const uint8_t*)PASSWORD, strlen(PASSWORD), 4, 8, 1, &sealer));
+	for (size_t pos = 0; pos < plain->len; pos += step) {
+		size_t len = plain->len - pos < step ? plain->len - pos : step;
+		CHECK(cp_sealer_update(sealer, plain->data + pos, len));
+		CHECK(collect(sealed, cp_sealer_pending(sealer), read_sealer, sealer));
+	}
+	CHECK(cp_sealer_finish(sealer));
+	CHECK(collect(sealed, cp_sealer_pending(sealer), read_sealer, sealer));
+	cp_sealer_free(sealer);
+	return CP_OK;
+}
+
+/* Opens `sealed` in `step`-sized increments and returns the first error-code */
+static int open_buffer(buffer_t* opened, const buffer_t* sealed, const char* password, size_t step) {
+	CpOpener* opener = NULL;
+	int code = cp_opener_new((const uint8_t*)password, strlen(password), &opener);
+	for (size_t pos = 0; code == CP_OK && pos < sealed->len; pos += step) {
+		size_t len = sealed->len - pos < step ? sealed->len - pos : step;
+		code = cp_opener_update(opener, sealed->data + pos, len);
+		if (code == CP_OK) code = collect(opened, cp_opener_pending(opener), read_opener, opener);
+	}
+	if (code == CP_OK) code = cp_opener_finish(opener);
+	if (code == CP_OK) code = collect(opened, cp_opener_pending(opener), read_opener, opener);
+	cp_opener_free(opener);
+	return code;
+}
+
+int main(void) {
+	buffer_t plain = { malloc(PLAIN_SIZE), PLAIN_SIZE }, sealed = { NULL, 0 }, opened = { NULL, 0 };
+	for (size_t i = 0; i < plain.len; i++) plain.data[i] = (uint8_t)(i * 7 + (i >> 12));
+	
+	/* Roundtrip */
+	CHECK(seal_buffer(&sealed, &plain, 65537));
+	CHECK(open_buffer(&opened, &sealed, PASSWORD, 7777));
+	if (opened.len != plain.len || memcmp(opened.data, plain.data, plain.len) != 0) {
+		fprintf(stderr, "The opened data does not match the plaintext\n");
+		return 1;
+	}
+	
+	/* Wrong password */
+	opened.len = 0;
+	if (open_buffer(&opened, &sealed, "Wrong password", 7777) != CP_ERR_WRONG_PASSWORD) {
+		fprintf(stderr, "A wrong password was not detected\n");
+		return 1;
+	}
+	
+	/* Damaged last chunk */
+	opened.len = 0;
+	sealed.data[sealed.len - 1] ^= 0x40;
+	if (open_buffer(&opened, &sealed, PASSWORD, sealed.len) != CP_ERR_INVALID_DATA) {
+		fprintf(stderr, "A damaged chunk was not detected\n");
+		return 1;
+	}
+	
+	/* Invalid parameters */
+	if (cp_sealer_update(NULL, NULL, 0) != CP_ERR_INVALID_PARAMETER) {
+		fprintf(stderr, "A `NULL`-handle was not detected\n");
+		return 1;
+	}
+	
+	free(plain.data);
+	free(sealed.data);
+	free(opened.data);
+	return 0;
+}
\ No newline at end of file
@@ -0,0 +1,237 @@
+use std;
+use super::{ Error, CpError };
+use super::{ crypto, io, stream };
+use super::io::Io;
+use super::error::into_io_error;
+use super::asn1_der::IntoDerEncoded;
+
+
+/// The amount of bytes buffered at once while the stream-header is incomplete
+const HEADER_BUFFER_SIZE: usize = 4096;
+
+
+/// A `std::io::Write`-adapter that seals all data written to it into `writer`
+///
+/// The data is buffered until a chunk is complete; the remaining data is sealed as last chunk by
+/// `finish()`. __Dropping the writer without calling `finish()` results in a truncated stream that
+/// cannot be opened.__
+pub struct SealingWriter<W: std::io::Write> {
+	writer: W,
+	key: crypto::Key,
+	stream_instance: crypto::StreamInstance,
+	counter: u64,
+	chunk: (Vec<u8>, usize)
+}
+impl<W: std::io::Write> SealingWriter<W> {
+	/// Derives the key from `password`, writes the stream-header to `writer` and creates a new
+	/// sealing-writer (fails if `stream_instance` has extensions that require the `Encryptor`)
+	pub fn new<P: Into<crypto::Password>>(password: P, mut writer: W, mut stream_instance: crypto::StreamInstance) -> Result<Self, Error<CpError>> {
+		// Derive key and write header
+		try_err!(stream::require_plain_chunks(&stream_instance));
+		let key = try_err!(stream::derive_sealing_key(&mut stream_instance, password.into()));
+		try_err_from!(writer.write_all(&stream_instance.as_serialized().into_der_encoded()), "Failed to write stream-header".to_owned());
+		
+		let chunk_buffer = vec![0u8; stream::CHUNK_DATA_SIZE + stream_instance.auth_enc.overhead()];
+		Ok(SealingWriter{ writer, key, stream_instance, counter: 0, chunk: (chunk_buffer, 0) })
+	}
+	
+	/// Returns a mutable reference to the underlying writer
+	pub fn get_mut(&mut self) -> &mut W {
+		&mut self.writer
+	}
+	
+	/// Seals the buffered data as last chunk, flushes the underlying writer and returns it
+	pub fn finish(mut self) -> Result<W, Error<CpError>> {
+		try_err!(self.seal_chunk(true));
+		try_err_from!(self.writer.flush());
+		Ok(self.writer)
+	}
+	
+	/// Seals and writes the buffered chunk
+	fn seal_chunk(&mut self, is_last: bool) -> Result<(), Error<CpError>> {
+		// Seal and write chunk
+		let sealed_length = try_err!(stream::seal_chunk(&self.stream_instance, &self.key, self.counter, is_last, &mut self.chunk.0, self.chunk.1));
+		try_err_from!(self.writer.write_all(&self.chunk.0[.. sealed_length]), "Failed to write sealed chunk".to_owned());
+		self.counter += 1;
+		self.chunk.1 = 0;
+		Ok(())
+	}
+}
+impl<W: std::io::Write> std::io::Write for SealingWriter<W> {
+	fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+		if data.is_empty() { return Ok(0) }
+		
+		// A full chunk is only sealed if more data follows because otherwise it is the last chunk
+		if self.chunk.1 == stream::CHUNK_DATA_SIZE { self.seal_chunk(false).map_err(into_io_error)? }
+		
+		// Buffer data
+		let to_copy = std::cmp::min(stream::CHUNK_DATA_SIZE - self.chunk.1, data.len());
+		self.chunk.0[self.chunk.1 .. self.chunk.1 + to_copy].copy_from_slice(&data[.. to_copy]);
+		self.chunk.1 += to_copy;
+		Ok(to_copy)
+	}
+	
+	fn flush(&mut self) -> std::io::Result<()> {
+		self.writer.flush()
+	}
+}
+
+
+
+/// A `std::io::Read`-adapter that opens a sealed stream from `reader` and yields the verified
+/// plaintext
+///
+/// A chunk is only returned after it has been verified successfully; the end of the stream is only
+/// reported if the last chunk was verified (so that a truncation will be detected).
+pub struct OpeningReader<R: std::io::Read> {
+	io: io::StreamIo<R, std::io::Sink>,
+	key: crypto::Key,
+	stream_instance: crypto::StreamInstance,
+	counter: u64,
+	chunk: (Vec<u8>, usize, usize),
+	is_last: bool
+}
+impl<R: std::io::Read> OpeningReader<R> {
+	/// Reads the stream-header from `reader`, derives the key from `password` and creates a new
+	/// opening-reader
+	pub fn new<P: Into<crypto::Password>>(password: P, reader: R) -> Result<Self, Error<CpError>> {
+		// Read header and derive key
+		let mut io = io::StreamIo::new(reader, std::io::sink());
+		let stream_instance = try_err!(stream::read_header(&mut io));
+		try_err!(stream::require_plain_chunks(&stream_instance));
+		let key = try_err!(stream::derive_opening_key(&stream_instance, password.into()));
+		
+		let chunk_buffer = vec![0u8; stream::CHUNK_DATA_SIZE + stream_instance.auth_enc.overhead()];
+		Ok(OpeningReader{ io, key, stream_instance, counter: 0, chunk: (chunk_buffer, 0, 0), is_last: false })
+	}
+	
+	/// Returns the underlying reader
+	pub fn into_inner(self) -> R {
+		self.io.into_inner().0
+	}
+	
+	/// Reads and opens the next chunk
+	fn open_chunk(&mut self) -> Result<(), Error<CpError>> {
+		// Read chunk
+		let (chunk_length, is_last) = try_err!(self.io.read_chunk(&mut self.chunk.0));
+		
+		// Open chunk
+		let data_length = try_err!(stream::open_chunk(&self.stream_instance, &self.key, self.counter, is_last, &mut self.chunk.0, chunk_length));
+		self.counter += 1;
+		self.chunk.1 = 0;
+		self.chunk.2 = data_length;
+		self.is_last = is_last;
+		Ok(())
+	}
+}
+impl<R: std::io::Read> std::io::Read for OpeningReader<R> {
+	fn read(&mut self, buffer: &mut[u8]) -> std::io::Result<usize> {
+		if buffer.is_empty() { return Ok(0) }
+		
+		// Open the next chunk if the current chunk is consumed
+		while self.chunk.1 == self.chunk.2 {
+			if self.is_last { return Ok(0) }
+			self.open_chunk().map_err(into_io_error)?;
+		}
+		
+		// Copy the plaintext
+		let to_copy = std::cmp::min(self.chunk.2 - self.chunk.1, buffer.len());
+		buffer[.. to_copy].copy_from_slice(&self.chunk.0[self.chunk.1 .. self.chunk.1 + to_copy]);
+		self.chunk.1 += to_copy;
+		Ok(to_copy)
+	}
+}
+
+
+
+/// A `std::io::Write`-adapter that accepts a sealed stream and writes the verified plaintext into
+/// `writer`
+///
+/// This is the push-based counterpart to `OpeningReader`: a chunk is only written to `writer` after
+/// it has been verified successfully. Because the last chunk can only be identified at the end of
+/// the stream, it is opened by `finish()`.
+pub struct OpeningWriter<W: std::io::Write> {
+	writer: W,
+	password: Option<crypto::Password>,
+	stream_instance: Option<(crypto::StreamInstance, crypto::Key)>,
+	counter: u64,
+	input: Vec<u8>
+}
+impl<W: std::io::Write> OpeningWriter<W> {
+	/// Creates a new opening-writer; the key is derived from `password` as soon as the stream-header
+	/// has been written completely
+	pub fn new<P: Into<crypto::Password>>(password: P, writer: W) -> Self {
+		OpeningWriter{ writer, password: Some(password.into()), stream_instance: None, counter: 0, input: Vec::new() }
+	}
+	
+	/// Returns a mutable reference to the underlying writer
+	pub fn get_mut(&mut self) -> &mut W {
+		&mut self.writer
+	}
+	
+	/// Opens the buffered data as last chunk, flushes the underlying writer and returns it
+	pub fn finish(mut self) -> Result<W, Error<CpError>> {
+		let (stream_instance, key) = match self.stream_instance.take() {
+			Some(stream_instance) => stream_instance,
+			None => throw_err!(std::io::ErrorKind::UnexpectedEof.into(), "The stream-header is incomplete")
+		};
+		
+		// Open and write the last chunk
+		let chunk_length = self.input.len();
+		let data_length = try_err!(stream::open_chunk(&stream_instance, &key, self.counter, true, &mut self.input, chunk_length));
+		try_err_from!(self.writer.write_all(&self.input[.. data_length]), "Failed to write opened chunk".to_owned());
+		try_err_from!(self.writer.flush());
+		Ok(self.writer)
+	}
+	
+	/// Parses the stream-header and opens all buffered chunks that are followed by more data
+	fn process(&mut self) -> Result<(), Error<CpError>> {
+		// Parse the stream-header and derive the key
+		if self.stream_instance.is_none() {
+			let header_length = match try_err!(crypto::StreamInstance::try_parse_length(&self.input)) {
+				Some(header_length) if self.input.len() >= header_length => header_length,
+				_ => return Ok(())
+			};
+			let remaining = self.input.split_off(header_length);
+			let stream_instance = try_err!(crypto::StreamInstance::from_serialized(std::mem::replace(&mut self.input, remaining)));
+			try_err!(stream::require_plain_chunks(&stream_instance));
+			
+			let password = if let Some(password) = self.password.take() { password }
+				else { throw_err!(CpError::Unsupported, "The key has already been derived") };
+			let key = try_err!(stream::derive_opening_key(&stream_instance, password));
+			self.stream_instance = Some((stream_instance, key));
+		}
+		
+		// Open all chunks that are followed by more data (=> they cannot be the last chunk)
+		if let Some((ref stream_instance, ref key)) = self.stream_instance {
+			let sealed_chunk_size = stream::CHUNK_DATA_SIZE + stream_instance.auth_enc.overhead();
+			while self.input.len() > sealed_chunk_size {
+				let data_length = try_err!(stream::open_chunk(stream_instance, key, self.counter, false, &mut self.input[.. sealed_chunk_size], sealed_chunk_size));
+				try_err_from!(self.writer.write_all(&self.input[.. data_length]), "Failed to write opened chunk".to_owned());
+				self.input.drain(.. sealed_chunk_size);
+				self.counter += 1;
+			}
+		}
+		Ok(())
+	}
+}
+impl<W: std::io::Write> std::io::Write for OpeningWriter<W> {
+	fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+		if data.is_empty() { return Ok(0) }
+		
+		// Buffer the data (at most one byte more than a sealed chunk so that we know if the buffered chunk is the last one)
+		let to_copy = match self.stream_instance {
+			Some((ref stream_instance, _)) => stream::CHUNK_DATA_SIZE + stream_instance.auth_enc.overhead() + 1 - self.input.len(),
+			None => HEADER_BUFFER_SIZE
+		};
+		let to_copy = std::cmp::min(to_copy, data.len());
+		self.input.extend_from_slice(&data[.. to_copy]);
+		
+		self.process().map_err(into_io_error)?;
+		Ok(to_copy)
+	}
+	
+	fn flush(&mut self) -> std::io::Result<()> {
+		self.writer.flush()
+	}
+}
\ No newline at end of file
@@ -0,0 +1,85 @@
+//! Appending data to an appendable stream
+//!
+//! In an appendable stream (see `SealBuilder::appendable`), every sealed chunk is followed by the
+//! random session-ID of the sealing-operation that wrote it and the chunk-key is derived from
+//! `counter || session_id || label` (see `stream::seal_appendable_chunk`). To append data, the last
+//! chunk is opened and sealed again as ordinary chunk (together with the new data) by a new session;
+//! because every session uses a new session-ID, no chunk-key is ever used for two different
+//! plaintexts - even if a file is restored from a backup and a different plaintext is appended.
+
+use std;
+use std::io::{ Read, Write, Seek, SeekFrom };
+use super::{ Error, CpError };
+use super::{ crypto, io, stream };
+use super::asn1_der::IntoDerEncoded;
+
+
+/// Appends the entire input of `reader` to the appendable stream in `file`
+///
+/// The last chunk is re-sealed in place; if this fails (e.g. because `reader` fails), the original
+/// last chunk is written back and `file` is truncated to its original length. The stream is not
+/// modified if the password is wrong, the last chunk cannot be opened or `reader` yields no data.
+pub fn append<P: Into<crypto::Password>, R: Read>(password: P, file: &mut std::fs::File, mut reader: R) -> Result<(), Error<CpError>> {
+	// Read the stream-header and derive the key
+	try_err_from!(file.seek(SeekFrom::Start(0)), "Failed to seek to the stream-header".to_owned());
+	let stream_instance = {
+		let mut io = io::StreamIo::new(&mut *file, std::io::sink());
+		try_err!(stream::read_header(&mut io))
+	};
+	if !stream_instance.appendable { throw_err!(CpError::Unsupported, "The stream is not appendable") }
+	let base_key = try_err!(stream::derive_opening_key(&stream_instance, password.into()));
+	
+	// Locate the last chunk (all other chunks are full chunks)
+	let body_offset = stream_instance.as_serialized().into_der_encoded().len() as u64 + stream_instance.metadata_length.unwrap_or(0);
+	let file_length = try_err_from!(file.seek(SeekFrom::End(0)), "Failed to seek to the end of the stream".to_owned());
+	if file_length <= body_offset { throw_err!(CpError::InvalidData, "The stream is truncated") }
+	let sealed_chunk_length = (stream::CHUNK_DATA_SIZE + stream::chunk_overhead(&stream_instance)) as u64;
+	let last_counter = (file_length - body_offset - 1) / sealed_chunk_length;
+	let last_offset = body_offset + last_counter * sealed_chunk_length;
+	
+	// Read and open the last chunk (the sealed chunk is kept to restore it if the append fails)
+	let mut sealed_last_chunk = vec![0u8; (file_length - last_offset) as usize];
+	try_err_from!(file.seek(SeekFrom::Start(last_offset)), "Failed to seek to the last chunk".to_owned());
+	try_err_from!(file.read_exact(&mut sealed_last_chunk), "Failed to read the last chunk".to_owned());
+	let (mut chunk_buffer, chunk_length) = (sealed_last_chunk.clone(), sealed_last_chunk.len());
+	let data_length = try_err!(
+		stream::open_appendable_chunk(&stream_instance, &base_key, last_counter, true, &mut chunk_buffer, chunk_length),
+		CpError::InvalidData, "The last chunk is damaged or the stream is truncated"
+	);
+	chunk_buffer.truncate(data_length);
+	
+	// Read the first byte of the new data before anything is written
+	let mut first_byte = Vec::new();
+	try_err_from!((&mut reader).take(1).read_to_end(&mut first_byte), "Failed to read the data to append".to_owned());
+	if first_byte.is_empty() { throw_err!(CpError::InvalidParameter, "There is no data to append") }
+	
+	// Seal the data of the last chunk and the new data (starting with the last chunk's index) and
+	// restore the original last chunk if this fails
+	let data = chunk_buffer.as_slice().chain(first_byte.as_slice()).chain(reader);
+	if let Err(error) = seal_tail(file, last_offset, last_counter, base_key, stream_instance, data) {
+		try_err_from!(restore_last_chunk(file, last_offset, &sealed_last_chunk), "Failed to restore the last chunk".to_owned());
+		return Err(error)
+	}
+	Ok(())
+}
+
+/// Seals `data` into `file` at `offset`, starting with the chunk-index `counter`
+fn seal_tail<R: Read>(file: &mut std::fs::File, offset: u64, counter: u64, base_key: crypto::Key, stream_instance: crypto::StreamInstance, data: R) -> Result<(), Error<CpError>> {
+	try_err_from!(file.seek(SeekFrom::Start(offset)), "Failed to seek to the last chunk".to_owned());
+	{
+		let mut io = io::StreamIo::new(data, &mut *file);
+		let mut encryptor = stream::Encryptor::with_key(base_key, &mut io, stream_instance);
+		encryptor.set_first_counter(counter);
+		try_err!(encryptor.seal_body());
+	}
+	try_err_from!(file.flush(), "Failed to flush the stream".to_owned());
+	Ok(())
+}
+
+/// Writes the sealed last chunk back to `offset` and truncates `file` behind it
+fn restore_last_chunk(file: &mut std::fs::File, offset: u64, sealed_last_chunk: &[u8]) -> std::io::Result<()> {
+	file.seek(SeekFrom::Start(offset))?;
+	file.write_all(sealed_last_chunk)?;
+	file.set_len(offset + sealed_last_chunk.len() as u64)?;
+	file.flush()
+}
\ No newline at end of file
@@ -0,0 +1,317 @@
+//! Archives of separately sealed files
+//!
+//! An archive consists of the stream-header (with the `archive`-extension), the sealed files, the
+//! sealed index and the trailer `index_offset (u64 BE) || index_length (u64 BE)`. Every file is
+//! sealed like the body of a stream (see `stream::Encryptor::seal_body`) with its own entry-key, and
+//! the index contains the offset of every file, so single files can be extracted without opening
+//! the others.
+
+use std;
+use std::io::{ Read, Write, Seek, SeekFrom };
+use std::path::Path;
+use super::{ Error, CpError };
+use super::{ asn1_der, crypto, io, stream, metadata, compression };
+use super::asn1_der::{ FromDerEncoded, FromDerObject, IntoDerEncoded, IntoDerObject };
+
+
+/// The KDF-info-label of the entry-keys (the counter is the index of the entry)
+pub const ENTRY_INFO: &str = "#Archive Entry";
+/// The KDF-info-label of the index-key
+pub const INDEX_INFO: &str = "#Archive Index";
+/// The length of the trailer
+pub const TRAILER_SIZE: usize = 16;
+
+
+
+/// An entry of the archive-index
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ArchiveEntry {
+	/// The relative, `/`-separated path of the file
+	pub path: String,
+	/// The offset of the sealed file in the archive
+	pub offset: u64,
+	/// The length of the sealed file
+	pub sealed_length: u64,
+	/// The size, the modification-time and the permissions of the file
+	pub metadata: metadata::Metadata
+}
+impl ArchiveEntry {
+	/// Parses a serialized entry
+	fn from_serialized(serialized: asn1_der::DerObject) -> Result<Self, Error<CpError>> {
+		let sequence: Vec<asn1_der::DerObject> = try_err!(Vec::<asn1_der::DerObject>::from_der_object(serialized), CpError::InvalidData);
+		if sequence.len() < 4 { throw_err!(CpError::InvalidData) }
+		
+		let path = try_err!(String::from_der_object(sequence[0].clone()), CpError::InvalidData);
+		try_err!(validate_path(&path), CpError::InvalidData);
+		Ok(ArchiveEntry {
+			path,
+			offset: try_err!(u64::from_der_object(sequence[1].clone()), CpError::InvalidData),
+			sealed_length: try_err!(u64::from_der_object(sequence[2].clone()), CpError::InvalidData),
+			metadata: try_err!(metadata::Metadata::from_serialized(&try_err!(Vec::<u8>::from_der_object(sequence[3].clone()), CpError::InvalidData)))
+		})
+	}
+	
+	/// Serializes the entry
+	fn serialize(&self) -> asn1_der::DerObject {
+		let sequence: Vec<asn1_der::DerObject> = vec![
+			self.path.clone().into_der_object(),
+			self.offset.into_der_object(),
+			self.sealed_length.into_der_object(),
+			self.metadata.serialize().into_der_object()
+		];
+		sequence.into_der_object()
+	}
+	
+	/// Returns whether the entry is `path` or below `path` (every entry matches an empty `path`)
+	pub fn matches(&self, path: &str) -> bool {
+		let path = path.trim_matches('/');
+		path.is_empty() || self.path == path || (self.path.starts_with(path) && self.path[path.len() ..].starts_with('/'))
+	}
+}
+
+/// Validates an archive-path (it must be relative, `/`-separated and must not contain empty, `.`-
+/// or `..`-components, so that it cannot escape the extraction-directory)
+pub fn validate_path(path: &str) -> Result<(), Error<CpError>> {
+	let is_valid = path.split('/').all(|component| !component.is_empty() && component != "." && component != ".." && !component.contains('\\'));
+	if !is_valid { throw_err!(CpError::InvalidParameter, format!("Invalid archive-path \"{}\"", path)) }
+	Ok(())
+}
+
+/// Derives the key with the KDF-info `counter || info`
+fn derive_key(stream_instance: &crypto::StreamInstance, base_key: &crypto::Key, counter: u64, info: &str) -> Result<crypto::Key, Error<CpError>> {
+	let mut kdf_buffer = [0u8; 32];
+	stream_instance.kdf.derive(base_key, try_err!(stream::kdf_info(&mut kdf_buffer, counter, info)))
+}
+
+
+
+/// A `Write`-adapter that counts the written bytes
+struct CountingWriter<'w, W: Write + 'w> {
+	writer: &'w mut W,
+	count: u64
+}
+impl<'w, W: Write + 'w> Write for CountingWriter<'w, W> {
+	fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+		let written = self.writer.write(data)?;
+		self.count += written as u64;
+		Ok(written)
+	}
+	fn flush(&mut self) -> std::io::Result<()> {
+		self.writer.flush()
+	}
+}
+
+
+
+/// Writes an archive
+pub struct ArchiveWriter<W: Write> {
+	writer: W,
+	stream_instance: crypto::StreamInstance,
+	key: crypto::Key,
+	entries: Vec<ArchiveEntry>,
+	offset: u64
+}
+impl<W: Write> ArchiveWriter<W> {
+	/// Derives the key from `password` and writes the archive-header to `writer` (the stream-instance
+	/// determines the parity, the compression and the padding of every file)
+	pub fn new<P: Into<crypto::Password>>(password: P, mut writer: W, mut stream_instance: crypto::StreamInstance) -> Result<Self, Error<CpError>> {
+		if stream_instance.appendable { throw_err!(CpError::InvalidParameter, "Archives cannot be appendable") }
+		if stream_instance.seek_index { throw_err!(CpError::InvalidParameter, "Archives cannot have a seek-index (the index locates the entries)") }
+		stream_instance.archive = true;
+		stream_instance.metadata_length = None;
+		let key = try_err!(stream::derive_sealing_key(&mut stream_instance, password.into()));
+		
+		let header = stream_instance.as_serialized().into_der_encoded();
+		try_err_from!(writer.write_all(&header), "Failed to write the archive-header".to_owned());
+		Ok(ArchiveWriter{ writer, stream_instance, key, entries: Vec::new(), offset: header.len() as u64 })
+	}
+	
+	/// Seals the data from `reader` as entry `path`
+	pub fn add<R: Read>(&mut self, path: &str, reader: R, metadata: metadata::Metadata) -> Result<(), Error<CpError>> {
+		try_err!(validate_path(path));
+		if self.entries.iter().any(|entry| entry.path == path) { throw_err!(CpError::InvalidParameter, format!("Duplicate archive-path \"{}\"", path)) }
+		
+		// Seal the data with the entry-key
+		let key = try_err!(derive_key(&self.stream_instance, &self.key, self.entries.len() as u64, ENTRY_INFO));
+		let mut output = CountingWriter{ writer: &mut self.writer, count: 0 };
+		{
+			let mut io = io::StreamIo::new(reader, &mut output);
+			try_err!(stream::Encryptor::with_key(key, &mut io, try_err!(self.stream_instance.try_clone())).seal_body());
+		}
+		
+		// Record the entry
+		self.entries.push(ArchiveEntry{ path: path.to_string(), offset: self.offset, sealed_length: output.count, metadata });
+		self.offset += output.count;
+		Ok(())
+	}
+	
+	/// Seals the file at `file` as entry `path` (with its size, modification-time and permissions)
+	pub fn add_file<F: AsRef<Path>>(&mut self, path: &str, file: F) -> Result<(), Error<CpError>> {
+		let metadata = metadata::Metadata{ filename: None, ..try_err!(metadata::Metadata::from_file(file.as_ref())) };
+		let reader = try_err_from!(std::fs::File::open(file.as_ref()), format!("Failed to open \"{}\"", file.as_ref().display()));
+		self.add(path, reader, metadata)
+	}
+	
+	/// Seals all regular files below `dir` in sorted order (symlinks, special files and empty
+	/// directories are skipped) and returns the amount of added files
+	pub fn add_dir<D: AsRef<Path>>(&mut self, dir: D) -> Result<usize, Error<CpError>> {
+		let mut files = Vec::new();
+		try_err!(walk(dir.as_ref(), "", &mut files));
+		for (path, file) in files.iter() { try_err!(self.add_file(path, file)) }
+		Ok(files.len())
+	}
+	
+	/// Writes the sealed index and the trailer and returns the writer
+	pub fn finish(mut self) -> Result<W, Error<CpError>> {
+		// Serialize and seal the index
+		let index: Vec<asn1_der::DerObject> = self.entries.iter().map(|entry| entry.serialize()).collect();
+		let serialized = index.into_der_object().into_der_encoded();
+		let key = try_err!(derive_key(&self.stream_instance, &self.key, 0, INDEX_INFO));
+		let mut sealed = serialized.clone();
+		sealed.resize(serialized.len() + self.stream_instance.auth_enc.overhead(), 0x00);
+		let sealed_length = try_err!(self.stream_instance.auth_enc.seal(&mut sealed, serialized.len(), key));
+		
+		// Write the index and the trailer
+		try_err_from!(self.writer.write_all(&sealed[.. sealed_length]), "Failed to write the archive-index".to_owned());
+		try_err_from!(self.writer.write_all(&self.offset.to_be_bytes()), "Failed to write the archive-trailer".to_owned());
+		try_err_from!(self.writer.write_all(&(sealed_length as u64).to_be_bytes()), "Failed to write the archive-trailer".to_owned());
+		try_err_from!(self.writer.flush());
+		Ok(self.writer)
+	}
+}
+
+/// Collects all regular files below `dir` as `(archive_path, file_path)`-pairs in sorted order
+fn walk(dir: &Path, prefix: &str, files: &mut Vec<(String, std::path::PathBuf)>) -> Result<(), Error<CpError>> {
+	let mut children = Vec::new();
+	for child in try_err_from!(std::fs::read_dir(dir), format!("Failed to read the directory \"{}\"", dir.display())) {
+		children.push(try_err_from!(child, format!("Failed to read the directory \"{}\"", dir.display())));
+	}
+	children.sort_by_key(|child| child.file_name());
+	
+	for child in children {
+		let name = try_err!(child.file_name().into_string(), CpError::InvalidParameter, format!("The filename \"{}\" is not valid UTF-8", child.path().display()));
+		let path = if prefix.is_empty() { name } else { format!("{}/{}", prefix, name) };
+		
+		let file_type = try_err_from!(child.file_type(), format!("Failed to read the file-type of \"{}\"", child.path().display()));
+		if file_type.is_dir() { try_err!(walk(&child.path(), &path, files)) }
+			else if file_type.is_file() { files.push((path, child.path())) }
+	}
+	Ok(())
+}
+
+
+
+/// Reads an archive and extracts single entries
+pub struct ArchiveReader<R: Read + Seek> {
+	reader: R,
+	stream_instance: crypto::StreamInstance,
+	key: crypto::Key,
+	entries: Vec<ArchiveEntry>,
+	max_compression_ratio: u64,
+	restore_special_bits: bool
+}
+impl<R: Read + Seek> ArchiveReader<R> {
+	/// Reads the archive-header, derives the key from `password` and opens the index
+	pub fn open<P: Into<crypto::Password>>(password: P, mut reader: R) -> Result<Self, Error<CpError>> {
+		// Read the header and derive the key
+		try_err_from!(reader.seek(SeekFrom::Start(0)));
+		let stream_instance = try_err!(stream::read_header(&mut io::StreamIo::new(&mut reader, std::io::sink())));
+		if !stream_instance.archive { throw_err!(CpError::Unsupported, "The stream is not an archive (use `stream::Decryptor` or `open`)") }
+		let key = try_err!(stream::derive_opening_key(&stream_instance, password.into()));
+		
+		// Read and validate the trailer
+		let header_length = stream_instance.as_serialized().into_der_encoded().len() as u64;
+		let length = try_err_from!(reader.seek(SeekFrom::End(0)));
+		if length < header_length + TRAILER_SIZE as u64 { throw_err!(CpError::InvalidData, "The archive is truncated") }
+		let mut trailer = [0u8; TRAILER_SIZE];
+		try_err_from!(reader.seek(SeekFrom::Start(length - TRAILER_SIZE as u64)));
+		try_err_from!(reader.read_exact(&mut trailer), "Failed to read the archive-trailer".to_owned());
+		
+		let (mut index_offset, mut index_length) = ([0u8; 8], [0u8; 8]);
+		index_offset.copy_from_slice(&trailer[.. 8]);
+		index_length.copy_from_slice(&trailer[8 ..]);
+		let (index_offset, index_length) = (u64::from_be_bytes(index_offset), u64::from_be_bytes(index_length));
+		if index_offset < header_length || index_offset.checked_add(index_length) != Some(length - TRAILER_SIZE as u64) {
+			throw_err!(CpError::InvalidData, "Invalid archive-trailer")
+		}
+		
+		// Read and open the index
+		let mut index = vec![0u8; index_length as usize];
+		try_err_from!(reader.seek(SeekFrom::Start(index_offset)));
+		try_err_from!(reader.read_exact(&mut index), "Failed to read the archive-index".to_owned());
+		let index_key = try_err!(derive_key(&stream_instance, &key, 0, INDEX_INFO));
+		let index_length = try_err!(stream_instance.auth_enc.open(&mut index, index_length as usize, index_key));
+		
+		// Parse and validate the entries
+		let index: asn1_der::DerObject = try_err!(asn1_der::DerObject::from_der_encoded(index[.. index_length].to_vec()), CpError::InvalidData);
+		let mut entries = Vec::new();
+		for entry in try_err!(Vec::<asn1_der::DerObject>::from_der_object(index), CpError::InvalidData) {
+			let entry = try_err!(ArchiveEntry::from_serialized(entry));
+			let is_valid = match entry.offset.checked_add(entry.sealed_length) {
+				Some(end) => entry.offset >= header_length && end <= index_offset,
+				None => false
+			};
+			if !is_valid {
+				throw_err!(CpError::InvalidData, format!("Invalid offset of the archive-entry \"{}\"", entry.path))
+			}
+			entries.push(entry);
+		}
+		
+		Ok(ArchiveReader{ reader, stream_instance, key, entries, max_compression_ratio: compression::DEFAULT_MAX_COMPRESSION_RATIO, restore_special_bits: false })
+	}
+	
+	/// Sets the maximum ratio between the decompressed and the compressed data of compressed entries
+	pub fn set_max_compression_ratio(&mut self, max_ratio: u64) {
+		self.max_compression_ratio = max_ratio
+	}
+	/// Sets whether `extract_to` restores the setuid-, setgid- and sticky-bits of the entries (see
+	/// `metadata::Metadata::apply`)
+	pub fn set_restore_special_bits(&mut self, restore_special_bits: bool) {
+		self.restore_special_bits = restore_special_bits
+	}
+	/// Returns the entries of the index
+	pub fn entries(&self) -> &[ArchiveEntry] {
+		&self.entries
+	}
+	
+	/// Opens the `index`th entry and writes its plaintext to `writer` (no other entry is read)
+	pub fn extract<W: Write>(&mut self, index: usize, writer: W) -> Result<(), Error<CpError>> {
+		let (offset, sealed_length) = match self.entries.get(index) {
+			Some(entry) => (entry.offset, entry.sealed_length),
+			None => throw_err!(CpError::InvalidParameter, format!("The archive has no entry #{}", index))
+		};
+		let key = try_err!(derive_key(&self.stream_instance, &self.key, index as u64, ENTRY_INFO));
+		
+		// Open the sealed file
+		try_err_from!(self.reader.seek(SeekFrom::Start(offset)));
+		let mut io = io::StreamIo::new((&mut self.reader).take(sealed_length), writer);
+		let mut decryptor = stream::Decryptor::without_password(&mut io);
+		decryptor.set_max_compression_ratio(self.max_compression_ratio);
+		decryptor.open_body(&self.stream_instance, &key)
+	}
+	
+	/// Extracts all entries that are `path` or below `path` (all entries if `path` is empty) into
+	/// `dir`, restores their modification-times and permissions (without the setuid-, setgid- and
+	/// sticky-bits unless `set_restore_special_bits` is set) and returns their paths
+	pub fn extract_to<D: AsRef<Path>>(&mut self, dir: D, path: &str) -> Result<Vec<String>, Error<CpError>> {
+		let selected: Vec<usize> = (0..self.entries.len()).filter(|index| self.entries[*index].matches(path)).collect();
+		if selected.is_empty() && !path.is_empty() { throw_err!(CpError::InvalidParameter, format!("The archive has no entry \"{}\"", path)) }
+		
+		let mut extracted = Vec::new();
+		for index in selected {
+			// Create the file (the path has been validated, so it stays within `dir`)
+			let entry = self.entries[index].clone();
+			let target = entry.path.split('/').fold(dir.as_ref().to_path_buf(), |target, component| target.join(component));
+			if let Some(parent) = target.parent() {
+				try_err_from!(std::fs::create_dir_all(parent), format!("Failed to create the directory \"{}\"", parent.display()));
+			}
+			let file = try_err_from!(std::fs::File::create(&target), format!("Failed to create \"{}\"", target.display()));
+			
+			// Extract the entry and restore its metadata
+			try_err!(self.extract(index, &file));
+			try_err!(entry.metadata.apply(&file, self.restore_special_bits));
+			extracted.push(entry.path);
+		}
+		Ok(extracted)
+	}
+}
\ No newline at end of file
@@ -0,0 +1,302 @@
+//! ASCII-armor for sealed streams
+//!
+//! The armored stream is the base64-encoding (RFC 4648 with padding) of the binary stream, wrapped
+//! into lines of at most `line_length` characters between `BEGIN_MARKER` and `END_MARKER`. The
+//! `DearmoringReader` detects the armor by the first non-whitespace bytes, ignores CRs and other
+//! whitespace and passes binary streams through unchanged.
+
+use std;
+use std::io::{ Read, Write };
+use super::{ Error, CpError };
+use super::error::into_io_error;
+
+
+/// The first line of an armored stream
+pub const BEGIN_MARKER: &str = "-----BEGIN CRYPTOPIPE SEALED STREAM-----";
+/// The last line of an armored stream
+pub const END_MARKER: &str = "-----END CRYPTOPIPE SEALED STREAM-----";
+/// The default maximum line-length
+pub const DEFAULT_LINE_LENGTH: usize = 64;
+/// The maximum length of a marker-line (including whitespace)
+const MAX_MARKER_LINE_LENGTH: usize = 256;
+/// The size of the read-buffer
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+/// The base64-alphabet
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+
+/// Encodes up to 3 bytes into 4 base64-characters (with padding)
+fn encode_quantum(data: &[u8]) -> [u8; 4] {
+	let mut bytes = [0u8; 3];
+	bytes[.. data.len()].copy_from_slice(data);
+	let bits = (u32::from(bytes[0]) << 16) | (u32::from(bytes[1]) << 8) | u32::from(bytes[2]);
+	
+	let mut quantum = [b'='; 4];
+	for (i, character) in quantum.iter_mut().enumerate().take(data.len() + 1) {
+		*character = ALPHABET[(bits >> (18 - 6 * i)) as usize & 0x3f]
+	}
+	quantum
+}
+
+/// Decodes 4 base64-characters into `buffer` and returns the amount of decoded bytes
+fn decode_quantum(quantum: &[u8; 4], buffer: &mut[u8; 3]) -> Result<usize, Error<CpError>> {
+	// Count and validate the padding
+	let padding = quantum.iter().rev().take_while(|character| **character == b'=').count();
+	if padding > 2 { throw_err!(CpError::InvalidData, "Invalid base64-padding") }
+	
+	let mut bits = 0u32;
+	for character in quantum[.. 4 - padding].iter() {
+		let value = match *character {
+			b'A' ..= b'Z' => character - b'A',
+			b'a' ..= b'z' => character - b'a' + 26,
+			b'0' ..= b'9' => character - b'0' + 52,
+			b'+' => 62,
+			b'/' => 63,
+			character => throw_err!(CpError::InvalidData, format!("Invalid base64-character (0x{:02x})", character))
+		};
+		bits = (bits << 6) | u32::from(value);
+	}
+	bits <<= 6 * padding as u32;
+	
+	buffer.copy_from_slice(&[(bits >> 16) as u8, (bits >> 8) as u8, bits as u8]);
+	Ok(3 - padding)
+}
+
+/// Returns whether `byte` is ignored between the base64-characters
+fn is_whitespace(byte: u8) -> bool {
+	byte == b' ' || byte == b'\t' || byte == b'\r' || byte == b'\n'
+}
+
+
+
+/// A `std::io::Write`-adapter that armors all data written to it into `writer`
+///
+/// __Dropping the writer without calling `finish()` results in an armored stream without the last
+/// characters and the end-marker.__
+pub struct ArmoredWriter<W: Write> {
+	writer: W,
+	line_length: usize,
+	column: usize,
+	pending: ([u8; 3], usize),
+	line: Vec<u8>
+}
+impl<W: Write> ArmoredWriter<W> {
+	/// Writes the begin-marker to `writer` and creates a new armored writer that wraps the lines after
+	/// `line_length` characters
+	pub fn new(mut writer: W, line_length: usize) -> Result<Self, Error<CpError>> {
+		if line_length == 0 { throw_err!(CpError::InvalidParameter, "The armor-line-length must not be zero") }
+		try_err_from!(writer.write_all(format!("{}\n", BEGIN_MARKER).as_bytes()), "Failed to write the armor-begin-marker".to_owned());
+		Ok(ArmoredWriter{ writer, line_length, column: 0, pending: ([0u8; 3], 0), line: Vec::new() })
+	}
+	
+	/// Encodes the remaining data, writes the end-marker, flushes the underlying writer and returns it
+	pub fn finish(mut self) -> Result<W, Error<CpError>> {
+		let (pending, pending_length) = self.pending;
+		if pending_length > 0 { self.push_quantum(&pending[.. pending_length]) }
+		if self.column > 0 { self.line.push(b'\n') }
+		self.line.extend_from_slice(format!("{}\n", END_MARKER).as_bytes());
+		
+		try_err_from!(self.writer.write_all(&self.line), "Failed to write the armor-end-marker".to_owned());
+		try_err_from!(self.writer.flush());
+		Ok(self.writer)
+	}
+	
+	/// Encodes `data` and appends the characters to the line-buffer
+	fn push_quantum(&mut self, data: &[u8]) {
+		for character in encode_quantum(data).iter() {
+			self.line.push(*character);
+			self.column += 1;
+			if self.column == self.line_length {
+				self.line.push(b'\n');
+				self.column = 0;
+			}
+		}
+	}
+}
+impl<W: Write> Write for ArmoredWriter<W> {
+	fn write(&mut self, data: &[u8]) -> std::io::Result<usize> {
+		// Complete the pending quantum and encode all complete quanta
+		let mut data = data;
+		let length = data.len();
+		while !data.is_empty() {
+			let to_copy = std::cmp::min(3 - self.pending.1, data.len());
+			self.pending.0[self.pending.1 .. self.pending.1 + to_copy].copy_from_slice(&data[.. to_copy]);
+			self.pending.1 += to_copy;
+			data = &data[to_copy ..];
+			
+			if self.pending.1 == 3 {
+				let pending = self.pending.0;
+				self.push_quantum(&pending);
+				self.pending.1 = 0;
+			}
+		}
+		
+		// Write the encoded characters
+		self.writer.write_all(&self.line)?;
+		self.line.clear();
+		Ok(length)
+	}
+	fn flush(&mut self) -> std::io::Result<()> {
+		self.writer.flush()
+	}
+}
+
+
+
+/// The state of a `DearmoringReader`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum State {
+	/// The first bytes have not been inspected yet
+	Detect,
+	/// The input is passed through unchanged
+	Binary,
+	/// The input is base64-decoded
+	Armored,
+	/// The end-marker has been read
+	Finished
+}
+
+/// A `std::io::Read`-adapter that removes the armor from armored streams and passes binary streams
+/// through unchanged
+pub struct DearmoringReader<R: Read> {
+	reader: R,
+	state: State,
+	input: (Vec<u8>, usize),
+	decoded: ([u8; 3], usize, usize),
+	padded: bool
+}
+impl<R: Read> DearmoringReader<R> {
+	/// Creates a new reader over `reader`
+	pub fn new(reader: R) -> Self {
+		DearmoringReader{ reader, state: State::Detect, input: (Vec::new(), 0), decoded: ([0u8; 3], 0, 0), padded: false }
+	}
+	
+	/// Returns whether the input is armored (detects the armor if necessary)
+	pub fn is_armored(&mut self) -> Result<bool, Error<CpError>> {
+		if self.state == State::Detect { try_err!(self.detect()) }
+		Ok(self.state != State::Binary)
+	}
+	
+	/// Reads more input into the input-buffer and returns `false` on EOF
+	fn fill(&mut self) -> Result<bool, Error<CpError>> {
+		// Drop the consumed input
+		let (ref mut input, ref mut position) = self.input;
+		input.drain(.. *position);
+		*position = 0;
+		
+		let length = input.len();
+		input.resize(length + READ_BUFFER_SIZE, 0x00);
+		let bytes_read = loop {
+			match self.reader.read(&mut input[length ..]) {
+				Ok(bytes_read) => break bytes_read,
+				Err(ref error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+				Err(error) => { input.truncate(length); throw_err!(error.into(), "Failed to read from input") }
+			}
+		};
+		input.truncate(length + bytes_read);
+		Ok(bytes_read > 0)
+	}
+	
+	/// Returns the next input-byte (or `None` on EOF)
+	fn next_byte(&mut self) -> Result<Option<u8>, Error<CpError>> {
+		if self.input.1 == self.input.0.len() && !try_err!(self.fill()) { return Ok(None) }
+		self.input.1 += 1;
+		Ok(Some(self.input.0[self.input.1 - 1]))
+	}
+	
+	/// Inspects the first non-whitespace bytes without consuming them (unless the input is armored;
+	/// then the leading whitespace and the begin-marker are consumed)
+	fn detect(&mut self) -> Result<(), Error<CpError>> {
+		let prefix = &BEGIN_MARKER.as_bytes()[.. 10];
+		self.state = loop {
+			// Compare the available non-whitespace bytes with the prefix of the begin-marker
+			let buffered = &self.input.0[self.input.1 ..];
+			let start = buffered.iter().position(|byte| !is_whitespace(*byte)).unwrap_or(buffered.len());
+			let available = std::cmp::min(buffered.len() - start, prefix.len());
+			if buffered[start .. start + available] != prefix[.. available] { break State::Binary }
+			if available == prefix.len() {
+				// Skip the leading whitespace
+				self.input.1 += start;
+				break State::Armored
+			}
+			
+			// Read more input (a short input cannot be armored)
+			if !try_err!(self.fill()) { break State::Binary }
+		};
+		if self.state == State::Armored { try_err!(self.read_marker(BEGIN_MARKER)) }
+		Ok(())
+	}
+	
+	/// Consumes the remaining line and validates that it is `marker` (surrounding whitespace is
+	/// ignored)
+	fn read_marker(&mut self, marker: &str) -> Result<(), Error<CpError>> {
+		let mut line = Vec::new();
+		while let Some(byte) = try_err!(self.next_byte()) {
+			if byte == b'\n' { break }
+			if line.len() == MAX_MARKER_LINE_LENGTH { throw_err!(CpError::InvalidData, "Invalid armor-marker") }
+			line.push(byte);
+		}
+		
+		let line = String::from_utf8_lossy(&line);
+		if line.trim() != marker { throw_err!(CpError::InvalidData, format!("Invalid armor-marker (expected \"{}\")", marker)) }
+		Ok(())
+	}
+	
+	/// Decodes the next quantum into the decoded-buffer (or reads the end-marker)
+	fn decode_next(&mut self) -> Result<(), Error<CpError>> {
+		let (mut quantum, mut length) = ([0u8; 4], 0);
+		while length < 4 {
+			match try_err!(self.next_byte()) {
+				None => throw_err!(CpError::InvalidData, "The armored stream is truncated (the end-marker is missing)"),
+				Some(byte) if is_whitespace(byte) => continue,
+				Some(b'-') if length == 0 => {
+					// Read the end-marker (the `-` has already been consumed)
+					self.input.1 -= 1;
+					try_err!(self.read_marker(END_MARKER));
+					self.state = State::Finished;
+					return Ok(())
+				},
+				Some(_) if self.padded => throw_err!(CpError::InvalidData, "Unexpected data after the base64-padding"),
+				Some(byte) => { quantum[length] = byte; length += 1 }
+			}
+		}
+		
+		let decoded_length = try_err!(decode_quantum(&quantum, &mut self.decoded.0));
+		self.decoded.1 = 0;
+		self.decoded.2 = decoded_length;
+		self.padded = decoded_length < 3;
+		Ok(())
+	}
+}
+impl<R: Read> Read for DearmoringReader<R> {
+	fn read(&mut self, buffer: &mut[u8]) -> std::io::Result<usize> {
+		if self.state == State::Detect { self.detect().map_err(into_io_error)? }
+		
+		// Pass binary data through (the inspected bytes are returned first)
+		if self.state == State::Binary {
+			if self.input.1 < self.input.0.len() {
+				let to_copy = std::cmp::min(buffer.len(), self.input.0.len() - self.input.1);
+				buffer[.. to_copy].copy_from_slice(&self.input.0[self.input.1 .. self.input.1 + to_copy]);
+				self.input.1 += to_copy;
+				return Ok(to_copy)
+			}
+			return self.reader.read(buffer)
+		}
+		
+		// Decode armored data
+		let mut written = 0;
+		while written < buffer.len() {
+			if self.decoded.1 == self.decoded.2 {
+				if self.state == State::Finished { break }
+				self.decode_next().map_err(into_io_error)?;
+				continue
+			}
+			
+			let to_copy = std::cmp::min(buffer.len() - written, self.decoded.2 - self.decoded.1);
+			buffer[written .. written + to_copy].copy_from_slice(&self.decoded.0[self.decoded.1 .. self.decoded.1 + to_copy]);
+			self.decoded.1 += to_copy;
+			written += to_copy;
+		}
+		Ok(written)
+	}
+}
\ No newline at end of file
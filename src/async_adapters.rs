@@ -0,0 +1,340 @@
+use std;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::future::Future;
+use std::task::{ Context, Poll };
+use super::{ Error, CpError };
+use super::{ crypto, stream };
+use super::error::into_io_error;
+use super::asn1_der::IntoDerEncoded;
+use super::tokio::io::{ AsyncRead, AsyncWrite, ReadBuf };
+use super::tokio::task::{ spawn_blocking, JoinHandle };
+
+
+/// Returns `Poll::Pending` or evaluates to the result of a ready `Poll`
+macro_rules! try_ready {
+	($poll:expr) => (match $poll {
+		Poll::Ready(result) => result,
+		Poll::Pending => return Poll::Pending
+	});
+}
+
+/// Unwraps the result of a finished blocking task
+fn join_result<T>(result: Result<std::io::Result<T>, super::tokio::task::JoinError>) -> std::io::Result<T> {
+	match result {
+		Ok(result) => result,
+		Err(error) => Err(std::io::Error::new(std::io::ErrorKind::Other, error))
+	}
+}
+
+/// Derives the base-key of a parsed stream and verifies it on a blocking thread
+fn spawn_derive(stream_instance: Arc<crypto::StreamInstance>, password: crypto::Password) -> JoinHandle<std::io::Result<crypto::Key>> {
+	spawn_blocking(move || stream::derive_opening_key(&stream_instance, password).map_err(into_io_error))
+}
+
+
+
+/// An `AsyncWrite`-adapter that seals all data written to it into `writer`
+///
+/// This is the asynchronous counterpart to `adapters::SealingWriter`: the key-derivation and the
+/// sealing of each chunk are performed on tokio's blocking thread-pool so that the reactor is never
+/// blocked. The last chunk is sealed on `shutdown()`; __dropping the writer without shutting it
+/// down results in a truncated stream that cannot be opened.__
+pub struct AsyncSealingWriter<W: AsyncWrite + Unpin> {
+	writer: W,
+	pending: Option<(crypto::StreamInstance, crypto::Password)>,
+	stream_instance: Option<Arc<crypto::StreamInstance>>,
+	key: Option<Arc<crypto::Key>>,
+	deriving: Option<JoinHandle<std::io::Result<(crypto::StreamInstance, crypto::Key)>>>,
+	counter: u64,
+	chunk: (Option<Vec<u8>>, usize),
+	sealing: Option<JoinHandle<std::io::Result<(Vec<u8>, usize)>>>,
+	output: Option<(Vec<u8>, usize, usize)>,
+	is_last_sealed: bool
+}
+impl<W: AsyncWrite + Unpin> AsyncSealingWriter<W> {
+	/// Creates a new sealing-writer (fails if `stream_instance` has extensions that require the
+	/// `Encryptor`); the key is derived from `password` once the writer is polled for the first time
+	pub fn new<P: Into<crypto::Password>>(password: P, writer: W, stream_instance: crypto::StreamInstance) -> Result<Self, Error<CpError>> {
+		try_err!(stream::require_plain_chunks(&stream_instance));
+		
+		let chunk_buffer = vec![0u8; stream::CHUNK_DATA_SIZE + stream_instance.auth_enc.overhead()];
+		Ok(AsyncSealingWriter {
+			writer, pending: Some((stream_instance, password.into())), stream_instance: None, key: None, deriving: None,
+			counter: 0, chunk: (Some(chunk_buffer), 0), sealing: None, output: None, is_last_sealed: false
+		})
+	}
+	
+	/// Returns the underlying writer
+	pub fn into_inner(self) -> W {
+		self.writer
+	}
+	
+	/// Drives all pending operations and returns `Poll::Ready(Ok(()))` if the chunk-buffer is
+	/// available again
+	fn poll_pending(&mut self, cx: &mut Context) -> Poll<std::io::Result<()>> {
+		loop {
+			// Start or poll the key-derivation (the header contains the key-check-value and is written
+			// afterwards)
+			if let Some((mut stream_instance, password)) = self.pending.take() {
+				self.deriving = Some(spawn_blocking(move || -> std::io::Result<(crypto::StreamInstance, crypto::Key)> {
+					let key = stream::derive_sealing_key(&mut stream_instance, password).map_err(into_io_error)?;
+					Ok((stream_instance, key))
+				}));
+			}
+			if let Some(derived) = match self.deriving { Some(ref mut deriving) => Some(try_ready!(Pin::new(deriving).poll(cx))), None => None } {
+				let (stream_instance, key) = join_result(derived)?;
+				let header = stream_instance.as_serialized().into_der_encoded();
+				let header_length = header.len();
+				
+				self.output = Some((header, 0, header_length));
+				self.stream_instance = Some(Arc::new(stream_instance));
+				self.key = Some(Arc::new(key));
+				self.deriving = None;
+				continue
+			}
+			
+			// Poll the sealing-task
+			if let Some(sealed) = match self.sealing { Some(ref mut sealing) => Some(try_ready!(Pin::new(sealing).poll(cx))), None => None } {
+				let (buffer, sealed_length) = join_result(sealed)?;
+				self.output = Some((buffer, 0, sealed_length));
+				self.sealing = None;
+				continue
+			}
+			
+			// Write the pending output
+			if let Some((buffer, mut written, length)) = self.output.take() {
+				while written < length {
+					match Pin::new(&mut self.writer).poll_write(cx, &buffer[written .. length]) {
+						Poll::Ready(Ok(0)) => return Poll::Ready(Err(std::io::ErrorKind::WriteZero.into())),
+						Poll::Ready(Ok(bytes_written)) => written += bytes_written,
+						Poll::Ready(Err(error)) => return Poll::Ready(Err(error)),
+						Poll::Pending => {
+							self.output = Some((buffer, written, length));
+							return Poll::Pending
+						}
+					}
+				}
+				
+				// Reuse the buffer of a sealed chunk as chunk-buffer
+				if self.chunk.0.is_none() { self.chunk.0 = Some(buffer) }
+				continue
+			}
+			
+			return Poll::Ready(Ok(()))
+		}
+	}
+	
+	/// Starts sealing the buffered chunk on a blocking thread
+	fn start_sealing(&mut self, is_last: bool) {
+		// Take the buffer and the current state
+		let (mut buffer, chunk_length) = (self.chunk.0.take().expect("The chunk-buffer is not available"), self.chunk.1);
+		let stream_instance = self.stream_instance.clone().expect("The stream-instance is not available");
+		let (key, counter) = (self.key.clone().expect("The key is not available"), self.counter);
+		self.counter += 1;
+		self.chunk.1 = 0;
+		
+		// Seal the chunk
+		self.sealing = Some(spawn_blocking(move || -> std::io::Result<(Vec<u8>, usize)> {
+			let sealed_length = stream::seal_chunk(&stream_instance, &key, counter, is_last, &mut buffer, chunk_length).map_err(into_io_error)?;
+			Ok((buffer, sealed_length))
+		}));
+	}
+}
+impl<W: AsyncWrite + Unpin> AsyncWrite for AsyncSealingWriter<W> {
+	fn poll_write(self: Pin<&mut Self>, cx: &mut Context, data: &[u8]) -> Poll<std::io::Result<usize>> {
+		let this = self.get_mut();
+		if this.is_last_sealed { return Poll::Ready(Err(std::io::Error::new(std::io::ErrorKind::Other, "The stream is already finished"))) }
+		try_ready!(this.poll_pending(cx))?;
+		if data.is_empty() { return Poll::Ready(Ok(0)) }
+		
+		// A full chunk is only sealed if more data follows because otherwise it is the last chunk
+		if this.chunk.1 == stream::CHUNK_DATA_SIZE {
+			this.start_sealing(false);
+			try_ready!(this.poll_pending(cx))?;
+		}
+		
+		// Buffer data
+		let (buffer, chunk_length) = (this.chunk.0.as_mut().expect("The chunk-buffer is not available"), &mut this.chunk.1);
+		let to_copy = std::cmp::min(stream::CHUNK_DATA_SIZE - *chunk_length, data.len());
+		buffer[*chunk_length .. *chunk_length + to_copy].copy_from_slice(&data[.. to_copy]);
+		*chunk_length += to_copy;
+		Poll::Ready(Ok(to_copy))
+	}
+	
+	fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<std::io::Result<()>> {
+		let this = self.get_mut();
+		try_ready!(this.poll_pending(cx))?;
+		Pin::new(&mut this.writer).poll_flush(cx)
+	}
+	
+	fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<std::io::Result<()>> {
+		let this = self.get_mut();
+		try_ready!(this.poll_pending(cx))?;
+		
+		// Seal the last chunk
+		if !this.is_last_sealed {
+			this.start_sealing(true);
+			this.is_last_sealed = true;
+			try_ready!(this.poll_pending(cx))?;
+		}
+		Pin::new(&mut this.writer).poll_shutdown(cx)
+	}
+}
+
+
+
+/// An `AsyncRead`-adapter that opens a sealed stream from `reader` and yields the verified
+/// plaintext
+///
+/// This is the asynchronous counterpart to `adapters::OpeningReader`: the key-derivation and the
+/// opening of each chunk are performed on tokio's blocking thread-pool so that the reactor is never
+/// blocked.
+pub struct AsyncOpeningReader<R: AsyncRead + Unpin> {
+	reader: R,
+	input: (Vec<u8>, bool),
+	password: Option<crypto::Password>,
+	stream_instance: Option<Arc<crypto::StreamInstance>>,
+	key: Option<Arc<crypto::Key>>,
+	deriving: Option<JoinHandle<std::io::Result<crypto::Key>>>,
+	counter: u64,
+	opening: Option<JoinHandle<std::io::Result<(Vec<u8>, usize, bool)>>>,
+	plain: (Vec<u8>, usize, usize),
+	is_last_opened: bool
+}
+impl<R: AsyncRead + Unpin> AsyncOpeningReader<R> {
+	/// Creates a new opening-reader; the stream-header is read and the key is derived from
+	/// `password` once the reader is polled for the first time
+	pub fn new<P: Into<crypto::Password>>(password: P, reader: R) -> Self {
+		AsyncOpeningReader {
+			reader, input: (Vec::new(), false), password: Some(password.into()), stream_instance: None, key: None, deriving: None,
+			counter: 0, opening: None, plain: (Vec::new(), 0, 0), is_last_opened: false
+		}
+	}
+	
+	/// Returns the underlying reader
+	pub fn into_inner(self) -> R {
+		self.reader
+	}
+	
+	/// Reads until the input-buffer contains at least `length` bytes or EOF is reached
+	fn poll_fill(&mut self, cx: &mut Context, length: usize) -> Poll<std::io::Result<()>> {
+		while self.input.0.len() < length && !self.input.1 {
+			// Read into the unused part of the buffer
+			let filled = self.input.0.len();
+			self.input.0.resize(length, 0x00);
+			let poll = {
+				let mut read_buffer = ReadBuf::new(&mut self.input.0[filled ..]);
+				match Pin::new(&mut self.reader).poll_read(cx, &mut read_buffer) {
+					Poll::Ready(Ok(())) => Poll::Ready(Ok(read_buffer.filled().len())),
+					Poll::Ready(Err(error)) => Poll::Ready(Err(error)),
+					Poll::Pending => Poll::Pending
+				}
+			};
+			
+			// Adjust the buffer to the amount of bytes read
+			match poll {
+				Poll::Ready(Ok(bytes_read)) => {
+					self.input.0.truncate(filled + bytes_read);
+					self.input.1 = bytes_read == 0;
+				},
+				Poll::Ready(Err(error)) => {
+					self.input.0.truncate(filled);
+					return Poll::Ready(Err(error))
+				},
+				Poll::Pending => {
+					self.input.0.truncate(filled);
+					return Poll::Pending
+				}
+			}
+		}
+		Poll::Ready(Ok(()))
+	}
+	
+	/// Reads and parses the stream-header
+	fn poll_header(&mut self, cx: &mut Context) -> Poll<std::io::Result<()>> {
+		// Determine the stream-header-length
+		let header_length = loop {
+			match crypto::StreamInstance::try_parse_length(&self.input.0).map_err(into_io_error)? {
+				Some(header_length) => break header_length,
+				None if self.input.1 => return Poll::Ready(Err(std::io::ErrorKind::UnexpectedEof.into())),
+				None => {
+					let length = self.input.0.len() + 1;
+					try_ready!(self.poll_fill(cx, length))?
+				}
+			}
+		};
+		
+		// Read the remaining header-data and parse the header
+		try_ready!(self.poll_fill(cx, header_length))?;
+		if self.input.0.len() < header_length { return Poll::Ready(Err(std::io::ErrorKind::UnexpectedEof.into())) }
+		
+		let remaining = self.input.0.split_off(header_length);
+		let header = std::mem::replace(&mut self.input.0, remaining);
+		let stream_instance = crypto::StreamInstance::from_serialized(header).map_err(into_io_error)?;
+		stream::require_plain_chunks(&stream_instance).map_err(into_io_error)?;
+		self.stream_instance = Some(Arc::new(stream_instance));
+		Poll::Ready(Ok(()))
+	}
+	
+	/// Drives all pending operations until there is either new plaintext available or the last
+	/// chunk was opened
+	fn poll_pending(&mut self, cx: &mut Context) -> Poll<std::io::Result<()>> {
+		while self.plain.1 == self.plain.2 && !self.is_last_opened {
+			// Read the stream-header
+			if self.stream_instance.is_none() {
+				try_ready!(self.poll_header(cx))?;
+				continue
+			}
+			let stream_instance = self.stream_instance.clone().expect("The stream-instance is not available");
+			
+			// Start or poll the key-derivation
+			if let Some(password) = self.password.take() {
+				self.deriving = Some(spawn_derive(stream_instance.clone(), password));
+			}
+			if let Some(key) = match self.deriving { Some(ref mut deriving) => Some(try_ready!(Pin::new(deriving).poll(cx))), None => None } {
+				self.key = Some(Arc::new(join_result(key)?));
+				self.deriving = None;
+				continue
+			}
+			
+			// Poll the opening-task
+			if let Some(opened) = match self.opening { Some(ref mut opening) => Some(try_ready!(Pin::new(opening).poll(cx))), None => None } {
+				let (buffer, data_length, is_last) = join_result(opened)?;
+				self.plain = (buffer, 0, data_length);
+				self.is_last_opened = is_last;
+				self.opening = None;
+				continue
+			}
+			
+			// Read the next chunk; the chunk is the last chunk if no data follows
+			let sealed_chunk_size = stream::CHUNK_DATA_SIZE + stream_instance.auth_enc.overhead();
+			try_ready!(self.poll_fill(cx, sealed_chunk_size + 1))?;
+			let is_last = self.input.0.len() <= sealed_chunk_size;
+			
+			let remaining = if is_last { Vec::new() } else { self.input.0.split_off(sealed_chunk_size) };
+			let mut buffer = std::mem::replace(&mut self.input.0, remaining);
+			let (key, counter, chunk_length) = (self.key.clone().expect("The key is not available"), self.counter, buffer.len());
+			self.counter += 1;
+			
+			// Open the chunk
+			self.opening = Some(spawn_blocking(move || -> std::io::Result<(Vec<u8>, usize, bool)> {
+				let data_length = stream::open_chunk(&stream_instance, &key, counter, is_last, &mut buffer, chunk_length).map_err(into_io_error)?;
+				Ok((buffer, data_length, is_last))
+			}));
+		}
+		Poll::Ready(Ok(()))
+	}
+}
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncOpeningReader<R> {
+	fn poll_read(self: Pin<&mut Self>, cx: &mut Context, buffer: &mut ReadBuf) -> Poll<std::io::Result<()>> {
+		let this = self.get_mut();
+		try_ready!(this.poll_pending(cx))?;
+		
+		// Copy the plaintext (if there is nothing left, the stream is finished)
+		let to_copy = std::cmp::min(this.plain.2 - this.plain.1, buffer.remaining());
+		buffer.put_slice(&this.plain.0[this.plain.1 .. this.plain.1 + to_copy]);
+		this.plain.1 += to_copy;
+		Poll::Ready(Ok(()))
+	}
+}
\ No newline at end of file
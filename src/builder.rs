@@ -0,0 +1,257 @@
+use super::{ Error, CpError };
+use std::io::{ Read, Write, Seek };
+use std::path::Path;
+use super::{ crypto, io, stream, fec, compression, padding, metadata, archive, volume };
+
+
+/// The default Argon2i-time-cost
+pub const DEFAULT_ARGON2I_TIME_COST: u32 = 12;
+/// The default Argon2i-memory-cost in MiB
+pub const DEFAULT_ARGON2I_MEMORY_COST_MIB: u32 = 512;
+/// The default Argon2i-parallelism
+pub const DEFAULT_ARGON2I_PARALLELISM: u32 = 4;
+/// The recommended minimum password-entropy in bits (see `SealBuilder::min_password_entropy`)
+pub const DEFAULT_MIN_PASSWORD_ENTROPY: f64 = 60.0;
+
+
+
+/// A builder to configure a sealing-operation
+///
+/// If no algorithms are set explicitly, Argon2i (with the default parameters), HMAC-SHA2-512 and
+/// ChaCha20+Poly1305 are used. The password-strength is not checked unless a minimum entropy is
+/// set.
+pub struct SealBuilder {
+	password: crypto::Password,
+	min_password_entropy: Option<f64>,
+	parity: Option<fec::Parity>,
+	compression: Option<compression::Compression>,
+	padding: Option<padding::Padding>,
+	metadata: Option<metadata::Metadata>,
+	appendable: bool,
+	seek_index: bool,
+	pbkdf: Option<Box<crypto::Pbkdf>>,
+	kdf: Option<Box<crypto::Kdf>>,
+	auth_enc: Option<Box<crypto::AuthEnc>>,
+	progress: Option<Box<FnMut(&stream::Position)>>
+}
+impl SealBuilder {
+	/// Creates a new builder that seals the data with `password`
+	pub fn new<P: Into<crypto::Password>>(password: P) -> Self {
+		SealBuilder{ password: password.into(), min_password_entropy: None, parity: None, compression: None, padding: None, metadata: None, appendable: false, seek_index: false, pbkdf: None, kdf: None, auth_enc: None, progress: None }
+	}
+	
+	/// Rejects passwords with an estimated entropy below `bits` (see
+	/// `crypto::Password::estimate_entropy`) with `CpError::InvalidParameter`
+	pub fn min_password_entropy(mut self, bits: f64) -> Self {
+		self.min_password_entropy = Some(bits);
+		self
+	}
+	
+	/// Appends parity-blocks to the stream so that damaged chunks can be repaired (see `fec`)
+	pub fn parity(mut self, parity: fec::Parity) -> Self {
+		self.parity = Some(parity);
+		self
+	}
+	
+	/// Compresses the plaintext before it is sealed (see `compression`)
+	pub fn compression(mut self, compression: compression::Compression) -> Self {
+		self.compression = Some(compression);
+		self
+	}
+	
+	/// Pads the sealed plaintext to hide its exact length (see `padding`)
+	pub fn padding(mut self, padding: padding::Padding) -> Self {
+		self.padding = Some(padding);
+		self
+	}
+	
+	/// Seals `metadata` as encrypted first chunk (see `metadata`)
+	pub fn metadata(mut self, metadata: metadata::Metadata) -> Self {
+		self.metadata = Some(metadata);
+		self
+	}
+	
+	/// Seals the stream in the appendable chunk-format so that data can be appended later (see
+	/// `append`; cannot be combined with parity-blocks, compression or padding)
+	pub fn appendable(mut self, appendable: bool) -> Self {
+		self.appendable = appendable;
+		self
+	}
+	
+	/// Appends an authenticated seek-index to the stream so that it can be read at random offsets (see
+	/// `seekable`; cannot be combined with compression or the appendable chunk-format)
+	pub fn seek_index(mut self, seek_index: bool) -> Self {
+		self.seek_index = seek_index;
+		self
+	}
+	
+	/// Sets the PBKDF-instance
+	pub fn pbkdf(mut self, pbkdf: Box<crypto::Pbkdf>) -> Self {
+		self.pbkdf = Some(pbkdf);
+		self
+	}
+	/// Uses Argon2i with a random nonce and the given parameters as PBKDF
+	pub fn argon2i(self, time_cost: u32, memory_cost_mib: u32, parallelism: u32) -> Self {
+		self.pbkdf(crypto::pbkdf::Argon2i::new(time_cost, memory_cost_mib, parallelism))
+	}
+	/// Sets the KDF-instance
+	pub fn kdf(mut self, kdf: Box<crypto::Kdf>) -> Self {
+		self.kdf = Some(kdf);
+		self
+	}
+	/// Sets the authenticated-encryption-instance
+	pub fn auth_enc(mut self, auth_enc: Box<crypto::AuthEnc>) -> Self {
+		self.auth_enc = Some(auth_enc);
+		self
+	}
+	
+	/// Enforces the password-policy and creates the stream-instance
+	fn into_parts(self) -> Result<(crypto::Password, crypto::StreamInstance, Option<metadata::Metadata>), Error<CpError>> {
+		// Enforce the password-policy
+		if let Some(min_entropy) = self.min_password_entropy {
+			let entropy = self.password.estimate_entropy();
+			if entropy < min_entropy { throw_err!(CpError::InvalidParameter, format!("The password is too weak (~{:.0} bits of entropy estimated but at least {:.0} bits are required)", entropy, min_entropy)) }
+		}
+		
+		let mut stream_instance = crypto::StreamInstance::new(
+			self.pbkdf.unwrap_or_else(|| crypto::pbkdf::Argon2i::new(
+				DEFAULT_ARGON2I_TIME_COST, DEFAULT_ARGON2I_MEMORY_COST_MIB, DEFAULT_ARGON2I_PARALLELISM
+			)),
+			self.kdf.unwrap_or_else(|| crypto::kdf::HmacSha2512::new()),
+			self.auth_enc.unwrap_or_else(|| crypto::auth_enc::ChaCha20Poly1305::new())
+		);
+		stream_instance.parity = self.parity;
+		stream_instance.compression = self.compression;
+		stream_instance.padding = self.padding;
+		stream_instance.appendable = self.appendable;
+		if self.appendable && (self.parity.is_some() || self.compression.is_some() || self.padding.is_some()) {
+			throw_err!(CpError::InvalidParameter, "Appendable streams cannot have parity-blocks, compression or padding")
+		}
+		stream_instance.seek_index = self.seek_index;
+		if self.seek_index && (self.compression.is_some() || self.appendable) {
+			throw_err!(CpError::InvalidParameter, "Streams with a seek-index cannot be compressed or appendable")
+		}
+		Ok((self.password, stream_instance, self.metadata))
+	}
+	
+	/// Sets a callback that is invoked with the current position after every sealed chunk (see
+	/// `stream::Encryptor::set_progress`; archives do not report their progress)
+	pub fn progress(mut self, progress: Box<FnMut(&stream::Position)>) -> Self {
+		self.progress = Some(progress);
+		self
+	}
+	
+	/// Derives the key and creates an `Encryptor` over `io`
+	pub fn build<'a>(mut self, io: &'a mut io::Io) -> Result<stream::Encryptor<'a>, Error<CpError>> {
+		let progress = self.progress.take();
+		let (password, stream_instance, metadata) = try_err!(self.into_parts());
+		let mut encryptor = try_err!(stream::Encryptor::new(password, io, stream_instance));
+		if let Some(metadata) = metadata { encryptor.set_metadata(metadata) }
+		if let Some(progress) = progress { encryptor.set_progress(progress) }
+		Ok(encryptor)
+	}
+	
+	/// Derives the key and creates an `archive::ArchiveWriter` that writes the archive to `writer`
+	/// (every entry has its own metadata, so the stream-metadata must not be set)
+	pub fn build_archive<W: Write>(self, writer: W) -> Result<archive::ArchiveWriter<W>, Error<CpError>> {
+		let (password, stream_instance, metadata) = try_err!(self.into_parts());
+		if metadata.is_some() { throw_err!(CpError::InvalidParameter, "Archives cannot have stream-metadata") }
+		archive::ArchiveWriter::new(password, writer, stream_instance)
+	}
+	
+	/// Seals the entire input of `reader` into volumes of at most `volume_size` bytes (`<prefix>.000`,
+	/// `<prefix>.001` etc.; see `volume`) and returns the amount of volumes
+	pub fn seal_volumes<R: Read, P: AsRef<Path>>(mut self, reader: R, prefix: P, volume_size: u64) -> Result<u64, Error<CpError>> {
+		let progress = self.progress.take();
+		let (password, mut stream_instance, metadata) = try_err!(self.into_parts());
+		let writer = try_err!(volume::VolumeWriter::new(prefix, volume_size, stream_instance.auth_enc.overhead()));
+		let key = try_err!(stream::derive_sealing_key(&mut stream_instance, password));
+		let volume_key = try_err!(volume::derive_volume_key(&stream_instance, &key));
+		let finishing_instance = try_err!(stream_instance.try_clone());
+		
+		// Seal the stream into the volumes and write the volume-headers
+		let mut io = volume::VolumeIo::new(reader, writer);
+		{
+			let mut encryptor = stream::Encryptor::with_key(key, &mut io, stream_instance);
+			if let Some(metadata) = metadata { encryptor.set_metadata(metadata) }
+			if let Some(progress) = progress { encryptor.set_progress(progress) }
+			try_err!(encryptor.runloop());
+		}
+		io.into_writer().finish(&finishing_instance, &volume_key)
+	}
+	
+	/// Seals the entire input of `io` and writes the sealed stream to `io`
+	pub fn seal(self, io: &mut io::Io) -> Result<(), Error<CpError>> {
+		try_err!(self.build(io)).runloop()
+	}
+}
+
+
+
+/// A builder to configure an opening-operation
+///
+/// The algorithms and their parameters are read from the stream-header.
+pub struct OpenBuilder {
+	password: crypto::Password,
+	salvage: stream::Salvage,
+	max_compression_ratio: u64,
+	progress: Option<Box<FnMut(&stream::Position)>>
+}
+impl OpenBuilder {
+	/// Creates a new builder that opens the data with `password`
+	pub fn new<P: Into<crypto::Password>>(password: P) -> Self {
+		OpenBuilder{ password: password.into(), salvage: stream::Salvage::Off, max_compression_ratio: compression::DEFAULT_MAX_COMPRESSION_RATIO, progress: None }
+	}
+	
+	/// Skips chunks that fail authentication instead of aborting (see
+	/// `stream::Decryptor::salvage_report`)
+	pub fn salvage(mut self, salvage: stream::Salvage) -> Self {
+		self.salvage = salvage;
+		self
+	}
+	
+	/// Sets the maximum ratio between the decompressed and the compressed data of compressed streams
+	/// (the default is `compression::DEFAULT_MAX_COMPRESSION_RATIO`)
+	pub fn max_compression_ratio(mut self, max_ratio: u64) -> Self {
+		self.max_compression_ratio = max_ratio;
+		self
+	}
+	
+	/// Sets a callback that is invoked with the current position after every opened chunk (see
+	/// `stream::Decryptor::set_progress`; archives do not report their progress)
+	pub fn progress(mut self, progress: Box<FnMut(&stream::Position)>) -> Self {
+		self.progress = Some(progress);
+		self
+	}
+	
+	/// Creates a `Decryptor` over `io`
+	pub fn build<'a>(self, io: &'a mut io::Io) -> Result<stream::Decryptor<'a>, Error<CpError>> {
+		let mut decryptor = try_err!(stream::Decryptor::new(self.password, io));
+		decryptor.set_salvage(self.salvage);
+		decryptor.set_max_compression_ratio(self.max_compression_ratio);
+		if let Some(progress) = self.progress { decryptor.set_progress(progress) }
+		Ok(decryptor)
+	}
+	
+	/// Reads the archive-header and the index from `reader` and creates an `archive::ArchiveReader`
+	pub fn open_archive<R: Read + Seek>(self, reader: R) -> Result<archive::ArchiveReader<R>, Error<CpError>> {
+		if self.salvage != stream::Salvage::Off { throw_err!(CpError::Unsupported, "Archives cannot be salvaged") }
+		let mut archive_reader = try_err!(archive::ArchiveReader::open(self.password, reader));
+		archive_reader.set_max_compression_ratio(self.max_compression_ratio);
+		Ok(archive_reader)
+	}
+	
+	/// Authenticates the volume-headers, opens the stream from `volumes` and writes the plaintext to
+	/// `writer`
+	pub fn open_volumes<W: Write>(self, volumes: &volume::VolumeSet, writer: W) -> Result<(), Error<CpError>> {
+		let mut io = io::StreamIo::new(volumes.reader(), writer);
+		let mut decryptor = try_err!(self.build(&mut io));
+		decryptor.set_key_verifier(Box::new(|stream_instance, base_key| volumes.verify(stream_instance, base_key)));
+		decryptor.runloop()
+	}
+	
+	/// Opens the entire sealed stream from `io` and writes the plaintext to `io`
+	pub fn open(self, io: &mut io::Io) -> Result<(), Error<CpError>> {
+		try_err!(self.build(io)).runloop()
+	}
+}
\ No newline at end of file
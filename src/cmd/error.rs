@@ -0,0 +1,137 @@
+//! Error-reporting for the command-line-application
+
+use std;
+use cli;
+use etrace::Error;
+use crypto_pipe::CpError;
+use crypto_pipe::stream::Position;
+
+
+/// The switch that selects the error-format
+pub const ERROR_FORMAT_SWITCH: &str = "--error-format=";
+
+
+
+/// The format in which errors are printed to stderr
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ErrorFormat {
+	/// A human-readable message (followed by the help-text on CLI-errors)
+	Text,
+	/// A single-line JSON-object
+	Json
+}
+impl ErrorFormat {
+	/// Reads the error-format directly from the command-line-arguments (the CLI-parser cannot be
+	/// used because the format must also apply to errors of the CLI-parser itself)
+	///
+	/// Unknown formats fall back to `ErrorFormat::Text`; they are rejected later by `validate`.
+	pub fn from_args() -> Self {
+		let format = std::env::args().skip(1)
+			.filter_map(|arg| if arg.starts_with(ERROR_FORMAT_SWITCH) { Some(arg[ERROR_FORMAT_SWITCH.len() ..].to_string()) } else { None })
+			.last();
+		match format.as_ref().map(|format| format.as_str()) {
+			Some("json") => ErrorFormat::Json,
+			_ => ErrorFormat::Text
+		}
+	}
+}
+
+/// Creates the parser for the error-format-switch (which is accepted by every verb)
+pub fn switch() -> (&'static str, cli::SwitchParser) {
+	(ERROR_FORMAT_SWITCH, cli::SwitchParser::with_default("text".to_string(), &cli::parsers::parse_from_str::<String>))
+}
+
+/// Validates the value of the error-format-switch
+pub fn validate(value: &str) -> Result<(), Error<CpError>> {
+	match value {
+		"text" | "json" => Ok(()),
+		format => throw_err!(CpError::CliError, format!("Unsupported error-format \"{}\"", format))
+	}
+}
+
+
+
+/// Returns the documented process-exit-code for `error` (`0` is success)
+pub fn exit_code(error: &CpError) -> i32 {
+	match *error {
+		CpError::Other(_) => 1,
+		CpError::CliError => 2,
+		CpError::IOError(_, _) => 3,
+		CpError::InvalidData => 4,
+		CpError::WrongPassword => 5,
+		CpError::ResourceError => 6,
+		CpError::InvalidParameter => 7,
+		CpError::Unsupported => 8
+	}
+}
+
+/// Returns the name of the error-class
+pub fn kind_name(error: &CpError) -> &'static str {
+	match *error {
+		CpError::Other(_) => "Other",
+		CpError::CliError => "CliError",
+		CpError::IOError(_, _) => "IOError",
+		CpError::InvalidData => "InvalidData",
+		CpError::WrongPassword => "WrongPassword",
+		CpError::ResourceError => "ResourceError",
+		CpError::InvalidParameter => "InvalidParameter",
+		CpError::Unsupported => "Unsupported"
+	}
+}
+
+
+
+/// Formats `error` (and the stream-`position` if any) as human-readable text
+pub fn to_text(error: &Error<CpError>, position: Option<Position>) -> String {
+	let mut text = error.to_string();
+	if let Some(position) = position {
+		text += &format!(
+			"\n\nThe error occurred in chunk #{} (ciphertext-offset: {} bytes); {} plaintext-bytes have been written before",
+			position.chunk_index, position.ciphertext_offset, position.plaintext_bytes
+		);
+	}
+	text
+}
+
+/// Formats `error` (and the stream-`position` if any) as single-line JSON-object like
+/// `{"kind":"InvalidData","exit_code":4,"detail":null,"description":"…","position":{…}}`
+pub fn to_json(error: &Error<CpError>, position: Option<Position>) -> String {
+	// Serialize the variant-specific detail
+	let detail = match error.kind {
+		CpError::IOError(ref kind, ref description) => Some(format!("{:?}: {}", kind, description)),
+		CpError::Other(ref description) => Some(description.clone()),
+		_ => None
+	};
+	let detail = detail.map(|detail| json_string(&detail)).unwrap_or("null".to_string());
+	
+	// Serialize the position
+	let position = match position {
+		Some(position) => format!(
+			r#"{{"chunk_index":{},"ciphertext_offset":{},"plaintext_bytes":{}}}"#,
+			position.chunk_index, position.ciphertext_offset, position.plaintext_bytes
+		),
+		None => "null".to_string()
+	};
+	
+	format!(
+		r#"{{"kind":{},"exit_code":{},"detail":{},"description":{},"position":{}}}"#,
+		json_string(kind_name(&error.kind)), exit_code(&error.kind), detail, json_string(&error.description), position
+	)
+}
+
+/// Encodes `string` as JSON-string-literal
+fn json_string(string: &str) -> String {
+	let mut encoded = "\"".to_string();
+	for c in string.chars() {
+		match c {
+			'"' => encoded += "\\\"",
+			'\\' => encoded += "\\\\",
+			'\n' => encoded += "\\n",
+			'\r' => encoded += "\\r",
+			'\t' => encoded += "\\t",
+			c if (c as u32) < 0x20 => encoded += &format!("\\u{:04x}", c as u32),
+			c => encoded.push(c)
+		}
+	}
+	encoded + "\""
+}
\ No newline at end of file
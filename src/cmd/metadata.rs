@@ -0,0 +1,50 @@
+//! Reading, restoring and displaying the metadata on the command-line
+
+use etrace::Error;
+use crypto_pipe::CpError;
+use crypto_pipe::metadata::Metadata;
+
+
+/// Reads the size, the modification-time and the permissions of stdin (which must be redirected from
+/// a regular file)
+pub fn from_stdin() -> Result<Metadata, Error<CpError>> {
+	super::with_std_file(super::STDIN_FD, |stdin| {
+		let fs_metadata = try_err_from!(stdin.metadata(), "Failed to read the metadata of stdin".to_owned());
+		if !fs_metadata.is_file() { throw_err!(CpError::CliError, "\"--metadata\" requires stdin to be redirected from a regular file") }
+		Ok(Metadata::from_fs_metadata(&fs_metadata))
+	})
+}
+
+/// Applies the modification-time and the permissions of `metadata` to stdout (which must be
+/// redirected to a regular file; see `Metadata::apply` for `special_bits`)
+pub fn restore(metadata: &Metadata, special_bits: bool) -> Result<(), Error<CpError>> {
+	super::with_std_file(super::STDOUT_FD, |stdout| {
+		let fs_metadata = try_err_from!(stdout.metadata(), "Failed to read the metadata of stdout".to_owned());
+		if !fs_metadata.is_file() { throw_err!(CpError::CliError, "\"--restore-metadata\" requires stdout to be redirected to a regular file") }
+		metadata.apply(stdout, special_bits)
+	})
+}
+
+/// Parses tags like `key=value,key2=value2`
+pub fn parse_tags(tags: &str) -> Result<Vec<(String, String)>, Error<CpError>> {
+	let mut parsed = Vec::new();
+	for tag in tags.split(',').filter(|tag| !tag.is_empty()) {
+		match tag.find('=') {
+			Some(index) if index > 0 => parsed.push((tag[.. index].to_string(), tag[index + 1 ..].to_string())),
+			_ => throw_err!(CpError::CliError, format!("Invalid tag \"{}\" (expected \"key=value\")", tag))
+		}
+	}
+	Ok(parsed)
+}
+
+/// Formats `metadata` as human-readable text (one field per line)
+pub fn to_text(metadata: &Metadata) -> String {
+	let mut text = String::new();
+	if let Some(ref filename) = metadata.filename { text += &format!("    Filename: {}\n", filename) }
+	if let Some(size) = metadata.size { text += &format!("    Size: {} bytes\n", size) }
+	if let Some(modified) = metadata.modified { text += &format!("    Modified: {} (seconds since the Unix-epoch)\n", modified) }
+	if let Some(permissions) = metadata.permissions { text += &format!("    Permissions: {:04o}\n", permissions) }
+	if let Some(ref content_type) = metadata.content_type { text += &format!("    Content-type: {}\n", content_type) }
+	for (key, value) in metadata.tags.iter() { text += &format!("    Tag \"{}\": {}\n", key, value) }
+	text
+}
\ No newline at end of file
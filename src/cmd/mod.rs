@@ -0,0 +1,76 @@
+//! Helpers that are only used by the command-line-application
+
+pub mod password;
+pub mod error;
+pub mod metadata;
+pub mod progress;
+#[cfg(test)] mod tests;
+
+use std;
+use std::collections::HashMap;
+use cli;
+use etrace::Error;
+use crypto_pipe::CpError;
+
+
+/// The value of a flag that was not set (a set flag has an empty value)
+const FLAG_UNSET: &str = "\u{0}unset";
+/// The file-descriptor of stdin
+pub const STDIN_FD: i32 = 0;
+/// The file-descriptor of stdout
+pub const STDOUT_FD: i32 = 1;
+
+/// Creates a parser for a flag (a switch without value like "--verbose")
+pub fn flag() -> cli::SwitchParser {
+	cli::SwitchParser::with_default(FLAG_UNSET.to_string(), &cli::parsers::parse_from_str::<String>)
+}
+
+/// Returns whether the flag `name` is set or not
+pub fn is_set(switches: &HashMap<String, cli::SwitchParser>, name: &str) -> Result<bool, Error<CpError>> {
+	let value = try_err!(switches[name].get::<String>(), CpError::CliError, format!("Failed to parse \"{}\"", name));
+	if value.as_str() == FLAG_UNSET { return Ok(false) }
+	if !value.is_empty() { throw_err!(CpError::CliError, format!("\"{}\" does not take a value", name)) }
+	Ok(true)
+}
+
+/// Parses a size in bytes with an optional `K`, `M` or `G`-suffix for KiB, MiB or GiB (like the
+/// bucket-size of `padding::Padding::from_spec`)
+pub fn parse_size(size: &str) -> Result<u64, Error<CpError>> {
+	let (digits, factor) = match size.chars().last() {
+		Some('K') | Some('k') => (&size[.. size.len() - 1], 1024),
+		Some('M') | Some('m') => (&size[.. size.len() - 1], 1024 * 1024),
+		Some('G') | Some('g') => (&size[.. size.len() - 1], 1024 * 1024 * 1024),
+		_ => (size, 1)
+	};
+	let bytes = try_err!(digits.parse::<u64>(), CpError::CliError, format!("Invalid size \"{}\"", size));
+	Ok(try_err!(bytes.checked_mul(factor).ok_or(()), CpError::CliError, format!("The size \"{}\" is too large", size)))
+}
+
+/// Creates the file at `path` or returns stdout if `path` is empty
+pub fn output_writer(path: &str) -> Result<Box<std::io::Write>, Error<CpError>> {
+	match path {
+		"" => Ok(Box::new(std::io::stdout())),
+		path => Ok(Box::new(try_err_from!(std::fs::File::create(path), format!("Failed to create \"{}\"", path))))
+	}
+}
+
+/// Returns the size of the file behind the standard-file-descriptor `fd` (if it is redirected from or
+/// to a regular file)
+pub fn std_file_size(fd: i32) -> Option<u64> {
+	let size = with_std_file(fd, |file| Ok(file.metadata().ok().filter(|metadata| metadata.is_file()).map(|metadata| metadata.len())));
+	size.unwrap_or(None)
+}
+
+/// Calls `f` with the file behind the standard-file-descriptor `fd` (the descriptor is not closed)
+#[cfg(unix)]
+pub fn with_std_file<T, F: FnOnce(&std::fs::File) -> Result<T, Error<CpError>>>(fd: i32, f: F) -> Result<T, Error<CpError>> {
+	use std::os::unix::io::{ FromRawFd, IntoRawFd };
+	let file = unsafe{ std::fs::File::from_raw_fd(fd) };
+	let result = f(&file);
+	let _ = file.into_raw_fd();
+	result
+}
+#[cfg(not(unix))]
+pub fn with_std_file<T, F: FnOnce(&std::fs::File) -> Result<T, Error<CpError>>>(_fd: i32, _f: F) -> Result<T, Error<CpError>> {
+	throw_err!(CpError::Unsupported, "Accessing stdin or stdout as file is only supported on unix-platforms")
+}
\ No newline at end of file
@@ -0,0 +1,162 @@
+use std;
+use std::env::var;
+use std::io::{ Read, Write };
+use std::collections::HashMap;
+use cli;
+use etrace::Error;
+use crypto_pipe::CpError;
+use crypto_pipe::crypto::{ Password, erase };
+
+
+/// The environment-variable that may contain the password
+const PASSWORD_ENV: &str = "CRYPTO_PIPE_PASSWORD";
+/// The terminal-device that is used to prompt for the password (stdin/stdout carry the data)
+const TTY_PATH: &str = "/dev/tty";
+
+
+
+/// The password-switches for all verbs that need a password
+pub fn switches() -> Vec<(&'static str, cli::SwitchParser)> {
+	vec![
+		("--password=", cli::SwitchParser::with_default(String::new(), &cli::parsers::parse_from_str::<String>)),
+		("--password-fd=", cli::SwitchParser::with_default(-1i32, &cli::parsers::parse_from_str::<i32>)),
+		("--password-file=", cli::SwitchParser::with_default(String::new(), &cli::parsers::parse_from_str::<String>))
+	]
+}
+
+/// Returns whether a password was given by one of the password-switches
+pub fn is_switch_given(switches: &HashMap<String, cli::SwitchParser>) -> Result<bool, Error<CpError>> {
+	let password = try_err!(switches["--password="].get::<String>(), CpError::CliError, "Failed to parse \"--password=\"");
+	let password_fd = try_err!(switches["--password-fd="].get::<i32>(), CpError::CliError, "Failed to parse \"--password-fd=\"");
+	let password_file = try_err!(switches["--password-file="].get::<String>(), CpError::CliError, "Failed to parse \"--password-file=\"");
+	Ok(!password.is_empty() || *password_fd >= 0 || !password_file.is_empty())
+}
+
+/// Returns whether a password was given by one of the switches or the environment-variable (i.e.
+/// whether `get_password` can return without prompting)
+pub fn is_given(switches: &HashMap<String, cli::SwitchParser>) -> Result<bool, Error<CpError>> {
+	Ok(try_err!(is_switch_given(switches)) || var(PASSWORD_ENV).is_ok())
+}
+
+/// Gets the password from the first available source:
+///  1. the "--password="-switch
+///  2. the first line of the file-descriptor passed as "--password-fd="
+///  3. the first line of the file passed as "--password-file="
+///  4. the "CRYPTO_PIPE_PASSWORD"-environment-variable
+///  5. an interactive prompt on the terminal (asked twice if `confirm` is `true`)
+///
+/// The environment-variable is removed from the process-environment and the parsed copies are
+/// erased once they are moved into the `Password`.
+pub fn get_password(switches: &mut HashMap<String, cli::SwitchParser>, confirm: bool) -> Result<Password, Error<CpError>> {
+	// Read and remove environment-var
+	let env_password = var(PASSWORD_ENV).ok().map(Password::from);
+	std::env::remove_var(PASSWORD_ENV);
+	
+	// Get switch-values
+	let password = try_err!(switches.remove("--password=").unwrap().into_value::<String>(), CpError::CliError, "Failed to parse \"--password=\"");
+	if !password.is_empty() { return Ok(Password::from(password)) }
+	
+	let password_fd = try_err!(switches.remove("--password-fd=").unwrap().into_value::<i32>(), CpError::CliError, "Failed to parse \"--password-fd=\"");
+	if password_fd >= 0 { return read_fd(password_fd) }
+	
+	let password_file = try_err!(switches.remove("--password-file=").unwrap().into_value::<String>(), CpError::CliError, "Failed to parse \"--password-file=\"");
+	if !password_file.is_empty() {
+		let mut file = try_err_from!(std::fs::File::open(&password_file), format!("Failed to open the password-file \"{}\"", password_file));
+		return read_line(&mut file)
+	}
+	
+	// Use the environment-var or prompt for the password
+	if let Some(password) = env_password { return Ok(password) }
+	prompt(confirm)
+}
+
+
+
+/// Reads the first line from the inherited file-descriptor `fd` (the descriptor is not closed; stdin,
+/// stdout and stderr are rejected because they carry the data)
+#[cfg(unix)]
+fn read_fd(fd: i32) -> Result<Password, Error<CpError>> {
+	use std::os::unix::io::FromRawFd;
+	if fd <= 2 { throw_err!(CpError::CliError, "\"--password-fd=\" must not be stdin, stdout or stderr (0, 1 or 2)") }
+	let mut file = std::mem::ManuallyDrop::new(unsafe{ std::fs::File::from_raw_fd(fd) });
+	read_line(&mut *file)
+}
+#[cfg(not(unix))]
+fn read_fd(_fd: i32) -> Result<Password, Error<CpError>> {
+	throw_err!(CpError::Unsupported, "\"--password-fd=\" is only supported on unix-platforms")
+}
+
+/// Reads a single line (without the line-break) from `source`
+///
+/// The data is read byte by byte so that nothing after the line-break is consumed.
+fn read_line(source: &mut Read) -> Result<Password, Error<CpError>> {
+	let mut line = Vec::new();
+	let mut byte = [0u8];
+	loop {
+		match source.read(&mut byte) {
+			Ok(0) => break,
+			Ok(_) if byte[0] == b'\n' => break,
+			Ok(_) => line.push(byte[0]),
+			Err(ref e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+			Err(e) => { erase(&mut line); throw_err!(e.into(), "Failed to read the password") }
+		}
+	}
+	if line.last() == Some(&b'\r') { line.pop(); }
+	
+	// Copy the password into guarded memory and erase the buffer
+	let password = Password::from_utf8(&line);
+	erase(&mut line);
+	Ok(try_err!(password))
+}
+
+
+
+/// Disables the terminal-echo and re-enables it on drop
+struct EchoGuard(std::fs::File);
+impl EchoGuard {
+	/// Disables the echo on `tty`
+	pub fn new(tty: &std::fs::File) -> Result<Self, Error<CpError>> {
+		let tty = try_err_from!(tty.try_clone());
+		try_err!(stty(&tty, "-echo"));
+		Ok(EchoGuard(tty))
+	}
+}
+impl Drop for EchoGuard {
+	fn drop(&mut self) {
+		let _ = stty(&self.0, "echo");
+	}
+}
+
+/// Calls `stty` with `argument` on `tty`
+fn stty(tty: &std::fs::File, argument: &str) -> Result<(), Error<CpError>> {
+	let tty = try_err_from!(tty.try_clone());
+	let status = try_err_from!(std::process::Command::new("stty").arg(argument).stdin(tty).status(), "Failed to execute `stty`".to_owned());
+	if !status.success() { throw_err!(CpError::Other("`stty` failed".to_owned()), format!("`stty {}` failed", argument)) }
+	Ok(())
+}
+
+/// Prompts for a password on the terminal with `text`
+fn prompt_once(tty: &mut std::fs::File, text: &str) -> Result<Password, Error<CpError>> {
+	try_err_from!(tty.write_all(text.as_bytes()));
+	let password = {
+		let _echo_guard = try_err!(EchoGuard::new(tty));
+		try_err!(read_line(tty))
+	};
+	try_err_from!(tty.write_all(b"\n"));
+	Ok(password)
+}
+
+/// Prompts for a password on the terminal and asks a second time if `confirm` is `true`
+fn prompt(confirm: bool) -> Result<Password, Error<CpError>> {
+	let mut tty = try_err!(
+		std::fs::OpenOptions::new().read(true).write(true).open(TTY_PATH), CpError::CliError,
+		"No password was given and there is no terminal to prompt for one; use \"--password-fd=\", \"--password-file=\" or the \"CRYPTO_PIPE_PASSWORD\"-environment-variable"
+	);
+	
+	let password = try_err!(prompt_once(&mut tty, "Password: "));
+	if password.is_empty() { throw_err!(CpError::CliError, "The password must not be empty") }
+	if confirm && try_err!(prompt_once(&mut tty, "Confirm password: ")).as_str() != password.as_str() {
+		throw_err!(CpError::CliError, "The passwords do not match")
+	}
+	Ok(password)
+}
\ No newline at end of file
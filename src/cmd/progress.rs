@@ -0,0 +1,125 @@
+//! Printing the progress of a sealing- or opening-operation to stderr (`--progress`)
+
+use std;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::time::{ Duration, Instant };
+use crypto_pipe::stream::Position;
+
+
+/// The minimum interval between two updates
+const UPDATE_INTERVAL: Duration = Duration::from_millis(250);
+/// The width of a progress-line (shorter lines are padded to overwrite the previous line)
+const LINE_WIDTH: usize = 78;
+
+/// The part of the position that is compared with the known total-size
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Measure {
+	/// The plaintext-bytes (the input of a sealing-operation)
+	Plaintext,
+	/// The ciphertext-offset (the input of an opening-operation)
+	Ciphertext
+}
+
+/// Formats `bytes` with a binary unit
+fn format_bytes(bytes: f64) -> String {
+	let units = ["B", "KiB", "MiB", "GiB", "TiB"];
+	let (mut value, mut unit) = (bytes, 0);
+	while value >= 1024.0 && unit + 1 < units.len() {
+		value /= 1024.0;
+		unit += 1;
+	}
+	match unit {
+		0 => format!("{:.0} {}", value, units[unit]),
+		_ => format!("{:.1} {}", value, units[unit])
+	}
+}
+
+/// Formats `seconds` as `h:mm:ss`
+fn format_duration(seconds: u64) -> String {
+	format!("{}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
+
+
+
+struct State {
+	measure: Measure,
+	total: Option<u64>,
+	start: Instant,
+	last_update: Option<Instant>,
+	position: Position,
+	finished: bool
+}
+impl State {
+	/// Stores the position and prints it if the last update is older than `UPDATE_INTERVAL`
+	fn update(&mut self, position: &Position) {
+		self.position = *position;
+		let now = Instant::now();
+		match self.last_update {
+			Some(last_update) if now.duration_since(last_update) < UPDATE_INTERVAL => (),
+			_ => {
+				self.last_update = Some(now);
+				self.print()
+			}
+		}
+	}
+	
+	/// Prints the processed bytes, the chunk-count, the throughput and the ETA (if the total-size is
+	/// known)
+	fn print(&self) {
+		let elapsed = self.start.elapsed();
+		let seconds = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9;
+		let processed = match self.measure {
+			Measure::Plaintext => self.position.plaintext_bytes,
+			Measure::Ciphertext => self.position.ciphertext_offset
+		};
+		let throughput = if seconds > 0.0 { processed as f64 / seconds } else { 0.0 };
+		
+		let mut line = format!(
+			"{} processed, {} chunk(s), {}/s", format_bytes(processed as f64), self.position.chunk_index, format_bytes(throughput)
+		);
+		if let Some(total) = self.total {
+			let remaining = total.saturating_sub(processed);
+			let percent = (std::cmp::min(processed, total) * 100).checked_div(total).unwrap_or(100);
+			line += &format!(", {}% of {}", percent, format_bytes(total as f64));
+			if throughput > 0.0 { line += &format!(", ETA {}", format_duration((remaining as f64 / throughput) as u64)) }
+		}
+		eprint!("\r{:<width$}", line, width = LINE_WIDTH);
+	}
+}
+
+
+
+/// A progress-reporter that prints the position to stderr at most every `UPDATE_INTERVAL`
+pub struct Progress {
+	state: Rc<RefCell<State>>
+}
+impl Progress {
+	/// Creates a new reporter that compares the `measure` with the `total`-size (if known)
+	pub fn new(measure: Measure, total: Option<u64>) -> Self {
+		let state = State{ measure, total, start: Instant::now(), last_update: None, position: Position::default(), finished: false };
+		Progress{ state: Rc::new(RefCell::new(state)) }
+	}
+	
+	/// Creates a callback for `SealBuilder::progress` or `OpenBuilder::progress`
+	pub fn callback(&self) -> Box<FnMut(&Position)> {
+		let state = self.state.clone();
+		Box::new(move |position: &Position| state.borrow_mut().update(position))
+	}
+	
+	/// Prints the final position and terminates the progress-line
+	pub fn finish(&self) {
+		let mut state = self.state.borrow_mut();
+		state.print();
+		state.finished = true;
+		eprintln!()
+	}
+}
+impl Drop for Progress {
+	/// Terminates the progress-line if the operation failed before `finish` (so that the error is
+	/// printed on its own line)
+	fn drop(&mut self) {
+		let state = self.state.borrow();
+		if !state.finished && state.last_update.is_some() { eprintln!() }
+	}
+}
\ No newline at end of file
@@ -0,0 +1,60 @@
+use std;
+use etrace::Error;
+use crypto_pipe::CpError;
+use crypto_pipe::stream::Position;
+use super::super::error::{ exit_code, kind_name, to_json };
+
+
+/// Creates an error of `kind` with `description`
+fn error(kind: CpError, description: &str) -> Error<CpError> {
+	fn fail(kind: CpError, description: &str) -> Result<(), Error<CpError>> {
+		throw_err!(kind, description.to_string())
+	}
+	fail(kind, description).unwrap_err()
+}
+
+
+#[test]
+fn exit_codes() {
+	let documented = [
+		(CpError::Other("Other".to_string()), 1, "Other"),
+		(CpError::CliError, 2, "CliError"),
+		(CpError::IOError(std::io::ErrorKind::NotFound, "NotFound".to_string()), 3, "IOError"),
+		(CpError::InvalidData, 4, "InvalidData"),
+		(CpError::WrongPassword, 5, "WrongPassword"),
+		(CpError::ResourceError, 6, "ResourceError"),
+		(CpError::InvalidParameter, 7, "InvalidParameter"),
+		(CpError::Unsupported, 8, "Unsupported")
+	];
+	for &(ref kind, code, name) in documented.iter() {
+		assert_eq!(exit_code(kind), code);
+		assert_eq!(kind_name(kind), name);
+	}
+}
+
+#[test]
+fn json_format() {
+	// An error without detail and position
+	let json = to_json(&error(CpError::WrongPassword, "Wrong password"), None);
+	assert_eq!(json, r#"{"kind":"WrongPassword","exit_code":5,"detail":null,"description":"Wrong password","position":null}"#);
+	
+	// An error with detail and position
+	let position = Position{ chunk_index: 7, ciphertext_offset: 4711, plaintext_bytes: 42 };
+	let kind = CpError::IOError(std::io::ErrorKind::UnexpectedEof, "unexpected end of file".to_string());
+	let json = to_json(&error(kind, "Failed to read from stdin"), Some(position));
+	assert_eq!(json, concat!(
+		r#"{"kind":"IOError","exit_code":3,"detail":"UnexpectedEof: unexpected end of file","description":"Failed to read from stdin","#,
+		r#""position":{"chunk_index":7,"ciphertext_offset":4711,"plaintext_bytes":42}}"#
+	));
+}
+
+#[test]
+fn json_escaping() {
+	let kind = CpError::Other("Quote \" and backslash \\".to_string());
+	let json = to_json(&error(kind, "Line\nbreak\r\ttab \u{1}\u{1f} and \u{e4}"), None);
+	assert_eq!(json, concat!(
+		r#"{"kind":"Other","exit_code":1,"detail":"Quote \" and backslash \\","#,
+		r#""description":"Line\nbreak\r\ttab \u0001\u001f and "#, "\u{e4}", r#"","position":null}"#
+	));
+	assert!(!json.contains('\n'));
+}
\ No newline at end of file
@@ -0,0 +1,226 @@
+//! Compression of the plaintext before it is sealed
+//!
+//! The plaintext is split into blocks of `COMPRESSION_BLOCK_SIZE` bytes which are compressed
+//! independently; every compressed block is stored as record
+//! `compressed_length (u32 BE) || plaintext_length (u32 BE) || compressed_block`. The records are
+//! concatenated and the resulting stream is sealed like any other plaintext, so the chunk-format
+//! does not change.
+
+use std;
+use super::{ Error, CpError };
+use super::{ asn1_der, io };
+use super::asn1_der::{ FromDerObject, IntoDerObject };
+#[cfg(feature = "zstd")] use super::zstd;
+#[cfg(feature = "lz4")] use super::lz4_flex;
+
+
+/// The size of the plaintext-blocks that are compressed independently
+pub const COMPRESSION_BLOCK_SIZE: usize = 1 * 1024 * 1024;
+/// The length of a record-header
+const RECORD_HEADER_SIZE: usize = 8;
+/// The default maximum ratio between the decompressed and the compressed data (see `Decompressor`;
+/// 1 MiB of zeros compresses to at least 38 bytes, so zero-runs stay below this limit)
+pub const DEFAULT_MAX_COMPRESSION_RATIO: u64 = 32768;
+/// The default zstd-compression-level
+pub const DEFAULT_ZSTD_LEVEL: u32 = 3;
+
+
+
+/// A compression-algorithm
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Algorithm {
+	/// Zstandard (supports the levels 1 to 22)
+	Zstd,
+	/// LZ4 (has no levels; the level must be 0)
+	Lz4
+}
+impl Algorithm {
+	/// Returns the name of the algorithm (as used in the stream-header and on the command-line)
+	pub fn name(&self) -> &'static str {
+		match *self {
+			Algorithm::Zstd => "zstd",
+			Algorithm::Lz4 => "lz4"
+		}
+	}
+	/// Parses the name of an algorithm
+	pub fn from_name(name: &str) -> Result<Self, Error<CpError>> {
+		match name {
+			"zstd" => Ok(Algorithm::Zstd),
+			"lz4" => Ok(Algorithm::Lz4),
+			name => throw_err!(CpError::Unsupported, format!("Unsupported compression-algorithm \"{}\"", name))
+		}
+	}
+}
+
+
+
+/// The compression-configuration of a stream
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Compression {
+	pub algorithm: Algorithm,
+	pub level: u32
+}
+impl Compression {
+	/// Creates and validates a compression-configuration
+	pub fn new(algorithm: Algorithm, level: u32) -> Result<Self, Error<CpError>> {
+		match (algorithm, level) {
+			(Algorithm::Zstd, 1 ..= 22) | (Algorithm::Lz4, 0) => Ok(Compression{ algorithm, level }),
+			(algorithm, level) => throw_err!(CpError::InvalidParameter, format!("Invalid {}-compression-level ({})", algorithm.name(), level))
+		}
+	}
+	
+	/// Parses a serialized configuration
+	pub fn from_serialized(serialized: asn1_der::DerObject) -> Result<Self, Error<CpError>> {
+		let sequence: Vec<asn1_der::DerObject> = try_err!(Vec::<asn1_der::DerObject>::from_der_object(serialized), CpError::InvalidData);
+		if sequence.len() < 2 { throw_err!(CpError::InvalidData) }
+		
+		let algorithm = try_err!(Algorithm::from_name(&try_err!(String::from_der_object(sequence[0].clone()), CpError::InvalidData)));
+		let level = try_err!(u64::from_der_object(sequence[1].clone()), CpError::InvalidData);
+		if level > 22 { throw_err!(CpError::InvalidData) }
+		Ok(try_err!(Compression::new(algorithm, level as u32), CpError::InvalidData))
+	}
+	
+	/// Serializes the configuration
+	pub fn serialize(&self) -> asn1_der::DerObject {
+		let sequence: Vec<asn1_der::DerObject> = vec![
+			self.algorithm.name().to_string().into_der_object(),
+			(self.level as u64).into_der_object()
+		];
+		sequence.into_der_object()
+	}
+	
+	/// Compresses a block
+	fn compress(&self, block: &[u8]) -> Result<Vec<u8>, Error<CpError>> {
+		match self.algorithm {
+			#[cfg(feature = "zstd")]
+			Algorithm::Zstd => Ok(try_err_from!(zstd::bulk::compress(block, self.level as i32), "zstd-compression failed".to_owned())),
+			#[cfg(feature = "lz4")]
+			Algorithm::Lz4 => Ok(lz4_flex::block::compress(block)),
+			#[allow(unreachable_patterns)]
+			algorithm => throw_err!(CpError::Unsupported, format!("{}-compression is not enabled in this build", algorithm.name()))
+		}
+	}
+	
+	/// Decompresses a block that decompresses to exactly `plaintext_length` bytes
+	fn decompress(&self, block: &[u8], plaintext_length: usize) -> Result<Vec<u8>, Error<CpError>> {
+		let plaintext = match self.algorithm {
+			#[cfg(feature = "zstd")]
+			Algorithm::Zstd => try_err!(zstd::bulk::decompress(block, plaintext_length), CpError::InvalidData, "Invalid zstd-block"),
+			#[cfg(feature = "lz4")]
+			Algorithm::Lz4 => try_err!(lz4_flex::block::decompress(block, plaintext_length), CpError::InvalidData, "Invalid lz4-block"),
+			#[allow(unreachable_patterns)]
+			algorithm => throw_err!(CpError::Unsupported, format!("{}-compression is not enabled in this build", algorithm.name()))
+		};
+		if plaintext.len() != plaintext_length { throw_err!(CpError::InvalidData, "Invalid compressed block-length") }
+		Ok(plaintext)
+	}
+}
+
+
+
+/// Reads the plaintext from an `io::Io` and returns the compressed record-stream in chunks
+pub struct Compressor {
+	compression: Compression,
+	block: Vec<u8>,
+	pending: Vec<u8>,
+	plaintext_bytes: u64,
+	is_last: bool
+}
+impl Compressor {
+	pub fn new(compression: Compression) -> Self {
+		Compressor{ compression, block: vec![0u8; COMPRESSION_BLOCK_SIZE], pending: Vec::new(), plaintext_bytes: 0, is_last: false }
+	}
+	
+	/// Returns the amount of plaintext-bytes that have been read from the `io::Io` so far
+	pub fn plaintext_bytes(&self) -> u64 {
+		self.plaintext_bytes
+	}
+	
+	/// Fills `chunk_buffer` with the next compressed data from `io` and returns
+	/// `(chunk_length, is_last)` (like `io::Io::read_chunk`)
+	pub fn read_chunk(&mut self, io: &mut io::Io, chunk_buffer: &mut[u8]) -> Result<(usize, bool), Error<CpError>> {
+		// Compress blocks until there is enough data for a chunk
+		while self.pending.len() <= chunk_buffer.len() && !self.is_last {
+			let (block_length, is_last) = try_err!(io.read_chunk(&mut self.block));
+			self.plaintext_bytes += block_length as u64;
+			self.is_last = is_last;
+			if block_length == 0 { continue }
+			
+			// Append the record
+			let compressed = try_err!(self.compression.compress(&self.block[.. block_length]));
+			self.pending.extend_from_slice(&(compressed.len() as u32).to_be_bytes());
+			self.pending.extend_from_slice(&(block_length as u32).to_be_bytes());
+			self.pending.extend_from_slice(&compressed);
+		}
+		
+		// Copy the pending data
+		let chunk_length = std::cmp::min(chunk_buffer.len(), self.pending.len());
+		chunk_buffer[.. chunk_length].copy_from_slice(&self.pending[.. chunk_length]);
+		self.pending.drain(.. chunk_length);
+		Ok((chunk_length, self.is_last && self.pending.is_empty()))
+	}
+}
+
+
+
+/// Decompresses the record-stream and writes the plaintext to an `io::Io`
+///
+/// To prevent compression-bombs, every record must decompress to at most `COMPRESSION_BLOCK_SIZE`
+/// bytes (which bounds the memory per record); if `max_ratio` is not `0`, the total amount of
+/// decompressed data must also not exceed the total amount of compressed data times `max_ratio`.
+/// Every record is charged at least its header and the chunk-overhead of the authenticated
+/// encryption, so a record that claims more data than its codec could ever produce is rejected
+/// before it is decompressed.
+pub struct Decompressor {
+	compression: Compression,
+	max_ratio: u64,
+	min_record_charge: u64,
+	pending: Vec<u8>,
+	compressed_bytes: u64,
+	plaintext_bytes: u64
+}
+impl Decompressor {
+	/// Creates a new decompressor for a stream whose chunks have an overhead of `overhead` bytes
+	pub fn new(compression: Compression, max_ratio: u64, overhead: usize) -> Self {
+		let min_record_charge = (RECORD_HEADER_SIZE + overhead) as u64;
+		Decompressor{ compression, max_ratio, min_record_charge, pending: Vec::new(), compressed_bytes: 0, plaintext_bytes: 0 }
+	}
+	
+	/// Decompresses all complete records in `data` (and the data passed before), writes them to
+	/// `io` and returns the amount of plaintext-bytes written
+	pub fn write_chunk(&mut self, io: &mut io::Io, data: &[u8]) -> Result<usize, Error<CpError>> {
+		self.pending.extend_from_slice(data);
+		
+		let (mut offset, mut written) = (0, 0);
+		while self.pending.len() - offset >= RECORD_HEADER_SIZE {
+			// Parse and validate the record-header
+			let compressed_length = u32::from_be_bytes([self.pending[offset], self.pending[offset + 1], self.pending[offset + 2], self.pending[offset + 3]]) as usize;
+			let plaintext_length = u32::from_be_bytes([self.pending[offset + 4], self.pending[offset + 5], self.pending[offset + 6], self.pending[offset + 7]]) as usize;
+			if plaintext_length == 0 || plaintext_length > COMPRESSION_BLOCK_SIZE || compressed_length > 2 * COMPRESSION_BLOCK_SIZE {
+				throw_err!(CpError::InvalidData, "Invalid compression-record")
+			}
+			if self.pending.len() - offset - RECORD_HEADER_SIZE < compressed_length { break }
+			
+			// Charge the record and enforce the ratio-limit before decompressing the block
+			self.compressed_bytes += std::cmp::max((RECORD_HEADER_SIZE + compressed_length) as u64, self.min_record_charge);
+			if self.max_ratio != 0 && self.plaintext_bytes + plaintext_length as u64 > self.compressed_bytes.saturating_mul(self.max_ratio) {
+				throw_err!(CpError::InvalidData, format!("The decompression-ratio exceeds the limit of {}:1 (compression-bomb?)", self.max_ratio))
+			}
+			
+			// Decompress and write the block
+			let record = &self.pending[offset + RECORD_HEADER_SIZE .. offset + RECORD_HEADER_SIZE + compressed_length];
+			try_err!(io.write_chunk(&try_err!(self.compression.decompress(record, plaintext_length))));
+			self.plaintext_bytes += plaintext_length as u64;
+			offset += RECORD_HEADER_SIZE + compressed_length;
+			written += plaintext_length;
+		}
+		self.pending.drain(.. offset);
+		Ok(written)
+	}
+	
+	/// Ensures that the record-stream is complete
+	pub fn finish(&self) -> Result<(), Error<CpError>> {
+		if !self.pending.is_empty() { throw_err!(CpError::InvalidData, "The compressed data is truncated") }
+		Ok(())
+	}
+}
\ No newline at end of file
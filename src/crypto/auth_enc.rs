@@ -1,15 +1,15 @@
 use super::{ Error, CpError };
 use super::super::asn1_der;
 use super::super::asn1_der::{ FromDerObject, IntoDerObject };
-use super::libsodium;
+use super::{ backend, Key };
 
-pub trait AuthEnc {
+pub trait AuthEnc: Send + Sync {
 	/// Seals the data using the specified key and returns `Ok(encrypted_data_length)` on success or
 	/// an error on error
-	fn seal(&self, data_buffer: &mut[u8], data_length: usize, key: libsodium::Key) -> Result<usize, Error<CpError>>;
+	fn seal(&self, data_buffer: &mut[u8], data_length: usize, key: Key) -> Result<usize, Error<CpError>>;
 	/// Opens some sealed data using the specified key and returns `Ok(encrypted_data_length)` on
 	/// success or an error on error
-	fn open(&self, data_buffer: &mut[u8], data_length: usize, key: libsodium::Key) -> Result<usize, Error<CpError>>;
+	fn open(&self, data_buffer: &mut[u8], data_length: usize, key: Key) -> Result<usize, Error<CpError>>;
 	
 	/// Returns the byte-overhead when encrypting (usually the MAC)
 	fn overhead(&self) -> usize;
@@ -61,20 +61,20 @@ impl ChaCha20Poly1305 {
 	///
 	/// Returns either `Ok((chacha20_key, chacha20_nonce, poly1305_key))` or an error if a parameter
 	/// is invalid
-	fn prepare(&self, key: libsodium::Key) -> Result<(libsodium::Key, libsodium::Key), Error<CpError>> {
+	fn prepare(&self, key: Key) -> Result<(Key, Key), Error<CpError>> {
 		// Validate input
 		if key.len() != 32 { throw_err!(CpError::InvalidParameter) }
 		
 		// Compute Poly1305-key
-		let mut poly1305_key = libsodium::Key::new(32);
+		let mut poly1305_key = Key::new(32);
 		for byte in poly1305_key.as_mut_slice().iter_mut() { *byte = 0x00 }
-		try_err!(libsodium::chacha20_xor(poly1305_key.as_mut_slice(), 0, &key, &[0u8; 8]));
+		try_err!(backend::chacha20_xor(poly1305_key.as_mut_slice(), 0, &key, &[0u8; 8]));
 		
 		Ok((key, poly1305_key))
 	}
 }
 impl AuthEnc for ChaCha20Poly1305 {
-	fn seal(&self, data_buffer: &mut[u8], data_length: usize, key: libsodium::Key) -> Result<usize, Error<CpError>> {
+	fn seal(&self, data_buffer: &mut[u8], data_length: usize, key: Key) -> Result<usize, Error<CpError>> {
 		// Validate and get keys and nonce
 		let (chacha20_key, poly1305_key) = try_err!(self.prepare(key));
 		
@@ -82,17 +82,17 @@ impl AuthEnc for ChaCha20Poly1305 {
 		if data_length > data_buffer.len() || data_buffer.len() - data_length < 16 { throw_err!(CpError::InvalidParameter) }
 		
 		// Encrypt data
-		try_err!(libsodium::chacha20_xor(&mut data_buffer[.. data_length], 64, &chacha20_key, &[0u8; 8]));
+		try_err!(backend::chacha20_xor(&mut data_buffer[.. data_length], 64, &chacha20_key, &[0u8; 8]));
 		
 		// Compute and copy MAC
 		let mut mac = [0u8; 16];
-		try_err!(libsodium::poly1305(&mut mac, &data_buffer[.. data_length], &poly1305_key));
+		try_err!(backend::poly1305(&mut mac, &data_buffer[.. data_length], &poly1305_key));
 		data_buffer[data_length .. data_length + 16].copy_from_slice(&mac);
 		
 		Ok(data_length + 16)
 	}
 	
-	fn open(&self, data_buffer: &mut[u8], data_length: usize, key: libsodium::Key) -> Result<usize, Error<CpError>> {
+	fn open(&self, data_buffer: &mut[u8], data_length: usize, key: Key) -> Result<usize, Error<CpError>> {
 		// Validate and get keys and nonce
 		let (chacha20_key, poly1305_key) = try_err!(self.prepare(key));
 		
@@ -102,11 +102,11 @@ impl AuthEnc for ChaCha20Poly1305 {
 		
 		// Validate MAC
 		let mut mac = [0u8; 16];
-		try_err!(libsodium::poly1305(&mut mac, &data_buffer[.. data_length - 16], &poly1305_key));
-		if !libsodium::compare_constant_time(&mac, &data_buffer[data_length - 16 .. data_length]) { throw_err!(CpError::InvalidData, "Invalid authentication-tag") }
+		try_err!(backend::poly1305(&mut mac, &data_buffer[.. data_length - 16], &poly1305_key));
+		if !backend::compare_constant_time(&mac, &data_buffer[data_length - 16 .. data_length]) { throw_err!(CpError::InvalidData, "Invalid authentication-tag") }
 		
 		// Decrypt data
-		try_err!(libsodium::chacha20_xor(&mut data_buffer[.. data_length - 16], 64, &chacha20_key, &[0u8; 8]));
+		try_err!(backend::chacha20_xor(&mut data_buffer[.. data_length - 16], 64, &chacha20_key, &[0u8; 8]));
 		Ok(data_length - 16)
 	}
 	
@@ -1,11 +1,11 @@
 use super::super::{ Error, CpError };
-use super::libsodium;
+use super::{ backend, Key };
 use super::super::asn1_der;
 use super::super::asn1_der::{ FromDerObject, IntoDerObject };
 
-pub trait Kdf {
+pub trait Kdf: Send + Sync {
 	/// Derives a key from a base-key
-	fn derive(&self, base_key: &libsodium::Key, info: &[u8]) -> Result<libsodium::Key, Error<CpError>>;
+	fn derive(&self, base_key: &Key, info: &[u8]) -> Result<Key, Error<CpError>>;
 	
 	/// Returns a string specifying the algorithm-instance
 	fn algorithm(&self) -> &'static str;
@@ -39,11 +39,11 @@ impl HmacSha2512 {
 	}
 }
 impl Kdf for HmacSha2512 {
-	fn derive(&self, base_key: &libsodium::Key, info: &[u8]) -> Result<libsodium::Key, Error<CpError>> {
-		let mut derived_key = libsodium::Key::new(64);
+	fn derive(&self, base_key: &Key, info: &[u8]) -> Result<Key, Error<CpError>> {
+		let mut derived_key = Key::new(64);
 		for byte in derived_key.as_mut_slice().iter_mut() { *byte = 0x00; }
 		
-		try_err!(libsodium::hmac_sha2_512(derived_key.as_mut_slice(), &info, base_key));
+		try_err!(backend::hmac_sha2_512(derived_key.as_mut_slice(), &info, base_key));
 		try_err!(derived_key.truncate(32));
 		Ok(derived_key)
 	}
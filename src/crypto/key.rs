@@ -0,0 +1,71 @@
+use std;
+use super::{ Error, CpError };
+use super::backend;
+
+
+
+/// A key-object to hold keys with various length. The memory is allocated by the backend (locked
+/// into RAM and surrounded by guard-pages if the backend supports it), initialized with random
+/// data and overwritten with `X`-bytes if it is dropped.
+///
+/// `Key` does not implement `Clone` to avoid untracked copies of the key-material.
+pub struct Key {
+	ptr: *mut u8,
+	len: usize
+}
+// The memory is exclusively owned by the `Key`
+unsafe impl Send for Key {}
+unsafe impl Sync for Key {}
+impl Key {
+	/// Initializes a new key filled with random data
+	pub fn new(size: usize) -> Self {
+		let mut key = Key{ ptr: backend::secure_alloc(size), len: size };
+		backend::random(key.as_mut_slice());
+		key
+	}
+	/// Truncates the key-length
+	pub fn truncate(&mut self, new_length: usize) -> Result<(), Error<CpError>> {
+		// Validate input
+		if new_length > self.len() { throw_err!(CpError::InvalidParameter, format!("Cannot truncate a {}-byte-key by {} bytes", self.len(), new_length)) }
+		
+		// Copy partial key
+		let mut truncated = Key{ ptr: backend::secure_alloc(new_length), len: new_length };
+		truncated.as_mut_slice().copy_from_slice(&self.as_slice()[.. new_length]);
+		
+		// Set partial key as key (the old key is erased and freed on drop)
+		std::mem::swap(self, &mut truncated);
+		Ok(())
+	}
+	
+	/// Returns the key-bytes as slice
+	pub fn as_slice(&self) -> &[u8] {
+		unsafe{ std::slice::from_raw_parts(self.ptr, self.len) }
+	}
+	/// Returns the key-bytes as mutable slice
+	pub fn as_mut_slice(&mut self) -> &mut[u8] {
+		unsafe{ std::slice::from_raw_parts_mut(self.ptr, self.len) }
+	}
+	/// Returns the key-length
+	pub fn len(&self) -> usize {
+		self.len
+	}
+	/// Returns a pointer to the key-bytes of type `T`
+	pub fn as_ptr<T>(&self) -> *const T {
+		self.ptr as *const T
+	}
+}
+impl Drop for Key {
+	fn drop(&mut self) {
+		erase(self.as_mut_slice());
+		unsafe{ backend::secure_free(self.ptr, self.len) }
+	}
+}
+
+
+
+/// Overwrites `buffer` with `X`-bytes
+pub fn erase(buffer: &mut[u8]) {
+	for i in 0..buffer.len() {
+		unsafe{ std::ptr::write_volatile(&mut buffer[i], 0x58) } // Will not be optimized away
+	}
+}
\ No newline at end of file
@@ -1,57 +1,7 @@
 use std;
-use std::os::raw::{ c_uchar, c_int, c_ulonglong, c_void };
+use std::os::raw::{ c_uchar, c_int, c_ulonglong, c_void, c_char };
 use super::{ Error, CpError };
-
-
-
-/// A key-object to hold keys with various length. The memory will be initialized with random
-/// data and overwritten with `X`-bytes if it is dropped.
-#[derive(Default, Clone)]
-pub struct Key(Vec<u8>);
-impl Key {
-	/// Initializes a new key filled with random data
-	pub fn new(size: usize) -> Self {
-		let mut key = Key(vec![0u8; size]);
-		random(key.as_mut_slice());
-		key
-	}
-	/// Truncates the key-length
-	pub fn truncate(&mut self, new_length: usize) -> Result<(), Error<CpError>> {
-		// Validate input
-		if new_length > self.len() { throw_err!(CpError::InvalidParameter, format!("Cannot truncate a {}-byte-key by {} bytes", self.len(), new_length)) }
-		
-		// Copy partial key
-		let mut truncated = vec![0u8; new_length];
-		truncated.copy_from_slice(&self.0[.. new_length]);
-		
-		// Erase old key-vec and set partial key as key-bytes
-		erase(&mut self.0);
-		self.0 = truncated;
-		Ok(())
-	}
-	
-	/// Returns the key-bytes as slice
-	pub fn as_slice(&self) -> &[u8] {
-		&self.0
-	}
-	/// Returns the key-bytes as mutable slice
-	pub fn as_mut_slice(&mut self) -> &mut[u8] {
-		&mut self.0
-	}
-	/// Returns the key-length
-	pub fn len(&self) -> usize {
-		self.0.len()
-	}
-	/// Returns a pointer to the key-bytes of type `T`
-	pub fn as_ptr<T>(&self) -> *const T {
-		self.0.as_ptr() as *const T
-	}
-}
-impl Drop for Key {
-	fn drop(&mut self) {
-		erase(self.as_mut_slice())
-	}
-}
+use super::Key;
 
 
 
@@ -69,10 +19,15 @@ extern {
 	fn crypto_onetimeauth_poly1305(mac: *mut c_uchar, data: *const c_uchar, data_len: c_ulonglong,  key: *const c_uchar) -> c_int;
 	fn crypto_auth_hmacsha512(mac: *mut c_uchar, data: *const c_uchar, data_len: c_ulonglong, key: *const c_uchar) -> c_int;
 	
+	// Guarded memory
+	fn sodium_malloc(size: usize) -> *mut c_void;
+	fn sodium_free(ptr: *mut c_void);
+	
 	// Helpers
 	fn sodium_init() -> c_int;
 	fn randombytes_buf(buffer: *mut c_void, buffer_len: isize);
 	fn sodium_memcmp(a: *const c_uchar, b: *const c_uchar, len: isize) -> c_int;
+	fn sodium_version_string() -> *const c_char;
 }
 
 
@@ -166,6 +121,21 @@ pub fn hmac_sha2_512(buffer: &mut[u8], data: &[u8], key: &Key) -> Result<(), Err
 }
 
 
+/// Allocates `size` bytes that are locked into RAM and surrounded by guard-pages
+pub fn secure_alloc(size: usize) -> *mut u8 {
+	if unsafe{ sodium_init() } == -1 { panic!("Failed to init libsodium") }
+	
+	let ptr = unsafe{ sodium_malloc(size) } as *mut u8;
+	if ptr.is_null() { panic!("Failed to allocate {} bytes of guarded memory", size) }
+	ptr
+}
+
+/// Zeroes, unlocks and frees memory that was allocated by `secure_alloc`
+pub unsafe fn secure_free(ptr: *mut u8, _size: usize) {
+	sodium_free(ptr as *mut c_void)
+}
+
+
 pub fn random(buffer: &mut[u8]) {
 	if unsafe{ sodium_init() } == -1 { panic!("Failed to init libsodium") }
 	
@@ -183,8 +153,8 @@ pub fn compare_constant_time(data0: &[u8], data1: &[u8]) -> bool {
 
 
 
-pub fn erase(buffer: &mut[u8]) {
-	for i in 0..buffer.len() {
-		unsafe{ std::ptr::write_volatile(&mut buffer[i], 0x58) } // Will not be optimized away
-	}
+/// Returns the name and version of the linked libsodium
+pub fn version() -> String {
+	let version = unsafe{ std::ffi::CStr::from_ptr(sodium_version_string()) };
+	format!("libsodium {}", version.to_string_lossy())
 }
\ No newline at end of file
@@ -1,29 +1,80 @@
-pub mod libsodium;
+#[cfg(not(feature = "pure-rust"))] pub mod libsodium;
+#[cfg(feature = "pure-rust")] pub mod rust_crypto;
+pub mod key;
+pub mod password;
+pub mod passphrase;
 pub mod pbkdf;
 pub mod kdf;
 pub mod auth_enc;
+pub mod selftest;
 
-use super::asn1_der;
-use super::asn1_der::{ FromDerEncoded, FromDerObject, IntoDerObject };
+use super::{ asn1_der, fec, compression, padding, metadata };
+use super::asn1_der::{ FromDerEncoded, FromDerObject, IntoDerEncoded, IntoDerObject };
 use super::{ Error, CpError };
 
-pub use self::libsodium::{ Key, random };
+/// The backend that implements the cryptographic primitives (libsodium or the pure-Rust-backend if
+/// the `pure-rust`-feature is enabled)
+#[cfg(not(feature = "pure-rust"))] pub use self::libsodium as backend;
+#[cfg(feature = "pure-rust")] pub use self::rust_crypto as backend;
+
+pub use self::key::{ Key, erase };
+pub use self::password::Password;
+pub use self::backend::random;
 pub use self::pbkdf::Pbkdf;
 pub use self::kdf::Kdf;
 pub use self::auth_enc::AuthEnc;
+pub use self::selftest::selftest;
+
 
 
+static VERSIONS: [&str; 2] = ["de.KizzyCode.CryptoPipe.v2", "de.KizzyCode.CryptoPipe.v1"]; // The current-version needs to be at index 0
+/// The name of the parity-extension (see `fec::Parity`)
+pub const PARITY_EXTENSION: &str = "parity";
+/// The name of the compression-extension (see `compression::Compression`)
+pub const COMPRESSION_EXTENSION: &str = "compression";
+/// The name of the padding-extension (see `padding::Padding`)
+pub const PADDING_EXTENSION: &str = "padding";
+/// The name of the metadata-extension (the length of the sealed metadata-chunk; see `metadata`)
+pub const METADATA_EXTENSION: &str = "metadata";
+/// The name of the archive-extension (the archive-format-version; see `archive`)
+pub const ARCHIVE_EXTENSION: &str = "archive";
+/// The supported archive-format-version
+pub const ARCHIVE_VERSION: u64 = 1;
+/// The name of the append-extension (the appendable-chunk-format-version; see `append`)
+pub const APPEND_EXTENSION: &str = "append";
+/// The supported appendable-chunk-format-version
+pub const APPEND_VERSION: u64 = 1;
+/// The name of the seek-index-extension (the seek-index-format-version; see `seekable`)
+pub const SEEK_INDEX_EXTENSION: &str = "seek-index";
+/// The supported seek-index-format-version
+pub const SEEK_INDEX_VERSION: u64 = 1;
 
-static VERSIONS: [&str; 1] = ["de.KizzyCode.CryptoPipe.v1"]; // The current-version needs to be at index 0
 pub struct StreamInstance {
 	pub pbkdf: Box<Pbkdf>,
 	pub kdf: Box<Kdf>,
-	pub auth_enc: Box<AuthEnc>
+	pub auth_enc: Box<AuthEnc>,
+	/// The key-check-value that also authenticates the header (see `stream::key_check_value`);
+	/// v1-headers don't contain one
+	pub key_check: Option<Vec<u8>>,
+	/// The parity-configuration (v2 only)
+	pub parity: Option<fec::Parity>,
+	/// The compression-configuration (v2 only)
+	pub compression: Option<compression::Compression>,
+	/// The padding-scheme (v2 only)
+	pub padding: Option<padding::Padding>,
+	/// The length of the sealed metadata-chunk that follows the header (v2 only)
+	pub metadata_length: Option<u64>,
+	/// Whether the stream is an archive of separately sealed files (v2 only; see `archive`)
+	pub archive: bool,
+	/// Whether the chunks carry a session-ID so that data can be appended (v2 only; see `append`)
+	pub appendable: bool,
+	/// Whether the stream ends with an authenticated seek-index (v2 only; see `seekable`)
+	pub seek_index: bool
 }
 impl StreamInstance {
 	/// Initializes the stream-info with the given algorithm and parameters
 	pub fn new(pbkdf: Box<Pbkdf>, kdf: Box<Kdf>, auth_enc: Box<AuthEnc>) -> Self {
-		StreamInstance{ pbkdf, kdf, auth_enc }
+		StreamInstance{ pbkdf, kdf, auth_enc, key_check: None, parity: None, compression: None, padding: None, metadata_length: None, archive: false, appendable: false, seek_index: false }
 	}
 	
 	/// Returns `Ok(Some(header_length))` if the length was decoded successfully or
@@ -48,21 +99,136 @@ impl StreamInstance {
 		if !VERSIONS.contains(&version.as_str()) { throw_err!(CpError::Unsupported, format!("Unsupported CryptoPipe-stream-version ({})", version)) }
 		
 		// Load instances
-		Ok(StreamInstance {
-			pbkdf: try_err!(pbkdf::from_serialized(sequence[1].clone())),
-			kdf: try_err!(kdf::from_serialized(sequence[2].clone())),
-			auth_enc: try_err!(auth_enc::from_serialized(sequence[3].clone()))
-		})
+		let mut stream_instance = StreamInstance::new(
+			try_err!(pbkdf::from_serialized(sequence[1].clone())),
+			try_err!(kdf::from_serialized(sequence[2].clone())),
+			try_err!(auth_enc::from_serialized(sequence[3].clone()))
+		);
+		
+		// Load the key-check-value and the extensions (v2)
+		if version == VERSIONS[0] {
+			if sequence.len() < 6 { throw_err!(CpError::InvalidData) }
+			stream_instance.key_check = Some(try_err!(Vec::<u8>::from_der_object(sequence[4].clone()), CpError::InvalidData));
+			try_err!(stream_instance.load_extensions(sequence[5].clone()))
+		}
+		
+		// The appendable chunk-format does not support the transformations that span multiple chunks
+		if stream_instance.appendable && (stream_instance.parity.is_some() || stream_instance.compression.is_some() || stream_instance.padding.is_some()) {
+			throw_err!(CpError::InvalidData, "Appendable streams cannot have parity-blocks, compression or padding")
+		}
+		if stream_instance.appendable && stream_instance.archive { throw_err!(CpError::InvalidData, "Archives cannot be appendable") }
+		
+		// The seek-index requires a fixed mapping between the plaintext- and the chunk-offsets
+		if stream_instance.seek_index && (stream_instance.compression.is_some() || stream_instance.appendable || stream_instance.archive) {
+			throw_err!(CpError::InvalidData, "Streams with a seek-index cannot be compressed, appendable or archives")
+		}
+		Ok(stream_instance)
+	}
+	
+	/// Loads the extensions (a sequence of `(name, value)`-sequences); every extension is
+	/// critical, so unknown extensions are rejected
+	fn load_extensions(&mut self, serialized: asn1_der::DerObject) -> Result<(), Error<CpError>> {
+		let extensions: Vec<asn1_der::DerObject> = try_err!(Vec::<asn1_der::DerObject>::from_der_object(serialized), CpError::InvalidData);
+		for extension in extensions {
+			// Parse the extension
+			let extension: Vec<asn1_der::DerObject> = try_err!(Vec::<asn1_der::DerObject>::from_der_object(extension), CpError::InvalidData);
+			if extension.len() < 2 { throw_err!(CpError::InvalidData) }
+			let name: String = try_err!(String::from_der_object(extension[0].clone()), CpError::InvalidData);
+			
+			// Load the extension
+			match name.as_str() {
+				PARITY_EXTENSION if self.parity.is_none() => self.parity = Some(try_err!(fec::Parity::from_serialized(extension[1].clone()))),
+				COMPRESSION_EXTENSION if self.compression.is_none() => self.compression = Some(try_err!(compression::Compression::from_serialized(extension[1].clone()))),
+				PADDING_EXTENSION if self.padding.is_none() => self.padding = Some(try_err!(padding::Padding::from_serialized(extension[1].clone()))),
+				METADATA_EXTENSION if self.metadata_length.is_none() => {
+					let metadata_length = try_err!(u64::from_der_object(extension[1].clone()), CpError::InvalidData);
+					if metadata_length > (metadata::MAX_METADATA_SIZE + self.auth_enc.overhead()) as u64 { throw_err!(CpError::InvalidData, "Invalid metadata-length") }
+					self.metadata_length = Some(metadata_length)
+				},
+				ARCHIVE_EXTENSION if !self.archive => {
+					let version = try_err!(u64::from_der_object(extension[1].clone()), CpError::InvalidData);
+					if version != ARCHIVE_VERSION { throw_err!(CpError::Unsupported, format!("Unsupported archive-format-version ({})", version)) }
+					self.archive = true
+				},
+				APPEND_EXTENSION if !self.appendable => {
+					let version = try_err!(u64::from_der_object(extension[1].clone()), CpError::InvalidData);
+					if version != APPEND_VERSION { throw_err!(CpError::Unsupported, format!("Unsupported appendable-chunk-format-version ({})", version)) }
+					self.appendable = true
+				},
+				SEEK_INDEX_EXTENSION if !self.seek_index => {
+					let version = try_err!(u64::from_der_object(extension[1].clone()), CpError::InvalidData);
+					if version != SEEK_INDEX_VERSION { throw_err!(CpError::Unsupported, format!("Unsupported seek-index-format-version ({})", version)) }
+					self.seek_index = true
+				},
+				PARITY_EXTENSION | COMPRESSION_EXTENSION | PADDING_EXTENSION | METADATA_EXTENSION | ARCHIVE_EXTENSION | APPEND_EXTENSION | SEEK_INDEX_EXTENSION => throw_err!(CpError::InvalidData, format!("Duplicate stream-header-extension \"{}\"", name)),
+				name => throw_err!(CpError::Unsupported, format!("Unsupported stream-header-extension \"{}\"", name))
+			}
+		}
+		Ok(())
+	}
+	
+	/// Serializes the extensions
+	fn serialize_extensions(&self) -> Vec<asn1_der::DerObject> {
+		let mut extensions: Vec<asn1_der::DerObject> = Vec::new();
+		if let Some(ref parity) = self.parity {
+			extensions.push(vec![PARITY_EXTENSION.to_string().into_der_object(), parity.serialize()].into_der_object())
+		}
+		if let Some(ref compression) = self.compression {
+			extensions.push(vec![COMPRESSION_EXTENSION.to_string().into_der_object(), compression.serialize()].into_der_object())
+		}
+		if let Some(ref padding) = self.padding {
+			extensions.push(vec![PADDING_EXTENSION.to_string().into_der_object(), padding.serialize()].into_der_object())
+		}
+		if let Some(metadata_length) = self.metadata_length {
+			extensions.push(vec![METADATA_EXTENSION.to_string().into_der_object(), metadata_length.into_der_object()].into_der_object())
+		}
+		if self.archive {
+			extensions.push(vec![ARCHIVE_EXTENSION.to_string().into_der_object(), ARCHIVE_VERSION.into_der_object()].into_der_object())
+		}
+		if self.appendable {
+			extensions.push(vec![APPEND_EXTENSION.to_string().into_der_object(), APPEND_VERSION.into_der_object()].into_der_object())
+		}
+		if self.seek_index {
+			extensions.push(vec![SEEK_INDEX_EXTENSION.to_string().into_der_object(), SEEK_INDEX_VERSION.into_der_object()].into_der_object())
+		}
+		extensions
+	}
+	
+	/// Creates a copy of the stream-info (the algorithm-instances are boxed and cannot be cloned, so
+	/// the stream-info is serialized and parsed again)
+	pub fn try_clone(&self) -> Result<Self, Error<CpError>> {
+		Self::from_serialized(self.as_serialized().into_der_encoded())
 	}
 	
-	/// Serializes this stream-info
+	/// Serializes this stream-info (as v1-header if neither a key-check-value nor an extension is set
+	/// and as v2-header otherwise)
 	pub fn as_serialized(&self) -> asn1_der::DerObject {
-		let sequence: Vec<asn1_der::DerObject> = vec![
-			VERSIONS[0].to_string().into_der_object(),
+		let extensions = self.serialize_extensions();
+		let version = match (&self.key_check, extensions.is_empty()) {
+			(&None, true) => VERSIONS[1],
+			_ => VERSIONS[0]
+		};
+		let mut sequence: Vec<asn1_der::DerObject> = vec![
+			version.to_string().into_der_object(),
 			self.pbkdf.serialize(),
 			self.kdf.serialize(),
 			self.auth_enc.serialize()
 		];
+		if let Some(ref key_check) = self.key_check { sequence.push(key_check.clone().into_der_object()) }
+		if version == VERSIONS[0] { sequence.push(extensions.into_der_object()) }
 		sequence.into_der_object()
 	}
+	
+	/// Serializes the v2-header without the key-check-value (the data that is authenticated by the
+	/// key-check-value; see `stream::key_check_value`)
+	pub fn authenticated_header(&self) -> Vec<u8> {
+		let sequence: Vec<asn1_der::DerObject> = vec![
+			VERSIONS[0].to_string().into_der_object(),
+			self.pbkdf.serialize(),
+			self.kdf.serialize(),
+			self.auth_enc.serialize(),
+			self.serialize_extensions().into_der_object()
+		];
+		sequence.into_der_object().into_der_encoded()
+	}
 }
\ No newline at end of file
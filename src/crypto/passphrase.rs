@@ -0,0 +1,41 @@
+//! A diceware-style passphrase-generator that uses the BIP-39-wordlist (2048 words, 11 bits per
+//! word)
+
+use std;
+use super::{ Password, random };
+
+
+/// The bundled wordlist (one word per line)
+const WORDLIST: &str = include_str!("../texts/wordlist.txt");
+
+
+
+/// Returns the bundled wordlist
+pub fn wordlist() -> Vec<&'static str> {
+	WORDLIST.lines().collect()
+}
+
+/// Returns the entropy in bits of a passphrase with `word_count` words
+pub fn entropy(word_count: usize) -> f64 {
+	(word_count as f64) * (wordlist().len() as f64).log2()
+}
+
+/// Generates a passphrase of `word_count` uniformly chosen words joined by `separator`
+pub fn generate(word_count: usize, separator: &str) -> Password {
+	let wordlist = wordlist();
+	let words: Vec<&str> = (0 .. word_count).map(|_| wordlist[random_index(wordlist.len())]).collect();
+	Password::from(words.join(separator))
+}
+
+/// Returns a uniformly distributed random index in `[0, bound)`
+fn random_index(bound: usize) -> usize {
+	// Use rejection-sampling to avoid a modulo-bias
+	let limit = std::u32::MAX - (std::u32::MAX % bound as u32);
+	loop {
+		let mut bytes = [0u8; 4];
+		random(&mut bytes);
+		
+		let value = (bytes[0] as u32) | (bytes[1] as u32) << 8 | (bytes[2] as u32) << 16 | (bytes[3] as u32) << 24;
+		if value < limit { return (value % bound as u32) as usize }
+	}
+}
\ No newline at end of file
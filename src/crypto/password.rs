@@ -0,0 +1,77 @@
+use std;
+use super::{ Error, CpError };
+use super::key::{ Key, erase };
+
+
+
+/// A UTF-8-password that lives in the same guarded memory as a `Key` and is erased if it is
+/// dropped
+///
+/// Converting a `String` into a `Password` erases the `String`.
+pub struct Password(Key);
+impl Password {
+	/// Copies `password` into guarded memory
+	pub fn new(password: &str) -> Self {
+		let mut buffer = Key::new(password.len());
+		buffer.as_mut_slice().copy_from_slice(password.as_bytes());
+		Password(buffer)
+	}
+	/// Copies `password` into guarded memory if it is valid UTF-8
+	pub fn from_utf8(password: &[u8]) -> Result<Self, Error<CpError>> {
+		Ok(Password::new(try_err_from!(std::str::from_utf8(password), "The password is not valid UTF-8".to_owned())))
+	}
+	
+	/// Returns the password as string-slice
+	pub fn as_str(&self) -> &str {
+		unsafe{ std::str::from_utf8_unchecked(self.0.as_slice()) } // The bytes were copied from a `str`
+	}
+	/// Returns the password-length in bytes
+	pub fn len(&self) -> usize {
+		self.0.len()
+	}
+	/// Returns whether the password is empty or not
+	pub fn is_empty(&self) -> bool {
+		self.len() == 0
+	}
+	
+	/// Estimates the entropy of the password in bits
+	///
+	/// The estimate is based on the size of the used character-classes; characters that repeat the
+	/// previous character or continue an ascending/descending sequence only count as 1 bit.
+	pub fn estimate_entropy(&self) -> f64 {
+		// Compute the pool-size from the used character-classes
+		let (mut lower, mut upper, mut digits, mut symbols, mut other) = (0, 0, 0, 0, 0);
+		for c in self.as_str().chars() {
+			if c.is_ascii_lowercase() { lower = 26 }
+				else if c.is_ascii_uppercase() { upper = 26 }
+				else if c.is_ascii_digit() { digits = 10 }
+				else if c.is_ascii() { symbols = 33 }
+				else { other = 100 }
+		}
+		let bits_per_char = ((lower + upper + digits + symbols + other) as f64).log2();
+		
+		// Sum up the entropy of all characters
+		let (mut entropy, mut previous) = (0.0, None);
+		for c in self.as_str().chars() {
+			let is_predictable = match previous {
+				Some(previous) => (c as i64 - previous as i64).abs() <= 1,
+				None => false
+			};
+			entropy += if is_predictable { 1.0 } else { bits_per_char };
+			previous = Some(c);
+		}
+		entropy
+	}
+}
+impl From<String> for Password {
+	fn from(mut password: String) -> Self {
+		let guarded = Password::new(&password);
+		erase(unsafe{ password.as_bytes_mut() }); // `X`-bytes are valid UTF-8
+		guarded
+	}
+}
+impl<'a> From<&'a str> for Password {
+	fn from(password: &'a str) -> Self {
+		Password::new(password)
+	}
+}
\ No newline at end of file
@@ -1,12 +1,12 @@
 use std;
 use super::super::{ Error, CpError };
-use super::libsodium;
+use super::{ backend, Key, Password };
 use super::super::asn1_der;
 use super::super::asn1_der::{ FromDerObject, IntoDerObject };
 
-pub trait Pbkdf {
-	/// Derives a key from the provided password and erases the password afterwards
-	fn derive(&self, password: String) -> Result<libsodium::Key, Error<CpError>>;
+pub trait Pbkdf: Send + Sync {
+	/// Derives a key from the provided password (the password is erased once it is dropped)
+	fn derive(&self, password: Password) -> Result<Key, Error<CpError>>;
 	
 	/// Returns the algorithm-ID
 	fn algorithm(&self) -> &str;
@@ -46,7 +46,7 @@ impl Argon2i {
 	pub fn new(time_cost: u32, memory_cost_mib: u32, parallelism: u32) -> Box<Pbkdf> {
 		// Create random nonce
 		let mut nonce = vec![0u8; 32];
-		libsodium::random(&mut nonce);
+		backend::random(&mut nonce);
 		
 		Argon2i::with_nonce(nonce, time_cost, memory_cost_mib, parallelism)
 	}
@@ -67,14 +67,13 @@ impl Argon2i {
 	}
 }
 impl Pbkdf for Argon2i {
-	fn derive(&self, mut password: String) -> Result<libsodium::Key, Error<CpError>> {
+	fn derive(&self, password: Password) -> Result<Key, Error<CpError>> {
 		// Compute memory-cost in kiB
 		let memory_cost_kib = try_err!(u64_to_u32((self.memory_cost_mib as u64) * 1024));
 		
 		// Compute master-key
-		let mut key = libsodium::Key::new(32);
-		try_err!(libsodium::argon2i_v13(&mut key, &password, &self.nonce, self.time_cost, memory_cost_kib, self.parallelism));
-		libsodium::erase(unsafe{ password.as_bytes_mut() });
+		let mut key = Key::new(32);
+		try_err!(backend::argon2i_v13(&mut key, password.as_str(), &self.nonce, self.time_cost, memory_cost_kib, self.parallelism));
 		
 		Ok(key)
 	}
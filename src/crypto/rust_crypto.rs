@@ -0,0 +1,104 @@
+//! A pure-Rust-backend that provides the same primitives as the libsodium-backend (and produces
+//! bit-identical results)
+
+use std;
+use super::super::{ argon2, chacha20, poly1305, hmac, sha2, getrandom, subtle };
+use self::chacha20::cipher::{ KeyIvInit, StreamCipher, StreamCipherSeek };
+use self::poly1305::universal_hash::KeyInit;
+use self::hmac::Mac;
+use self::subtle::ConstantTimeEq;
+use super::{ Error, CpError };
+use super::Key;
+
+
+
+pub fn argon2i_v13(buffer: &mut Key, password: &str, nonce: &[u8], iterations: u32, memory_cost_kib: u32, parallelism: u32) -> Result<(), Error<CpError>> {
+	// Validate parameters
+	let params = match argon2::Params::new(memory_cost_kib, iterations, parallelism, Some(buffer.len())) {
+		Ok(params) => params,
+		Err(error) => throw_err!(CpError::InvalidParameter, format!("Invalid Argon2i-parameters ({})", error))
+	};
+	
+	// Derive key
+	let argon2i = argon2::Argon2::new(argon2::Algorithm::Argon2i, argon2::Version::V0x13, params);
+	match argon2i.hash_password_into(password.as_bytes(), nonce, buffer.as_mut_slice()) {
+		Ok(()) => Ok(()),
+		Err(argon2::Error::MemoryTooMuch) => throw_err!(CpError::ResourceError, "Argon2i failed (not enough memory)"),
+		Err(error) => throw_err!(CpError::InvalidParameter, format!("Argon2i failed ({})", error))
+	}
+}
+
+
+
+pub fn chacha20_xor(to_xor: &mut[u8], state_byte_offset: u64, key: &Key, nonce: &[u8]) -> Result<(), Error<CpError>> {
+	// Validate input
+	if key.len() != 32 { throw_err!(CpError::InvalidParameter, format!("The key-length is invalid ({} bytes instead of 32)", key.len())) }
+	if nonce.len() != 8 { throw_err!(CpError::InvalidParameter, format!("The nonce-length is invalid ({} bytes instead of 8)", nonce.len())) }
+	
+	// Use the original ChaCha20 with a 64-bit nonce and a 64-bit block-counter (like libsodium's `crypto_stream_chacha20`)
+	let mut chacha20 = chacha20::ChaCha20Legacy::new(chacha20::Key::from_slice(key.as_slice()), chacha20::LegacyNonce::from_slice(nonce));
+	chacha20.seek(state_byte_offset);
+	chacha20.apply_keystream(to_xor);
+	Ok(())
+}
+
+
+
+pub fn poly1305(buffer: &mut[u8], data: &[u8], key: &Key) -> Result<(), Error<CpError>> {
+	// Validate input
+	if buffer.len() < 16 { throw_err!(CpError::InvalidParameter, format!("The target-buffer is too small ({} bytes instead of 16)", buffer.len())) }
+	if key.len() != 32 { throw_err!(CpError::InvalidParameter, format!("The key-length is invalid ({} bytes instead of 32)", key.len())) }
+	
+	// Compute MAC
+	let mac = poly1305::Poly1305::new(poly1305::Key::from_slice(key.as_slice())).compute_unpadded(data);
+	buffer[.. 16].copy_from_slice(mac.as_slice());
+	Ok(())
+}
+
+pub fn hmac_sha2_512(buffer: &mut[u8], data: &[u8], key: &Key) -> Result<(), Error<CpError>> {
+	// Validate input
+	if buffer.len() < 64 { throw_err!(CpError::InvalidParameter, format!("The target-buffer is too small ({} bytes instead of 64)", buffer.len())) }
+	if key.len() != 32 { throw_err!(CpError::InvalidParameter, format!("The key-length is invalid ({} bytes instead of 32)", key.len())) }
+	
+	// Compute HMAC
+	let mut hmac = match <hmac::Hmac<sha2::Sha512> as Mac>::new_from_slice(key.as_slice()) {
+		Ok(hmac) => hmac,
+		Err(_) => throw_err!(CpError::InvalidParameter, "Invalid HMAC-key")
+	};
+	hmac.update(data);
+	buffer[.. 64].copy_from_slice(hmac.finalize().into_bytes().as_slice());
+	Ok(())
+}
+
+
+/// Allocates `size` bytes on the heap
+///
+/// Locking memory and guard-pages require OS-specific APIs, so the pure-Rust-backend can only
+/// ensure that the memory is erased before it is freed.
+pub fn secure_alloc(size: usize) -> *mut u8 {
+	Box::into_raw(vec![0u8; size].into_boxed_slice()) as *mut u8
+}
+
+/// Frees memory that was allocated by `secure_alloc`
+pub unsafe fn secure_free(ptr: *mut u8, size: usize) {
+	drop(Box::from_raw(std::slice::from_raw_parts_mut(ptr, size) as *mut [u8]))
+}
+
+
+pub fn random(buffer: &mut[u8]) {
+	if getrandom::getrandom(buffer).is_err() { panic!("Failed to get random bytes from the OS") }
+}
+
+
+
+pub fn compare_constant_time(data0: &[u8], data1: &[u8]) -> bool {
+	if data0.len() != data1.len() { return false }
+	data0.ct_eq(data1).into()
+}
+
+
+
+/// Returns the name of the backend
+pub fn version() -> String {
+	"pure-Rust (RustCrypto)".to_string()
+}
\ No newline at end of file
@@ -0,0 +1,108 @@
+//! Known-answer-tests for every primitive of the crypto-backend
+//!
+//! A backend that was built against (or loaded) an incompatible library will produce streams that
+//! cannot be opened anymore; the self-test detects this before any data is processed.
+
+use super::{ Error, CpError, Key, backend };
+
+
+
+/// The known-answer-tests as `(primitive_name, test)`-tuples
+pub const TESTS: [(&str, fn() -> Result<(), Error<CpError>>); 6] = [
+	("Argon2i v1.3", argon2i_v13),
+	("ChaCha20", chacha20),
+	("Poly1305", poly1305),
+	("HMAC-SHA2-512", hmac_sha2_512),
+	("RNG", random),
+	("Constant-time comparison", compare_constant_time)
+];
+
+/// Runs all known-answer-tests and fails on the first mismatch
+pub fn selftest() -> Result<(), Error<CpError>> {
+	for &(_, test) in TESTS.iter() { try_err!(test()) }
+	Ok(())
+}
+
+
+
+/// Decodes a hex-string
+fn unhex(hex: &str) -> Vec<u8> {
+	(0 .. hex.len() / 2).map(|i| u8::from_str_radix(&hex[i * 2 .. i * 2 + 2], 16).unwrap()).collect()
+}
+
+/// Creates a key from a hex-string
+fn key(hex: &str) -> Key {
+	let mut key = Key::new(hex.len() / 2);
+	key.as_mut_slice().copy_from_slice(&unhex(hex));
+	key
+}
+
+/// Fails with a descriptive error if `result` does not match `expected`
+fn check(primitive: &str, result: &[u8], expected: &[u8]) -> Result<(), Error<CpError>> {
+	if result != expected { throw_err!(CpError::Unsupported, format!("Self-test failed: {} produced an unexpected result (incompatible crypto-backend)", primitive)) }
+	Ok(())
+}
+
+
+
+/// Argon2i v1.3 with `t = 3`, `m = 8 KiB` and `p = 1` (cheap enough to run on every start)
+fn argon2i_v13() -> Result<(), Error<CpError>> {
+	let mut buffer = Key::new(32);
+	try_err!(backend::argon2i_v13(&mut buffer, "password", b"somesalt", 3, 8, 1));
+	check("Argon2i v1.3", buffer.as_slice(), &unhex("798b65e249fc27d45f2b9fed9c90af3aceaf97138e3f1cd2759fcb8243a22c05"))
+}
+
+/// The all-zero-key/nonce-vector from RFC 7539 (A.1) – starting at an unaligned offset to cover
+/// the block-handling
+fn chacha20() -> Result<(), Error<CpError>> {
+	let expected = unhex(concat!(
+		"76b8e0ada0f13d90405d6ae55386bd28bdd219b8a08ded1aa836efcc8b770dc7",
+		"da41597c5157488d7724e03fb8d84a376a43b8f41518a11cc387b669b2ee6586",
+		"9f07e7be5551387a98ba977c732d080dcb0f29a048e3656912c6533e32ee7aed",
+		"29b721769ce64e43d57133b074d839d531ed1f28510afb45ace10a1f4b794d6f"
+	));
+	
+	let mut buffer = vec![0u8; 91];
+	try_err!(backend::chacha20_xor(&mut buffer, 37, &key(&"00".repeat(32)), &[0u8; 8]));
+	check("ChaCha20", &buffer, &expected[37 ..])
+}
+
+/// The Poly1305-vector from RFC 7539 (2.5.2)
+fn poly1305() -> Result<(), Error<CpError>> {
+	let mut buffer = [0u8; 16];
+	try_err!(backend::poly1305(&mut buffer, b"Cryptographic Forum Research Group", &key("85d6be7857556d337f4452fe42d506a80103808afb0db2fd4abff6af4149f51b")));
+	check("Poly1305", &buffer, &unhex("a8061dc1305136c6c22b8baf0c0127a9"))
+}
+
+/// The "Hi There"-message from RFC 4231 (4.2) with a 32-byte key
+fn hmac_sha2_512() -> Result<(), Error<CpError>> {
+	let expected = unhex(concat!(
+		"cf768c6fd3f08f640f779ddbd9bc3842fe78a261f197da9c4a958510ac8226db",
+		"6b059f6de7340409ed8dded44666a63dcd5fc669fc89b50292781e39362dcb58"
+	));
+	
+	let mut buffer = [0u8; 64];
+	try_err!(backend::hmac_sha2_512(&mut buffer, b"Hi There", &key(&"0b".repeat(32))));
+	check("HMAC-SHA2-512", &buffer, &expected)
+}
+
+/// There is no known answer for an RNG; check that two outputs differ and are not all-zero
+fn random() -> Result<(), Error<CpError>> {
+	let (mut buffer0, mut buffer1) = ([0u8; 32], [0u8; 32]);
+	backend::random(&mut buffer0);
+	backend::random(&mut buffer1);
+	
+	if buffer0 == [0u8; 32] || buffer0 == buffer1 { throw_err!(CpError::Unsupported, "Self-test failed: the RNG produced an unexpected result (incompatible crypto-backend)") }
+	Ok(())
+}
+
+/// Checks equal and unequal inputs (including a length-mismatch)
+fn compare_constant_time() -> Result<(), Error<CpError>> {
+	let results = [
+		backend::compare_constant_time(b"Testolope", b"Testolope"),
+		!backend::compare_constant_time(b"Testolope", b"Testolopf"),
+		!backend::compare_constant_time(b"Testolope", b"Testolop")
+	];
+	if results.iter().any(|result| !result) { throw_err!(CpError::Unsupported, "Self-test failed: the constant-time comparison produced an unexpected result (incompatible crypto-backend)") }
+	Ok(())
+}
\ No newline at end of file
@@ -6,6 +6,8 @@ use std;
 pub enum CpError {
 	/// Invalid data (invalid encoding, integrity error etc.)
 	InvalidData,
+	/// The password does not match the key-check-value of the stream
+	WrongPassword,
 	/// Not enough resources to process data
 	ResourceError,
 	
@@ -47,4 +49,16 @@ impl From<std::num::ParseIntError> for CpError {
 	fn from(_: std::num::ParseIntError) -> Self {
 		CpError::InvalidData
 	}
+}
+
+/// Converts `error` into a `std::io::Error` (required by the `std::io::Read`/`std::io::Write`-adapters)
+pub fn into_io_error(error: etrace::Error<CpError>) -> std::io::Error {
+	let kind = match error.kind {
+		CpError::IOError(ref kind, _) => *kind,
+		CpError::InvalidData => std::io::ErrorKind::InvalidData,
+		CpError::WrongPassword => std::io::ErrorKind::PermissionDenied,
+		CpError::InvalidParameter => std::io::ErrorKind::InvalidInput,
+		_ => std::io::ErrorKind::Other
+	};
+	std::io::Error::new(kind, error.to_string())
 }
\ No newline at end of file
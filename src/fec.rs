@@ -0,0 +1,221 @@
+//! A systematic Reed-Solomon-erasure-code over GF(2^8) that is used to compute parity-blocks over
+//! groups of sealed chunks
+//!
+//! The parity-rows of the encoding-matrix form a Cauchy-matrix (`1 / (x_i + y_j)` with
+//! `x_i = data_shards + i` and `y_j = j`); because every square sub-matrix of a Cauchy-matrix is
+//! invertible, any `n` erased data-shards can be reconstructed from any `n` parity-shards.
+
+use super::{ Error, CpError };
+use super::asn1_der;
+use super::asn1_der::{ FromDerObject, IntoDerObject };
+
+
+/// The reducing polynomial of GF(2^8) (x^8 + x^4 + x^3 + x^2 + 1)
+const GF_POLYNOMIAL: u16 = 0x11d;
+/// The amount of data-chunks per parity-group (see `Parity::from_percent`)
+pub const PARITY_GROUP_CHUNKS: usize = 20;
+
+
+
+/// The parity-configuration of a stream: every group of `data_chunks` sealed chunks is followed by
+/// `parity_chunks` parity-blocks
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Parity {
+	pub data_chunks: usize,
+	pub parity_chunks: usize
+}
+impl Parity {
+	/// Creates a configuration with `PARITY_GROUP_CHUNKS` data-chunks per group and at least
+	/// `percent`% (1 to 100) parity
+	pub fn from_percent(percent: u32) -> Result<Self, Error<CpError>> {
+		if percent < 1 || percent > 100 { throw_err!(CpError::InvalidParameter, format!("The parity must be between 1% and 100% (not {}%)", percent)) }
+		let parity_chunks = (PARITY_GROUP_CHUNKS * percent as usize + 99) / 100;
+		Ok(Parity{ data_chunks: PARITY_GROUP_CHUNKS, parity_chunks })
+	}
+	
+	/// Parses a serialized configuration
+	pub fn from_serialized(serialized: asn1_der::DerObject) -> Result<Self, Error<CpError>> {
+		let sequence: Vec<asn1_der::DerObject> = try_err!(Vec::<asn1_der::DerObject>::from_der_object(serialized), CpError::InvalidData);
+		if sequence.len() < 2 { throw_err!(CpError::InvalidData) }
+		
+		// Parse and validate the configuration
+		let data_chunks = try_err!(u64::from_der_object(sequence[0].clone()), CpError::InvalidData);
+		let parity_chunks = try_err!(u64::from_der_object(sequence[1].clone()), CpError::InvalidData);
+		if data_chunks < 1 || parity_chunks < 1 || data_chunks.checked_add(parity_chunks).map_or(true, |chunks| chunks > 256) {
+			throw_err!(CpError::InvalidData, format!("Invalid parity-configuration ({} data- and {} parity-chunks)", data_chunks, parity_chunks))
+		}
+		Ok(Parity{ data_chunks: data_chunks as usize, parity_chunks: parity_chunks as usize })
+	}
+	
+	/// Serializes the configuration
+	pub fn serialize(&self) -> asn1_der::DerObject {
+		let sequence: Vec<asn1_der::DerObject> = vec![
+			(self.data_chunks as u64).into_der_object(),
+			(self.parity_chunks as u64).into_der_object()
+		];
+		sequence.into_der_object()
+	}
+}
+
+
+
+/// The arithmetic in GF(2^8)
+struct Gf256 {
+	exp: [u8; 512],
+	log: [u8; 256]
+}
+impl Gf256 {
+	/// Computes the exponent- and logarithm-tables
+	pub fn new() -> Self {
+		let (mut exp, mut log, mut x) = ([0u8; 512], [0u8; 256], 1u16);
+		for i in 0..255 {
+			exp[i] = x as u8;
+			log[x as usize] = i as u8;
+			x <<= 1;
+			if x & 0x100 != 0 { x ^= GF_POLYNOMIAL }
+		}
+		for i in 255..512 { exp[i] = exp[i - 255] }
+		Gf256{ exp, log }
+	}
+	
+	pub fn mul(&self, a: u8, b: u8) -> u8 {
+		if a == 0 || b == 0 { return 0 }
+		self.exp[self.log[a as usize] as usize + self.log[b as usize] as usize]
+	}
+	pub fn inv(&self, a: u8) -> u8 {
+		assert_ne!(a, 0, "Zero has no inverse in GF(2^8)");
+		self.exp[255 - self.log[a as usize] as usize]
+	}
+	
+	/// Computes `target ^= factor * source` for every byte
+	pub fn mul_add(&self, target: &mut[u8], source: &[u8], factor: u8) {
+		if factor == 0 { return }
+		
+		// Precompute the products with `factor`
+		let mut products = [0u8; 256];
+		for (i, product) in products.iter_mut().enumerate() { *product = self.mul(i as u8, factor) }
+		for (target, source) in target.iter_mut().zip(source.iter()) { *target ^= products[*source as usize] }
+	}
+}
+
+
+
+/// A Reed-Solomon-erasure-code with `data_shards` data- and `parity_shards` parity-shards
+pub struct ReedSolomon {
+	data_shards: usize,
+	parity_shards: usize,
+	gf: Gf256
+}
+impl ReedSolomon {
+	/// Creates the code for `parity`
+	pub fn with_parity(parity: Parity) -> Result<Self, Error<CpError>> {
+		Self::new(parity.data_chunks, parity.parity_chunks)
+	}
+	/// Creates a new code (`data_shards + parity_shards` must not exceed 256)
+	pub fn new(data_shards: usize, parity_shards: usize) -> Result<Self, Error<CpError>> {
+		if data_shards == 0 || parity_shards == 0 || data_shards + parity_shards > 256 {
+			throw_err!(CpError::InvalidParameter, format!("Invalid Reed-Solomon-parameters ({} data- and {} parity-shards)", data_shards, parity_shards))
+		}
+		Ok(ReedSolomon{ data_shards, parity_shards, gf: Gf256::new() })
+	}
+	
+	/// The coefficient of the `data_index`th data-shard in the `parity_index`th parity-shard
+	fn coefficient(&self, parity_index: usize, data_index: usize) -> u8 {
+		self.gf.inv(((self.data_shards + parity_index) ^ data_index) as u8)
+	}
+	
+	/// Computes the parity-shards over `data` (missing data-shards at the end of a group are treated
+	/// as zero-shards)
+	///
+	/// All shards must have the same length.
+	pub fn encode(&self, data: &[&[u8]], parity: &mut[Vec<u8>]) -> Result<(), Error<CpError>> {
+		// Validate input
+		if data.len() > self.data_shards || parity.len() != self.parity_shards { throw_err!(CpError::InvalidParameter, "Invalid amount of shards") }
+		let shard_length = parity.first().map(|shard| shard.len()).unwrap_or(0);
+		if data.iter().any(|shard| shard.len() != shard_length) || parity.iter().any(|shard| shard.len() != shard_length) {
+			throw_err!(CpError::InvalidParameter, "The shards have different lengths")
+		}
+		
+		// Compute parity
+		for (parity_index, parity_shard) in parity.iter_mut().enumerate() {
+			for byte in parity_shard.iter_mut() { *byte = 0x00 }
+			for (data_index, data_shard) in data.iter().enumerate() {
+				self.gf.mul_add(parity_shard, data_shard, self.coefficient(parity_index, data_index))
+			}
+		}
+		Ok(())
+	}
+	
+	/// Reconstructs the data-shards at the indices in `erased` from the remaining data-shards and
+	/// the `(parity_index, parity_shard)`-pairs in `parity`
+	///
+	/// Exactly `erased.len()` parity-shards must be passed; the contents of the erased data-shards
+	/// are ignored and replaced.
+	pub fn reconstruct(&self, data: &mut[Vec<u8>], erased: &[usize], parity: &[(usize, &[u8])]) -> Result<(), Error<CpError>> {
+		// Validate input
+		if data.len() > self.data_shards || erased.len() != parity.len() { throw_err!(CpError::InvalidParameter, "Invalid amount of shards") }
+		if erased.iter().any(|index| *index >= data.len()) || parity.iter().any(|&(index, _)| index >= self.parity_shards) {
+			throw_err!(CpError::InvalidParameter, "Invalid shard-index")
+		}
+		let shard_length = data.first().map(|shard| shard.len()).unwrap_or(0);
+		if data.iter().any(|shard| shard.len() != shard_length) || parity.iter().any(|&(_, shard)| shard.len() != shard_length) {
+			throw_err!(CpError::InvalidParameter, "The shards have different lengths")
+		}
+		
+		// Compute the syndromes (the parity-shards minus the contributions of the intact data-shards)
+		let mut syndromes: Vec<Vec<u8>> = Vec::new();
+		for &(parity_index, parity_shard) in parity.iter() {
+			let mut syndrome = parity_shard.to_vec();
+			for (data_index, data_shard) in data.iter().enumerate() {
+				if !erased.contains(&data_index) { self.gf.mul_add(&mut syndrome, data_shard, self.coefficient(parity_index, data_index)) }
+			}
+			syndromes.push(syndrome);
+		}
+		
+		// Invert the Cauchy-sub-matrix and multiply it with the syndromes
+		let inverse = try_err!(self.invert(erased, parity));
+		for (row, &data_index) in erased.iter().enumerate() {
+			for byte in data[data_index].iter_mut() { *byte = 0x00 }
+			for (column, syndrome) in syndromes.iter().enumerate() {
+				self.gf.mul_add(&mut data[data_index], syndrome, inverse[row][column])
+			}
+		}
+		Ok(())
+	}
+	
+	/// Inverts the sub-matrix of the parity-rows in `parity` and the data-columns in `erased` using
+	/// the Gauss-Jordan-elimination
+	fn invert(&self, erased: &[usize], parity: &[(usize, &[u8])]) -> Result<Vec<Vec<u8>>, Error<CpError>> {
+		let size = erased.len();
+		let mut matrix: Vec<Vec<u8>> = parity.iter()
+			.map(|&(parity_index, _)| erased.iter().map(|data_index| self.coefficient(parity_index, *data_index)).collect())
+			.collect();
+		let mut inverse: Vec<Vec<u8>> = (0..size).map(|i| (0..size).map(|j| if i == j { 1 } else { 0 }).collect()).collect();
+		
+		for column in 0..size {
+			// Find the pivot and normalize its row
+			let pivot = match (column .. size).find(|row| matrix[*row][column] != 0) {
+				Some(pivot) => pivot,
+				None => throw_err!(CpError::InvalidParameter, "The matrix is singular (duplicate shard-indices?)")
+			};
+			matrix.swap(column, pivot);
+			inverse.swap(column, pivot);
+			
+			let factor = self.gf.inv(matrix[column][column]);
+			for j in 0..size {
+				matrix[column][j] = self.gf.mul(matrix[column][j], factor);
+				inverse[column][j] = self.gf.mul(inverse[column][j], factor);
+			}
+			
+			// Eliminate the column in all other rows
+			for row in (0..size).filter(|row| *row != column) {
+				let factor = matrix[row][column];
+				for j in 0..size {
+					matrix[row][j] ^= self.gf.mul(factor, matrix[column][j]);
+					inverse[row][j] ^= self.gf.mul(factor, inverse[column][j]);
+				}
+			}
+		}
+		Ok(inverse)
+	}
+}
\ No newline at end of file
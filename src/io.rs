@@ -1,45 +1,60 @@
 use std;
 use super::{ Error, CpError };
 
+/// The IO-interface used by `stream::Encryptor` and `stream::Decryptor` to read and write the data
 pub trait Io {
+	/// Reads the next chunk into `chunk_buffer` and returns `(chunk_length, is_last)`
 	fn read_chunk(&mut self, chunk_buffer: &mut[u8]) -> Result<(usize, bool), Error<CpError>>;
+	/// Writes a processed chunk
 	fn write_chunk(&mut self, data: &[u8]) -> Result<(), Error<CpError>>;
+	/// Fills `buffer` completely or fails
 	fn read_exact(&mut self, buffer: &mut[u8]) -> Result<(), Error<CpError>>;
+	/// Writes `data` completely or fails
 	fn write_exact(&mut self, data: &[u8]) -> Result<(), Error<CpError>>;
 }
 
 
 
-pub struct Stdio {
-	stdin: std::io::Stdin,
-	stdout: std::io::Stdout,
+/// An `Io`-implementation over an arbitrary `std::io::Read` and `std::io::Write`
+pub struct StreamIo<R: std::io::Read, W: std::io::Write> {
+	reader: R,
+	writer: W,
 	next_chunk: (Vec<u8>, usize)
 }
-impl Stdio {
-	pub fn new() -> Self {
-		Stdio{ stdin: std::io::stdin(), stdout: std::io::stdout(), next_chunk: (vec![0u8; 0], std::usize::MAX) }
+impl<R: std::io::Read, W: std::io::Write> StreamIo<R, W> {
+	/// Creates a new `Io`-instance that reads from `reader` and writes to `writer`
+	pub fn new(reader: R, writer: W) -> Self {
+		StreamIo{ reader, writer, next_chunk: (vec![0u8; 0], std::usize::MAX) }
+	}
+	
+	/// Returns a mutable reference to the underlying reader
+	pub fn reader_mut(&mut self) -> &mut R {
+		&mut self.reader
+	}
+	
+	/// Returns the underlying reader and writer
+	pub fn into_inner(self) -> (R, W) {
+		(self.reader, self.writer)
 	}
 	
 	fn read_next_chunk(&mut self, chunk_size: usize) -> Result<(), Error<CpError>> {
-		use std::io::Read;
-		
 		// Reset chunk
 		self.next_chunk.0.resize(chunk_size, 0x00);
 		self.next_chunk.1 = 0;
 		
 		// Read until EOF or any error other than `ErrorKind::Interrupted`
 		'read_loop: loop {
-			let bytes_read = match self.stdin.read(&mut self.next_chunk.0[self.next_chunk.1 ..]) {
+			let bytes_read = match self.reader.read(&mut self.next_chunk.0[self.next_chunk.1 ..]) {
 				Ok(bytes_read) => bytes_read,
 				Err(ref error) if error.kind() == std::io::ErrorKind::Interrupted => continue 'read_loop,
-				Err(error) => throw_err!(error.into(), "Failed to read from stdin")
+				Err(error) => throw_err!(error.into(), "Failed to read from input")
 			};
 			if bytes_read > 0 { self.next_chunk.1 += bytes_read }
 				else { return Ok(()) }
 		}
 	}
 }
-impl Io for Stdio {
+impl<R: std::io::Read, W: std::io::Write> Io for StreamIo<R, W> {
 	fn read_chunk(&mut self, chunk_buffer: &mut[u8]) -> Result<(usize, bool), Error<CpError>> {
 		// Prefetch chunk
 		if self.next_chunk.1 == std::usize::MAX { try_err!(self.read_next_chunk(chunk_buffer.len())) }
@@ -57,14 +72,21 @@ impl Io for Stdio {
 	}
 	
 	fn read_exact(&mut self, buffer: &mut[u8]) -> Result<(), Error<CpError>> {
-		use std::io::Read;
-		try_err_from!(self.stdin.read_exact(buffer), "Failed to read from stdin".to_owned());
+		try_err_from!(self.reader.read_exact(buffer), "Failed to read from input".to_owned());
 		Ok(())
 	}
 	fn write_exact(&mut self, data: &[u8]) -> Result<(), Error<CpError>> {
-		use std::io::Write;
-		try_err_from!(self.stdout.write_all(data), "Failed to write to stdout".to_owned());
-		try_err_from!(self.stdout.flush());
+		try_err_from!(self.writer.write_all(data), "Failed to write to output".to_owned());
+		try_err_from!(self.writer.flush());
 		Ok(())
 	}
+}
+
+
+
+/// An `Io`-instance over StdIn and StdOut
+pub type Stdio = StreamIo<std::io::Stdin, std::io::Stdout>;
+/// Creates a new `Io`-instance over StdIn and StdOut
+pub fn stdio() -> Stdio {
+	StreamIo::new(std::io::stdin(), std::io::stdout())
 }
\ No newline at end of file
@@ -0,0 +1,70 @@
+//! CryptoPipe is a fast and secure stream-encryption-library
+//!
+//! The easiest way to seal or open a stream is to use the builders:
+//! ```no_run
+//! # extern crate crypto_pipe;
+//! use crypto_pipe::{ SealBuilder, OpenBuilder, io };
+//!
+//! # fn main() {
+//! // Seal StdIn to StdOut
+//! SealBuilder::new("Password".to_string()).seal(&mut io::stdio()).unwrap();
+//!
+//! // Open StdIn to StdOut
+//! OpenBuilder::new("Password".to_string()).open(&mut io::stdio()).unwrap();
+//! # }
+//! ```
+//!
+//! If you already work with `std::io::Read`/`std::io::Write`-objects, you can use the
+//! `SealingWriter` and `OpeningReader`-adapters instead. If you need more control, you can use
+//! `stream::Encryptor` and `stream::Decryptor` together with the algorithms in `crypto` directly. Multiple files can
+//! be sealed into one archive with an encrypted index (see `archive`); sealed streams can be ASCII-armored for
+//! text-only channels (see `armor`) or split into fixed-size volumes (see `volume`). Data can be
+//! appended to streams that are sealed in the appendable chunk-format (see `append`); streams with a
+//! seek-index can be read at random offsets (see `seekable`) and an interrupted sealing-operation of a file can be
+//! resumed (see `resume`).
+//!
+//! With the `async`-feature enabled, `AsyncSealingWriter` and `AsyncOpeningReader` provide tokio's
+//! `AsyncWrite`/`AsyncRead` for the same stream-format. A C-API is provided by the separate
+//! `crypto_pipe_ffi`-crate in `ffi/`.
+//!
+//! The cryptographic primitives are provided by libsodium by default; the `pure-rust`-feature
+//! replaces libsodium with pure-Rust implementations that produce bit-identical streams.
+
+#[macro_use] extern crate etrace;
+extern crate asn1_der;
+extern crate cli;
+#[cfg(feature = "async")] extern crate tokio;
+#[cfg(feature = "pure-rust")] extern crate argon2;
+#[cfg(feature = "pure-rust")] extern crate chacha20;
+#[cfg(feature = "pure-rust")] extern crate poly1305;
+#[cfg(feature = "pure-rust")] extern crate hmac;
+#[cfg(feature = "pure-rust")] extern crate sha2;
+#[cfg(feature = "pure-rust")] extern crate getrandom;
+#[cfg(feature = "pure-rust")] extern crate subtle;
+#[cfg(feature = "zstd")] extern crate zstd;
+#[cfg(feature = "lz4")] extern crate lz4_flex;
+
+pub mod error;
+pub mod crypto;
+pub mod io;
+pub mod stream;
+pub mod fec;
+pub mod compression;
+pub mod padding;
+pub mod metadata;
+pub mod archive;
+pub mod armor;
+pub mod volume;
+pub mod append;
+pub mod seekable;
+pub mod resume;
+pub mod builder;
+pub mod adapters;
+#[cfg(feature = "async")] pub mod async_adapters;
+#[cfg(test)] mod tests;
+
+pub use error::CpError;
+pub use etrace::Error;
+pub use builder::{ SealBuilder, OpenBuilder };
+pub use adapters::{ SealingWriter, OpeningReader, OpeningWriter };
+#[cfg(feature = "async")] pub use async_adapters::{ AsyncSealingWriter, AsyncOpeningReader };
\ No newline at end of file
@@ -1,19 +1,24 @@
 #[macro_use] extern crate etrace;
-extern crate asn1_der;
 extern crate cli;
+extern crate crypto_pipe;
 
-mod error;
-#[cfg(test)] mod tests;
-mod crypto;
-mod io;
-mod stream;
+mod cmd;
 
-use std::env::var;
 use std::collections::HashMap;
-use error::CpError;
+use crypto_pipe::{ CpError, SealBuilder, OpenBuilder, crypto, io, stream, fec, compression, padding, armor, volume, append, resume };
+use crypto_pipe::builder::{ DEFAULT_ARGON2I_TIME_COST, DEFAULT_ARGON2I_MEMORY_COST_MIB, DEFAULT_ARGON2I_PARALLELISM, DEFAULT_MIN_PASSWORD_ENTROPY };
+use crypto_pipe::stream::{ Position, Salvage };
+use crypto_pipe::metadata::Metadata;
+use cmd::password;
+use cmd::progress::{ Progress, Measure };
+use cmd::error::ErrorFormat;
 use etrace::Error;
 
 
+/// The default amount of words for generated passphrases (88 bits of entropy)
+const DEFAULT_PASSPHRASE_WORDS: usize = 8;
+
+
 
 /// Prints `message` to stderr and terminates with `code`
 fn die(text: &str, code: i32) -> ! {
@@ -27,12 +32,22 @@ fn die_licenses() -> ! {
 	die(include_str!("texts/licenses.txt"), 0)
 }
 
-/// Prints `error` and the help-text to stderr and terminates with `1`
-fn die_error_help(error: &str) -> ! {
-	// Replace `%PROGRAM_NAME%` and build message
-	let program_name = std::env::args().next().unwrap_or("<program_name>".to_owned());
-	let text = format!("{}\n\n{}", error, include_str!("texts/help.txt").replace("%PROGRAM_NAME%", &program_name));
-	die(&text, 1)
+/// Prints `error` (and the stream-`position` if any) in the requested format to stderr and
+/// terminates with the exit-code of the error-class (the help-text is only printed on CLI-errors)
+fn die_error(error: &Error<CpError>, position: Option<Position>) -> ! {
+	let text = match ErrorFormat::from_args() {
+		ErrorFormat::Json => {
+			eprintln!("{}", cmd::error::to_json(error, position));
+			std::process::exit(cmd::error::exit_code(&error.kind))
+		},
+		ErrorFormat::Text if error.kind == CpError::CliError => {
+			// Replace `%PROGRAM_NAME%` and append the help-text
+			let program_name = std::env::args().next().unwrap_or("<program_name>".to_owned());
+			format!("{}\n\n{}", cmd::error::to_text(error, position), include_str!("texts/help.txt").replace("%PROGRAM_NAME%", &program_name))
+		},
+		ErrorFormat::Text => cmd::error::to_text(error, position)
+	};
+	die(&text, cmd::error::exit_code(&error.kind))
 }
 
 /// Prints the help-text to stderr and terminates with `0`
@@ -45,81 +60,467 @@ fn die_help() -> ! {
 
 
 
+/// The switches that are shared by `seal` and `seal-dir`
+fn seal_switches() -> Vec<(&'static str, cli::SwitchParser)> {
+	password::switches().into_iter().chain(vec![
+		cmd::error::switch(),
+		("--allow-weak-password", cmd::flag()),
+		("--min-entropy=", cli::SwitchParser::with_default(DEFAULT_MIN_PASSWORD_ENTROPY, &cli::parsers::parse_from_str::<f64>)),
+		("--generate-password", cmd::flag()),
+		("--verbose", cmd::flag()),
+		("--parity=", cli::SwitchParser::with_default("none".to_string(), &cli::parsers::parse_from_str::<String>)),
+		("--compress=", cli::SwitchParser::with_default("none".to_string(), &cli::parsers::parse_from_str::<String>)),
+		("--compress-level=", cli::SwitchParser::with_default("default".to_string(), &cli::parsers::parse_from_str::<String>)),
+		("--pad=", cli::SwitchParser::with_default("none".to_string(), &cli::parsers::parse_from_str::<String>)),
+		
+		("--pbkdf-time-cost=", cli::SwitchParser::with_default(DEFAULT_ARGON2I_TIME_COST, &cli::parsers::parse_from_str::<u32>)),
+		("--pbkdf-memory-cost=", cli::SwitchParser::with_default(DEFAULT_ARGON2I_MEMORY_COST_MIB, &cli::parsers::parse_from_str::<u32>)),
+		("--pbkdf-parallelism=", cli::SwitchParser::with_default(DEFAULT_ARGON2I_PARALLELISM, &cli::parsers::parse_from_str::<u32>)),
+		
+		("--pbkdf-algo=", cli::SwitchParser::with_default("Argon2i".to_string(), &cli::parsers::parse_from_str::<String>)),
+		("--kdf-algo=", cli::SwitchParser::with_default("HMAC-SHA512".to_string(), &cli::parsers::parse_from_str::<String>)),
+		("--auth-enc-algo=", cli::SwitchParser::with_default("ChaChaPoly".to_string(), &cli::parsers::parse_from_str::<String>))
+	]).collect()
+}
+
 /// Parses the CLI-verb and it's switches
 fn parse_cli() -> Result<cli::CliResult, Error<CpError>> {
 	let verbs = vec![
-		("help", cli::VerbParser::new()),
-		("licenses", cli::VerbParser::new()),
-		("seal", cli::VerbParser::with_switches(vec![
-			("--password=", cli::SwitchParser::with_default(String::new(), &cli::parsers::parse_from_str::<String>)),
-			
-			("--pbkdf-time-cost=", cli::SwitchParser::with_default(12u32, &cli::parsers::parse_from_str::<u32>)),
-			("--pbkdf-memory-cost=", cli::SwitchParser::with_default(512u32, &cli::parsers::parse_from_str::<u32>)),
-			("--pbkdf-parallelism=", cli::SwitchParser::with_default(4u32, &cli::parsers::parse_from_str::<u32>)),
-			
-			("--pbkdf-algo=", cli::SwitchParser::with_default("Argon2i".to_string(), &cli::parsers::parse_from_str::<String>)),
-			("--kdf-algo=", cli::SwitchParser::with_default("HMAC-SHA512".to_string(), &cli::parsers::parse_from_str::<String>)),
-			("--auth-enc-algo=", cli::SwitchParser::with_default("ChaChaPoly".to_string(), &cli::parsers::parse_from_str::<String>))
+		("help", cli::VerbParser::with_switches(vec![cmd::error::switch()])),
+		("licenses", cli::VerbParser::with_switches(vec![cmd::error::switch()])),
+		("selftest", cli::VerbParser::with_switches(vec![cmd::error::switch()])),
+		("genpass", cli::VerbParser::with_switches(vec![
+			cmd::error::switch(),
+			("--words=", cli::SwitchParser::with_default(DEFAULT_PASSPHRASE_WORDS, &cli::parsers::parse_from_str::<usize>)),
+			("--separator=", cli::SwitchParser::with_default("-".to_string(), &cli::parsers::parse_from_str::<String>))
 		])),
-		("open", cli::VerbParser::with_switches(vec![
-			("--password=", cli::SwitchParser::with_default(String::new(), &cli::parsers::parse_from_str::<String>))
-		]))
+		("seal", cli::VerbParser::with_switches(seal_switches().into_iter().chain(vec![
+			("--armor", cmd::flag()),
+			("--armor-line-length=", cli::SwitchParser::with_default(armor::DEFAULT_LINE_LENGTH, &cli::parsers::parse_from_str::<usize>)),
+			("--volume-size=", cli::SwitchParser::with_default("none".to_string(), &cli::parsers::parse_from_str::<String>)),
+			("--in=", cli::SwitchParser::with_default(String::new(), &cli::parsers::parse_from_str::<String>)),
+			("--out=", cli::SwitchParser::with_default(String::new(), &cli::parsers::parse_from_str::<String>)),
+			("--resume", cmd::flag()),
+			("--progress", cmd::flag()),
+			("--appendable", cmd::flag()),
+			("--seek-index", cmd::flag()),
+			("--metadata", cmd::flag()),
+			("--filename=", cli::SwitchParser::with_default(String::new(), &cli::parsers::parse_from_str::<String>)),
+			("--content-type=", cli::SwitchParser::with_default(String::new(), &cli::parsers::parse_from_str::<String>)),
+			("--tags=", cli::SwitchParser::with_default(String::new(), &cli::parsers::parse_from_str::<String>))
+		]).collect())),
+		("seal-dir", cli::VerbParser::with_switches(seal_switches().into_iter().chain(vec![
+			("--dir=", cli::SwitchParser::with_default(String::new(), &cli::parsers::parse_from_str::<String>))
+		]).collect())),
+		("open", cli::VerbParser::with_switches(password::switches().into_iter().chain(vec![
+			cmd::error::switch(),
+			("--max-ratio=", cli::SwitchParser::with_default(compression::DEFAULT_MAX_COMPRESSION_RATIO, &cli::parsers::parse_from_str::<u64>)),
+			("--salvage", cmd::flag()),
+			("--salvage-fill=", cli::SwitchParser::with_default("zeros".to_string(), &cli::parsers::parse_from_str::<String>)),
+			("--restore-metadata", cmd::flag()),
+			("--restore-special-bits", cmd::flag()),
+			("--volumes=", cli::SwitchParser::with_default(String::new(), &cli::parsers::parse_from_str::<String>)),
+			("--progress", cmd::flag())
+		]).collect())),
+		("append", cli::VerbParser::with_switches(password::switches().into_iter().chain(vec![
+			cmd::error::switch(),
+			("--file=", cli::SwitchParser::with_default(String::new(), &cli::parsers::parse_from_str::<String>))
+		]).collect())),
+		("open-dir", cli::VerbParser::with_switches(password::switches().into_iter().chain(vec![
+			cmd::error::switch(),
+			("--max-ratio=", cli::SwitchParser::with_default(compression::DEFAULT_MAX_COMPRESSION_RATIO, &cli::parsers::parse_from_str::<u64>)),
+			("--out=", cli::SwitchParser::with_default(".".to_string(), &cli::parsers::parse_from_str::<String>)),
+			("--extract=", cli::SwitchParser::with_default(String::new(), &cli::parsers::parse_from_str::<String>)),
+			("--list", cmd::flag()),
+			("--restore-special-bits", cmd::flag())
+		]).collect())),
+		("info", cli::VerbParser::with_switches(password::switches().into_iter().chain(vec![
+			cmd::error::switch()
+		]).collect()))
 	];
 	Ok(try_err_from!(cli::parse_verbs(verbs), "Failed to parse CLI-arguments"))
 }
 
-/// Get the password either from the "--password="-CLI-switch or from the "CRYPTO_PIPE_PASSWORD"-
-/// environment-variable
-fn get_password(switches: &mut HashMap<String, cli::SwitchParser>) -> Result<String, Error<CpError>> {
-	// Get switch-value
-	let password = try_err!(switches.remove("--password=").unwrap().into_value::<String>(), CpError::CliError, "Failed to parse \"--password=\"");
-	if !password.is_empty() { return Ok(password) }
+/// Gets the password and configures a `SealBuilder` from the switches that are shared by `seal`
+/// and `seal-dir`
+fn seal_builder(switches: &mut HashMap<String, cli::SwitchParser>) -> Result<SealBuilder, Error<CpError>> {
+	// Read PBKDF-params
+	let pbkdf_params: (u32, u32, u32) = (
+		*try_err!(switches["--pbkdf-time-cost="].get::<u32>(), CpError::CliError, "Failed to parse \"--pbkdf-time-cost=\""),
+		*try_err!(switches["--pbkdf-memory-cost="].get::<u32>(), CpError::CliError, "Failed to parse \"--pbkdf-memory-cost=\""),
+		*try_err!(switches["--pbkdf-parallelism="].get::<u32>(), CpError::CliError, "Failed to parse \"--pbkdf-parallelism=\"")
+	);
+	
+	// Get or generate the password and check its strength
+	let password = match try_err!(cmd::is_set(switches, "--generate-password")) {
+		true if try_err!(password::is_switch_given(switches)) => {
+			throw_err!(CpError::CliError, "\"--generate-password\" cannot be combined with \"--password=\", \"--password-fd=\" or \"--password-file=\"")
+		},
+		true => {
+			let password = crypto::passphrase::generate(DEFAULT_PASSPHRASE_WORDS, "-");
+			eprintln!("Generated password: {}", password.as_str());
+			password
+		},
+		false => try_err!(password::get_password(switches, true))
+	};
+	if try_err!(cmd::is_set(switches, "--verbose")) {
+		eprintln!("Password-strength: ~{:.0} bits of entropy (estimated)", password.estimate_entropy())
+	}
+	let min_entropy = *try_err!(switches["--min-entropy="].get::<f64>(), CpError::CliError, "Failed to parse \"--min-entropy=\"");
+	if min_entropy.is_nan() || min_entropy < 0.0 { throw_err!(CpError::CliError, "\"--min-entropy=\" must not be negative") }
+	let builder = match try_err!(cmd::is_set(switches, "--allow-weak-password")) || min_entropy <= 0.0 {
+		true => SealBuilder::new(password),
+		false => SealBuilder::new(password).min_password_entropy(min_entropy)
+	};
+	
+	// Configure the sealing-operation
+	let builder = {
+		let builder = match try_err!(switches["--pbkdf-algo="].get::<String>(), CpError::CliError, "Failed to parse \"--pbkdf-algo=\"").as_str() {
+			"Argon2i" => builder.argon2i(pbkdf_params.0, pbkdf_params.1, pbkdf_params.2),
+			algo => throw_err!(CpError::CliError, format!("Unsupported PBKDF-algorithm \"{}\"", algo))
+		};
+		let builder = match try_err!(switches["--kdf-algo="].get::<String>(), CpError::CliError, "Failed to parse \"--kdf-algo=\"").as_str() {
+			"HMAC-SHA512" => builder.kdf(crypto::kdf::HmacSha2512::new()),
+			algo => throw_err!(CpError::CliError, format!("Unsupported KDF-algorithm \"{}\"", algo))
+		};
+		match try_err!(switches["--auth-enc-algo="].get::<String>(), CpError::CliError, "Failed to parse \"--auth-enc-algo=\"").as_str() {
+			"ChaChaPoly" => builder.auth_enc(crypto::auth_enc::ChaCha20Poly1305::new()),
+			algo => throw_err!(CpError::CliError, format!("Unsupported authenticated-encryption-algorithm \"{}\"", algo))
+		}
+	};
 	
-	// Try to read environment-var
-	if let Ok(password) = var("CRYPTO_PIPE_PASSWORD") { return Ok(password) }
-	throw_err!(CpError::CliError, "You either need to set the \"--password=\"-switch or the \"CRYPTO_PIPE_PASSWORD\"-environment-variable")
+	// Configure the parity
+	let builder = match try_err!(switches["--parity="].get::<String>(), CpError::CliError, "Failed to parse \"--parity=\"").as_str() {
+		"none" => builder,
+		parity => {
+			let percent = try_err!(parity.trim_right_matches('%').parse::<u32>(), CpError::CliError, format!("Invalid parity \"{}\"", parity));
+			builder.parity(try_err!(fec::Parity::from_percent(percent)))
+		}
+	};
+	
+	// Configure the compression
+	let level = try_err!(switches["--compress-level="].get::<String>(), CpError::CliError, "Failed to parse \"--compress-level=\"").clone();
+	let builder = match try_err!(switches["--compress="].get::<String>(), CpError::CliError, "Failed to parse \"--compress=\"").as_str() {
+		"none" => builder,
+		algorithm => {
+			let algorithm = try_err!(compression::Algorithm::from_name(algorithm), CpError::CliError);
+			let level = match (algorithm, level.as_str()) {
+				(compression::Algorithm::Zstd, "default") => compression::DEFAULT_ZSTD_LEVEL,
+				(compression::Algorithm::Lz4, "default") => 0,
+				(_, level) => try_err!(level.parse::<u32>(), CpError::CliError, format!("Invalid compression-level \"{}\"", level))
+			};
+			builder.compression(try_err!(compression::Compression::new(algorithm, level)))
+		}
+	};
+	
+	// Configure the padding
+	let builder = match try_err!(switches["--pad="].get::<String>(), CpError::CliError, "Failed to parse \"--pad=\"").as_str() {
+		"none" => builder,
+		spec => builder.padding(try_err!(padding::Padding::from_spec(spec), CpError::CliError))
+	};
+	
+	Ok(builder)
 }
 
 /// Reads and executes the verb
 fn run() -> Result<(), Error<CpError>> {
 	// Read and process CLI-input
 	let (verb, mut switches): (String, HashMap<String, cli::SwitchParser>) = try_err!(parse_cli());
+	try_err!(cmd::error::validate(try_err!(
+		switches[cmd::error::ERROR_FORMAT_SWITCH].get::<String>(), CpError::CliError, "Failed to parse \"--error-format=\""
+	)));
+	
+	// Verify the crypto-backend before any data is processed
+	if ["seal", "open", "seal-dir", "open-dir", "append"].contains(&verb.as_str()) { try_err!(crypto::selftest()) }
 	
 	match verb.as_str() {
 		"help" => die_help(),
 		"licenses" => die_licenses(),
+		"selftest" => {
+			// Run all known-answer-tests and collect the results
+			let (mut report, mut failed) = (format!("Crypto-backend: {}\n", crypto::backend::version()), 0);
+			for &(primitive, test) in crypto::selftest::TESTS.iter() {
+				match test() {
+					Ok(()) => report += &format!("    {}: OK\n", primitive),
+					Err(_) => { report += &format!("    {}: FAILED\n", primitive); failed += 1 }
+				}
+			}
+			
+			if failed > 0 { throw_err!(CpError::Unsupported, format!("{}\n{} self-test(s) failed", report, failed)) }
+			die(&report, 0)
+		},
+		"genpass" => {
+			// Read the passphrase-params
+			let words = *try_err!(switches["--words="].get::<usize>(), CpError::CliError, "Failed to parse \"--words=\"");
+			let separator = try_err!(switches["--separator="].get::<String>(), CpError::CliError, "Failed to parse \"--separator=\"");
+			if words == 0 { throw_err!(CpError::CliError, "\"--words=\" must be greater than 0") }
+			
+			// Print the passphrase to stdout and the entropy to stderr
+			println!("{}", crypto::passphrase::generate(words, separator).as_str());
+			eprintln!("Entropy: {:.0} bits ({} words from a {}-word list)", crypto::passphrase::entropy(words), words, crypto::passphrase::wordlist().len());
+			Ok(())
+		},
 		"seal" => {
-			// Read PBKDF-params
-			let pbkdf_params: (u32, u32, u32) = (
-				*try_err!(switches["--pbkdf-time-cost="].get::<u32>(), CpError::CliError, "Failed to parse \"--pbkdf-time-cost=\""),
-				*try_err!(switches["--pbkdf-memory-cost="].get::<u32>(), CpError::CliError, "Failed to parse \"--pbkdf-memory-cost=\""),
-				*try_err!(switches["--pbkdf-parallelism="].get::<u32>(), CpError::CliError, "Failed to parse \"--pbkdf-parallelism=\"")
-			);
+			// Read the input- and the output-path (stdin and stdout are used by default) and the volume-params
+			let input = try_err!(switches["--in="].get::<String>(), CpError::CliError, "Failed to parse \"--in=\"").clone();
+			let out = try_err!(switches["--out="].get::<String>(), CpError::CliError, "Failed to parse \"--out=\"").clone();
+			let volume_size = match try_err!(switches["--volume-size="].get::<String>(), CpError::CliError, "Failed to parse \"--volume-size=\"").as_str() {
+				"none" => None,
+				size => Some(try_err!(cmd::parse_size(size)))
+			};
+			let armored = try_err!(cmd::is_set(&switches, "--armor"));
+			let show_progress = try_err!(cmd::is_set(&switches, "--progress"));
 			
-			// Create stream-instance
-			let stream_instance = {
-				// Create algorithm-instances
-				let pbkdf = match try_err!(switches["--pbkdf-algo="].get::<String>(), CpError::CliError, "Failed to parse \"--pbkdf-algo=\"").as_str() {
-					"Argon2i" => crypto::pbkdf::Argon2i::new(pbkdf_params.0, pbkdf_params.1, pbkdf_params.2),
-					algo => throw_err!(CpError::CliError, format!("Unsupported PBKDF-algorithm \"{}\"", algo))
-				};
-				let kdf = match try_err!(switches["--kdf-algo="].get::<String>(), CpError::CliError, "Failed to parse \"--kdf-algo=\"").as_str() {
-					"HMAC-SHA512" => crypto::kdf::HmacSha2512::new(),
-					algo => throw_err!(CpError::CliError, format!("Unsupported KDF-algorithm \"{}\"", algo))
-				};
-				let auth_enc = match try_err!(switches["--auth-enc-algo="].get::<String>(), CpError::CliError, "Failed to parse \"--auth-enc-algo=\"").as_str() {
-					"ChaChaPoly" => crypto::auth_enc::ChaCha20Poly1305::new(),
-					algo => throw_err!(CpError::CliError, format!("Unsupported authenticated-encryption-algorithm \"{}\"", algo))
+			// Resume an interrupted sealing-operation (the configuration is read from the existing stream-header)
+			if try_err!(cmd::is_set(&switches, "--resume")) {
+				if input.is_empty() || out.is_empty() { throw_err!(CpError::CliError, "\"--resume\" requires \"--in=\" and \"--out=\"") }
+				if volume_size.is_some() || armored { throw_err!(CpError::CliError, "\"--resume\" cannot be combined with \"--volume-size=\" or \"--armor\"") }
+				let password = try_err!(password::get_password(&mut switches, false));
+				
+				let mut input_file = try_err_from!(std::fs::File::open(&input), format!("Failed to open \"{}\"", input));
+				let mut output_file = try_err_from!(std::fs::OpenOptions::new().read(true).write(true).open(&out), format!("Failed to open \"{}\"", out));
+				let progress = match show_progress {
+					true => Some(Progress::new(Measure::Plaintext, input_file.metadata().ok().map(|metadata| metadata.len()))),
+					false => None
 				};
-				crypto::StreamInstance::new(pbkdf, kdf, auth_enc)
+				let verified = try_err!(resume::resume(password, &mut input_file, &mut output_file, progress.as_ref().map(Progress::callback)));
+				if let Some(ref progress) = progress { progress.finish() }
+				try_err_from!(output_file.sync_all(), format!("Failed to sync \"{}\"", out));
+				if try_err!(cmd::is_set(&switches, "--verbose")) { eprintln!("Verified {} chunk(s) and resumed after them", verified) }
+				return Ok(())
+			}
+			let builder = try_err!(seal_builder(&mut switches));
+			let input_file = match input.as_str() {
+				"" => None,
+				path => Some(try_err_from!(std::fs::File::open(path), format!("Failed to open \"{}\"", path)))
 			};
 			
-			// Start runloop
-			try_err!(stream::Encryptor::new(try_err!(get_password(&mut switches)), &mut io::Stdio::new(), stream_instance)).runloop()
+			// Collect the metadata
+			let mut metadata = match (try_err!(cmd::is_set(&switches, "--metadata")), &input_file) {
+				(true, Some(_)) => try_err!(Metadata::from_file(&input)),
+				(true, None) => try_err!(cmd::metadata::from_stdin()),
+				(false, _) => Metadata::default()
+			};
+			let filename = try_err!(switches["--filename="].get::<String>(), CpError::CliError, "Failed to parse \"--filename=\"");
+			if !filename.is_empty() { metadata.filename = Some(filename.clone()) }
+			let content_type = try_err!(switches["--content-type="].get::<String>(), CpError::CliError, "Failed to parse \"--content-type=\"");
+			if !content_type.is_empty() { metadata.content_type = Some(content_type.clone()) }
+			metadata.tags = try_err!(cmd::metadata::parse_tags(try_err!(switches["--tags="].get::<String>(), CpError::CliError, "Failed to parse \"--tags=\"")));
+			let builder = if metadata != Metadata::default() { builder.metadata(metadata) } else { builder };
+			let builder = builder.appendable(try_err!(cmd::is_set(&switches, "--appendable")));
+			let builder = builder.seek_index(try_err!(cmd::is_set(&switches, "--seek-index")));
+			
+			// Report the progress against the size of the input (if it is a regular file)
+			let progress = match show_progress {
+				true => {
+					let total = match input_file {
+						Some(ref input_file) => input_file.metadata().ok().map(|metadata| metadata.len()),
+						None => cmd::std_file_size(cmd::STDIN_FD)
+					};
+					Some(Progress::new(Measure::Plaintext, total))
+				},
+				false => None
+			};
+			let builder = match progress {
+				Some(ref progress) => builder.progress(progress.callback()),
+				None => builder
+			};
+			let reader: Box<std::io::Read> = match input_file {
+				Some(input_file) => Box::new(input_file),
+				None => Box::new(std::io::stdin())
+			};
+			
+			// Start runloop (and split or armor the output if requested)
+			let result = match (volume_size, armored) {
+				(Some(_), true) => throw_err!(CpError::CliError, "\"--volume-size=\" cannot be combined with \"--armor\""),
+				(Some(_), false) if out.is_empty() => throw_err!(CpError::CliError, "\"--volume-size=\" requires \"--out=\""),
+				(Some(volume_size), false) => {
+					let total = try_err!(builder.seal_volumes(reader, &out, volume_size));
+					if try_err!(cmd::is_set(&switches, "--verbose")) { eprintln!("Wrote {} volume(s)", total) }
+					Ok(())
+				},
+				(None, true) => {
+					let line_length = *try_err!(switches["--armor-line-length="].get::<usize>(), CpError::CliError, "Failed to parse \"--armor-line-length=\"");
+					if line_length == 0 { throw_err!(CpError::CliError, "\"--armor-line-length=\" must be greater than 0") }
+					let mut io = io::StreamIo::new(reader, try_err!(armor::ArmoredWriter::new(try_err!(cmd::output_writer(&out)), line_length)));
+					try_err!(builder.seal(&mut io));
+					try_err!(io.into_inner().1.finish());
+					Ok(())
+				},
+				(None, false) => builder.seal(&mut io::StreamIo::new(reader, try_err!(cmd::output_writer(&out))))
+			};
+			if let Some(ref progress) = progress { progress.finish() }
+			result
 		},
 		"open" => {
-			// Start runloop
-			try_err!(stream::Decryptor::new(try_err!(get_password(&mut switches)), &mut io::Stdio::new())).runloop()
+			// Read the salvage-mode
+			let salvage = match try_err!(cmd::is_set(&switches, "--salvage")) {
+				true => match try_err!(switches["--salvage-fill="].get::<String>(), CpError::CliError, "Failed to parse \"--salvage-fill=\"").as_str() {
+					"zeros" => Salvage::Zeros,
+					"skip" => Salvage::Skip,
+					fill => throw_err!(CpError::CliError, format!("Unsupported salvage-fill \"{}\"", fill))
+				},
+				false => Salvage::Off
+			};
+			let special_bits = try_err!(cmd::is_set(&switches, "--restore-special-bits"));
+			if special_bits && !try_err!(cmd::is_set(&switches, "--restore-metadata")) {
+				throw_err!(CpError::CliError, "\"--restore-special-bits\" requires \"--restore-metadata\"")
+			}
+			
+			// Read the volume-set if any (the volumes are ordered and validated by their headers)
+			let volumes = match try_err!(switches["--volumes="].get::<String>(), CpError::CliError, "Failed to parse \"--volumes=\"").as_str() {
+				"" => None,
+				prefix => Some(try_err!(volume::VolumeSet::from_prefix(prefix)))
+			};
+			let reader: Box<std::io::Read> = match volumes {
+				Some(ref volumes) => Box::new(volumes.reader()),
+				None => Box::new(armor::DearmoringReader::new(std::io::stdin()))
+			};
+			
+			// Report the progress against the size of stdin (if it is a regular file; armored input is
+			// larger than the ciphertext, so the ETA is only an estimate)
+			let progress = match try_err!(cmd::is_set(&switches, "--progress")) {
+				true if volumes.is_some() => Some(Progress::new(Measure::Ciphertext, None)),
+				true => Some(Progress::new(Measure::Ciphertext, cmd::std_file_size(cmd::STDIN_FD))),
+				false => None
+			};
+			
+			// Start runloop and report the position if the stream is invalid (armored input is detected
+			// automatically)
+			let mut stdio = io::StreamIo::new(reader, std::io::stdout());
+			let max_ratio = *try_err!(switches["--max-ratio="].get::<u64>(), CpError::CliError, "Failed to parse \"--max-ratio=\"");
+			let builder = OpenBuilder::new(try_err!(password::get_password(&mut switches, false))).salvage(salvage).max_compression_ratio(max_ratio);
+			let builder = match progress {
+				Some(ref progress) => builder.progress(progress.callback()),
+				None => builder
+			};
+			let mut decryptor = try_err!(builder.build(&mut stdio));
+			if let Some(ref volumes) = volumes {
+				decryptor.set_key_verifier(Box::new(move |stream_instance, base_key| volumes.verify(stream_instance, base_key)))
+			}
+			let result = decryptor.runloop();
+			if let Some(ref progress) = progress { progress.finish() }
+			
+			// Report the repaired chunks and the missing plaintext-ranges
+			for repaired in decryptor.repaired_chunks().iter() {
+				eprintln!("Repaired chunk #{} at ciphertext-offset {} from the parity-blocks", repaired.chunk_index, repaired.ciphertext_offset)
+			}
+			if let Err(error) = result { die_error(&error, Some(decryptor.position())) }
+			let report = decryptor.salvage_report();
+			for damaged in report.damaged.iter() {
+				eprintln!(
+					"Missing plaintext: bytes {}..{} (chunk #{} at ciphertext-offset {} is damaged)",
+					damaged.plaintext_offset, damaged.plaintext_offset + damaged.plaintext_length, damaged.chunk_index, damaged.ciphertext_offset
+				);
+			}
+			if report.truncated {
+				// A stream that was cut within the last chunk ends with that (damaged) chunk
+				let missing_from = match report.damaged.last() {
+					Some(damaged) if damaged.chunk_index + 1 == decryptor.position().chunk_index => damaged.plaintext_offset + damaged.plaintext_length,
+					_ => decryptor.position().chunk_index * stream::CHUNK_DATA_SIZE as u64
+				};
+				eprintln!("Missing plaintext: everything after byte {} (the stream is truncated)", missing_from)
+			}
+			if !report.is_complete() { throw_err!(CpError::InvalidData, "The stream is damaged; the readable chunks have been salvaged") }
+			
+			// Apply the stored modification-time and permissions to the output-file
+			if try_err!(cmd::is_set(&switches, "--restore-metadata")) {
+				match decryptor.metadata() {
+					Some(metadata) => try_err!(cmd::metadata::restore(metadata, special_bits)),
+					None => eprintln!("The stream contains no metadata to restore")
+				}
+			}
+			Ok(())
+		},
+		"seal-dir" => {
+			let dir = try_err!(switches["--dir="].get::<String>(), CpError::CliError, "Failed to parse \"--dir=\"").clone();
+			if dir.is_empty() { throw_err!(CpError::CliError, "\"seal-dir\" requires \"--dir=\"") }
+			let builder = try_err!(seal_builder(&mut switches));
+			
+			// Seal every file below the directory and write the archive to stdout
+			let mut archive_writer = try_err!(builder.build_archive(std::io::BufWriter::new(std::io::stdout())));
+			try_err!(archive_writer.add_dir(&dir));
+			try_err!(archive_writer.finish());
+			Ok(())
+		},
+		"append" => {
+			let path = try_err!(switches["--file="].get::<String>(), CpError::CliError, "Failed to parse \"--file=\"").clone();
+			if path.is_empty() { throw_err!(CpError::CliError, "\"append\" requires \"--file=\"") }
+			let password = try_err!(password::get_password(&mut switches, false));
+			
+			// Append stdin to the sealed file
+			let mut file = try_err_from!(std::fs::OpenOptions::new().read(true).write(true).open(&path), format!("Failed to open \"{}\"", path));
+			try_err!(append::append(password, &mut file, std::io::stdin()));
+			try_err_from!(file.sync_all(), format!("Failed to sync \"{}\"", path));
+			Ok(())
+		},
+		"open-dir" => {
+			// Read the extraction-params
+			let out = try_err!(switches["--out="].get::<String>(), CpError::CliError, "Failed to parse \"--out=\"").clone();
+			let extract = try_err!(switches["--extract="].get::<String>(), CpError::CliError, "Failed to parse \"--extract=\"").clone();
+			let list = try_err!(cmd::is_set(&switches, "--list"));
+			let special_bits = try_err!(cmd::is_set(&switches, "--restore-special-bits"));
+			let max_ratio = *try_err!(switches["--max-ratio="].get::<u64>(), CpError::CliError, "Failed to parse \"--max-ratio=\"");
+			let builder = OpenBuilder::new(try_err!(password::get_password(&mut switches, false))).max_compression_ratio(max_ratio);
+			
+			// Open the index (the archive is read with random access, so stdin must be a regular file)
+			cmd::with_std_file(cmd::STDIN_FD, |stdin| {
+				let fs_metadata = try_err_from!(stdin.metadata(), "Failed to read the metadata of stdin".to_owned());
+				if !fs_metadata.is_file() { throw_err!(CpError::CliError, "\"open-dir\" requires stdin to be redirected from a regular file") }
+				let mut archive_reader = try_err!(builder.open_archive(stdin));
+				archive_reader.set_restore_special_bits(special_bits);
+				
+				// List or extract the entries
+				if list {
+					for entry in archive_reader.entries().iter() {
+						match entry.metadata.size {
+							Some(size) => println!("{}\t{} bytes", entry.path, size),
+							None => println!("{}", entry.path)
+						}
+					}
+					return Ok(())
+				}
+				for path in try_err!(archive_reader.extract_to(&out, &extract)) { eprintln!("Extracted \"{}\"", path) }
+				Ok(())
+			})
+		},
+		"info" => {
+			// Read the stream-header and describe the stream
+			let mut stdio = io::StreamIo::new(armor::DearmoringReader::new(std::io::stdin()), std::io::stdout());
+			let stream_instance = try_err!(stream::read_header(&mut stdio));
+			let mut info = format!(
+				"PBKDF: {}\nKDF: {}\nAuthenticated encryption: {}\nKey-check-value: {}\n",
+				stream_instance.pbkdf.algorithm(), stream_instance.kdf.algorithm(), stream_instance.auth_enc.algorithm(),
+				if stream_instance.key_check.is_some() { "yes (authenticates the header)" } else { "no (v1-stream)" }
+			);
+			if try_err!(stdio.reader_mut().is_armored()) { info += "Armored: yes\n" }
+			if let Some(parity) = stream_instance.parity {
+				info += &format!("Parity: {} parity-blocks per {} chunks\n", parity.parity_chunks, parity.data_chunks)
+			}
+			if let Some(compression) = stream_instance.compression {
+				info += &format!("Compression: {} (level {})\n", compression.algorithm.name(), compression.level)
+			}
+			match stream_instance.padding {
+				Some(padding::Padding::Padme) => info += "Padding: padme\n",
+				Some(padding::Padding::Bucket(size)) => info += &format!("Padding: buckets of {} bytes\n", size),
+				None => ()
+			}
+			if stream_instance.appendable { info += "Appendable: yes\n" }
+			if stream_instance.seek_index { info += "Seek-index: yes\n" }
+			if stream_instance.archive { info += "Archive: yes (use \"open-dir --list\" to list the entries)\n" }
+			
+			// Open and display the metadata if a password is given
+			match stream_instance.metadata_length {
+				Some(_) if try_err!(password::is_given(&switches)) => {
+					let base_key = try_err!(stream::derive_opening_key(&stream_instance, try_err!(password::get_password(&mut switches, false))));
+					let metadata = try_err!(stream::read_metadata(&mut stdio, &stream_instance, &base_key)).unwrap_or_default();
+					info += &format!("Metadata:\n{}", cmd::metadata::to_text(&metadata));
+				},
+				Some(_) => info += "Metadata: encrypted (pass the password to display it)\n",
+				None => info += "Metadata: none\n"
+			}
+			print!("{}", info);
+			Ok(())
 		},
 		_ => unreachable!()
 	}
@@ -129,6 +530,6 @@ fn main() {
 	// "Catch" all errors to print them readable
 	match run() {
 		Ok(result) => result,
-		Err(error) => die_error_help(&error.to_string())
+		Err(error) => die_error(&error, None)
 	}
 }
\ No newline at end of file
@@ -0,0 +1,137 @@
+//! Encrypted metadata about the sealed data (filename, size, modification-time, permissions,
+//! content-type and user-defined tags)
+//!
+//! The metadata is serialized as sequence of `(name, value)`-entries and sealed as a dedicated chunk
+//! directly after the stream-header (see `stream::METADATA_INFO`). Unknown entries are ignored, so
+//! new fields can be added without a new stream-version.
+
+use std;
+use super::{ Error, CpError };
+use super::asn1_der;
+use super::asn1_der::{ FromDerEncoded, FromDerObject, IntoDerEncoded, IntoDerObject };
+
+
+/// The maximum length of the serialized metadata
+pub const MAX_METADATA_SIZE: usize = 64 * 1024;
+/// The permission-bits that are restored by default (the setuid-, setgid- and sticky-bits are only
+/// restored on request; see `Metadata::apply`)
+pub const DEFAULT_PERMISSION_MASK: u32 = 0o777;
+
+
+
+/// The metadata of the sealed data (every field is optional)
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct Metadata {
+	/// The original filename (without directories)
+	pub filename: Option<String>,
+	/// The size of the plaintext in bytes
+	pub size: Option<u64>,
+	/// The modification-time in seconds since the Unix-epoch
+	pub modified: Option<u64>,
+	/// The Unix-permissions (the lower 12 bits of the mode)
+	pub permissions: Option<u32>,
+	/// The MIME-content-type
+	pub content_type: Option<String>,
+	/// User-defined `(key, value)`-tags
+	pub tags: Vec<(String, String)>
+}
+impl Metadata {
+	/// Reads the size, the modification-time and the permissions from `fs_metadata`
+	pub fn from_fs_metadata(fs_metadata: &std::fs::Metadata) -> Self {
+		let modified = fs_metadata.modified().ok()
+			.and_then(|modified| modified.duration_since(std::time::UNIX_EPOCH).ok())
+			.map(|duration| duration.as_secs());
+		Metadata{ size: Some(fs_metadata.len()), modified, permissions: permissions(fs_metadata), ..Metadata::default() }
+	}
+	/// Reads the filename, the size, the modification-time and the permissions of the file at `path`
+	pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self, Error<CpError>> {
+		let fs_metadata = try_err_from!(std::fs::metadata(path.as_ref()), format!("Failed to read the metadata of \"{}\"", path.as_ref().display()));
+		let filename = path.as_ref().file_name().map(|filename| filename.to_string_lossy().into_owned());
+		Ok(Metadata{ filename, ..Self::from_fs_metadata(&fs_metadata) })
+	}
+	
+	/// Applies the modification-time and the permissions (if any) to `file` (the setuid-, setgid- and
+	/// sticky-bits are only restored if `special_bits` is set)
+	pub fn apply(&self, file: &std::fs::File, special_bits: bool) -> Result<(), Error<CpError>> {
+		let mask = if special_bits { 0o7777 } else { DEFAULT_PERMISSION_MASK };
+		if let Some(permissions) = self.permissions { try_err!(set_permissions(file, permissions & mask)) }
+		if let Some(modified) = self.modified {
+			let modified = std::time::UNIX_EPOCH + std::time::Duration::from_secs(modified);
+			try_err_from!(file.set_modified(modified), "Failed to set the modification-time".to_owned());
+		}
+		Ok(())
+	}
+	
+	/// Parses the serialized metadata
+	pub fn from_serialized(serialized: &[u8]) -> Result<Self, Error<CpError>> {
+		let der_object: asn1_der::DerObject = try_err!(asn1_der::DerObject::from_der_encoded(serialized.to_vec()), CpError::InvalidData);
+		let entries: Vec<asn1_der::DerObject> = try_err!(Vec::<asn1_der::DerObject>::from_der_object(der_object), CpError::InvalidData);
+		
+		let mut metadata = Metadata::default();
+		for entry in entries {
+			// Parse the entry
+			let entry: Vec<asn1_der::DerObject> = try_err!(Vec::<asn1_der::DerObject>::from_der_object(entry), CpError::InvalidData);
+			if entry.len() < 2 { throw_err!(CpError::InvalidData) }
+			let (name, value) = (try_err!(String::from_der_object(entry[0].clone()), CpError::InvalidData), entry[1].clone());
+			
+			// Load the known entries
+			match name.as_str() {
+				"filename" => metadata.filename = Some(try_err!(String::from_der_object(value), CpError::InvalidData)),
+				"size" => metadata.size = Some(try_err!(u64::from_der_object(value), CpError::InvalidData)),
+				"modified" => metadata.modified = Some(try_err!(u64::from_der_object(value), CpError::InvalidData)),
+				"permissions" => metadata.permissions = Some(try_err!(u64::from_der_object(value), CpError::InvalidData) as u32 & 0o7777),
+				"content-type" => metadata.content_type = Some(try_err!(String::from_der_object(value), CpError::InvalidData)),
+				"tag" => {
+					let tag: Vec<String> = try_err!(Vec::<String>::from_der_object(value), CpError::InvalidData);
+					if tag.len() != 2 { throw_err!(CpError::InvalidData) }
+					metadata.tags.push((tag[0].clone(), tag[1].clone()))
+				},
+				_ => ()
+			}
+		}
+		Ok(metadata)
+	}
+	
+	/// Serializes the metadata
+	pub fn serialize(&self) -> Vec<u8> {
+		fn entry(name: &str, value: asn1_der::DerObject) -> asn1_der::DerObject {
+			vec![name.to_string().into_der_object(), value].into_der_object()
+		}
+		
+		let mut entries: Vec<asn1_der::DerObject> = Vec::new();
+		if let Some(ref filename) = self.filename { entries.push(entry("filename", filename.clone().into_der_object())) }
+		if let Some(size) = self.size { entries.push(entry("size", size.into_der_object())) }
+		if let Some(modified) = self.modified { entries.push(entry("modified", modified.into_der_object())) }
+		if let Some(permissions) = self.permissions { entries.push(entry("permissions", (permissions as u64).into_der_object())) }
+		if let Some(ref content_type) = self.content_type { entries.push(entry("content-type", content_type.clone().into_der_object())) }
+		for (key, value) in self.tags.iter() {
+			entries.push(entry("tag", vec![key.clone(), value.clone()].into_der_object()))
+		}
+		entries.into_der_object().into_der_encoded()
+	}
+}
+
+
+
+/// Returns the Unix-permissions of `fs_metadata`
+#[cfg(unix)]
+fn permissions(fs_metadata: &std::fs::Metadata) -> Option<u32> {
+	use std::os::unix::fs::PermissionsExt;
+	Some(fs_metadata.permissions().mode() & 0o7777)
+}
+#[cfg(not(unix))]
+fn permissions(_fs_metadata: &std::fs::Metadata) -> Option<u32> {
+	None
+}
+
+/// Sets the Unix-permissions of `file`
+#[cfg(unix)]
+fn set_permissions(file: &std::fs::File, permissions: u32) -> Result<(), Error<CpError>> {
+	use std::os::unix::fs::PermissionsExt;
+	try_err_from!(file.set_permissions(std::fs::Permissions::from_mode(permissions & 0o7777)), "Failed to set the permissions".to_owned());
+	Ok(())
+}
+#[cfg(not(unix))]
+fn set_permissions(_file: &std::fs::File, _permissions: u32) -> Result<(), Error<CpError>> {
+	throw_err!(CpError::Unsupported, "Restoring the permissions is only supported on unix-platforms")
+}
\ No newline at end of file
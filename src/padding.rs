@@ -0,0 +1,221 @@
+//! Length-hiding padding of the sealed plaintext
+//!
+//! The plaintext (after the compression, if any) is followed by `padding_length` zero-bytes and the
+//! trailer `padding_length (u64 BE)`, so that the total length falls into a size-class. Because the
+//! padding and the trailer are part of the sealed plaintext, they are authenticated like any other
+//! data and can be stripped exactly.
+
+use std;
+use super::{ Error, CpError };
+use super::asn1_der;
+use super::asn1_der::{ FromDerObject, IntoDerObject };
+
+
+/// The length of the padding-trailer
+pub const TRAILER_SIZE: usize = 8;
+/// The maximum bucket-size (1 TiB)
+pub const MAX_BUCKET_SIZE: u64 = 1 << 40;
+/// A block of zeros to write the padding (and zero-runs of the plaintext) from
+const ZEROS: [u8; 4096] = [0u8; 4096];
+
+
+
+/// A padding-scheme
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Padding {
+	/// Padmé (pads to a length with at most `floor(log2(floor(log2(length)))) + 1` significant bits;
+	/// the overhead is at most 12%)
+	Padme,
+	/// Pads to a multiple of the bucket-size
+	Bucket(u64)
+}
+impl Padding {
+	/// Parses a padding-specification (`padme` or `bucket:<size>` where `<size>` is a byte-count with an
+	/// optional `K`, `M` or `G`-suffix for KiB, MiB or GiB)
+	pub fn from_spec(spec: &str) -> Result<Self, Error<CpError>> {
+		if spec == "padme" { return Ok(Padding::Padme) }
+		if !spec.starts_with("bucket:") { throw_err!(CpError::Unsupported, format!("Unsupported padding \"{}\"", spec)) }
+		
+		// Parse the bucket-size
+		let size = &spec["bucket:".len() ..];
+		let (digits, factor) = match size.chars().last() {
+			Some('K') | Some('k') => (&size[.. size.len() - 1], 1024),
+			Some('M') | Some('m') => (&size[.. size.len() - 1], 1024 * 1024),
+			Some('G') | Some('g') => (&size[.. size.len() - 1], 1024 * 1024 * 1024),
+			_ => (size, 1)
+		};
+		let size = try_err!(digits.parse::<u64>(), CpError::InvalidParameter, format!("Invalid bucket-size \"{}\"", size));
+		Self::bucket(try_err!(size.checked_mul(factor).ok_or(()), CpError::InvalidParameter, "The bucket-size is too large"))
+	}
+	/// Creates and validates a bucket-padding
+	pub fn bucket(size: u64) -> Result<Self, Error<CpError>> {
+		if size == 0 { throw_err!(CpError::InvalidParameter, "The bucket-size must not be zero") }
+		if size > MAX_BUCKET_SIZE { throw_err!(CpError::InvalidParameter, format!("The bucket-size must not exceed {} bytes", MAX_BUCKET_SIZE)) }
+		Ok(Padding::Bucket(size))
+	}
+	
+	/// Parses a serialized padding-scheme
+	pub fn from_serialized(serialized: asn1_der::DerObject) -> Result<Self, Error<CpError>> {
+		let sequence: Vec<asn1_der::DerObject> = try_err!(Vec::<asn1_der::DerObject>::from_der_object(serialized), CpError::InvalidData);
+		if sequence.len() < 2 { throw_err!(CpError::InvalidData) }
+		
+		let name: String = try_err!(String::from_der_object(sequence[0].clone()), CpError::InvalidData);
+		let size = try_err!(u64::from_der_object(sequence[1].clone()), CpError::InvalidData);
+		match name.as_str() {
+			"padme" => Ok(Padding::Padme),
+			"bucket" => Ok(try_err!(Self::bucket(size), CpError::InvalidData)),
+			name => throw_err!(CpError::Unsupported, format!("Unsupported padding \"{}\"", name))
+		}
+	}
+	
+	/// Serializes the padding-scheme
+	pub fn serialize(&self) -> asn1_der::DerObject {
+		let (name, size) = match *self {
+			Padding::Padme => ("padme", 0),
+			Padding::Bucket(size) => ("bucket", size)
+		};
+		let sequence: Vec<asn1_der::DerObject> = vec![name.to_string().into_der_object(), size.into_der_object()];
+		sequence.into_der_object()
+	}
+	
+	/// Computes the padded length for `length` plaintext-bytes (including the trailer)
+	pub fn padded_length(&self, length: u64) -> Result<u64, Error<CpError>> {
+		let padded_length = length.checked_add(TRAILER_SIZE as u64).and_then(|length| match *self {
+			Padding::Padme => padme(length),
+			Padding::Bucket(size) => size.checked_sub(1).and_then(|rounding| length.checked_add(rounding)).map(|length| length / size * size)
+		});
+		Ok(try_err!(padded_length.ok_or(()), CpError::InvalidParameter, "The padded length is too large"))
+	}
+}
+
+/// Rounds `length` up to a value whose mantissa has at most `floor(log2(exponent)) + 1` bits (where
+/// `exponent = floor(log2(length))`) or returns `None` if the result does not fit into an `u64`
+pub fn padme(length: u64) -> Option<u64> {
+	if length < 2 { return Some(length) }
+	let exponent = 63 - length.leading_zeros() as u64;
+	let mantissa_bits = 64 - exponent.leading_zeros() as u64;
+	let mask = (1u64 << (exponent - mantissa_bits)) - 1;
+	length.checked_add(mask).map(|length| length & !mask)
+}
+
+/// Writes `count` zero-bytes to `sink`
+fn write_zeros(mut count: u64, sink: &mut FnMut(&[u8]) -> Result<(), Error<CpError>>) -> Result<(), Error<CpError>> {
+	while count > 0 {
+		let to_write = std::cmp::min(count, ZEROS.len() as u64) as usize;
+		try_err!(sink(&ZEROS[.. to_write]));
+		count -= to_write as u64;
+	}
+	Ok(())
+}
+
+
+
+/// Appends the padding and the trailer to the plaintext
+pub struct Padder {
+	padding: Padding,
+	plaintext_bytes: u64,
+	/// The amount of padding- and trailer-bytes that have not been returned yet (once the plaintext
+	/// is complete)
+	remaining: Option<u64>,
+	trailer: [u8; TRAILER_SIZE]
+}
+impl Padder {
+	pub fn new(padding: Padding) -> Self {
+		Padder{ padding, plaintext_bytes: 0, remaining: None, trailer: [0u8; TRAILER_SIZE] }
+	}
+	
+	/// Fills `chunk_buffer` with the next plaintext from `source` (which behaves like
+	/// `io::Io::read_chunk`) followed by the padding and returns `(chunk_length, is_last)`
+	pub fn read_chunk(&mut self, chunk_buffer: &mut[u8], source: &mut FnMut(&mut[u8]) -> Result<(usize, bool), Error<CpError>>) -> Result<(usize, bool), Error<CpError>> {
+		// Read the plaintext until it is complete
+		let mut chunk_length = 0;
+		if self.remaining.is_none() {
+			let (length, is_last) = try_err!(source(chunk_buffer));
+			self.plaintext_bytes += length as u64;
+			if !is_last { return Ok((length, false)) }
+			
+			// Compute the padding
+			let remaining = try_err!(self.padding.padded_length(self.plaintext_bytes)) - self.plaintext_bytes;
+			self.trailer = (remaining - TRAILER_SIZE as u64).to_be_bytes();
+			self.remaining = Some(remaining);
+			chunk_length = length;
+		}
+		
+		// Append the padding and the trailer
+		let mut remaining = self.remaining.unwrap_or(0);
+		while chunk_length < chunk_buffer.len() && remaining > 0 {
+			if remaining > TRAILER_SIZE as u64 {
+				let to_fill = std::cmp::min((chunk_buffer.len() - chunk_length) as u64, remaining - TRAILER_SIZE as u64) as usize;
+				for byte in chunk_buffer[chunk_length .. chunk_length + to_fill].iter_mut() { *byte = 0x00 }
+				chunk_length += to_fill;
+				remaining -= to_fill as u64;
+			} else {
+				chunk_buffer[chunk_length] = self.trailer[TRAILER_SIZE - remaining as usize];
+				chunk_length += 1;
+				remaining -= 1;
+			}
+		}
+		self.remaining = Some(remaining);
+		Ok((chunk_length, remaining == 0))
+	}
+}
+
+
+
+/// Strips the padding and the trailer from the opened plaintext
+///
+/// The last `TRAILER_SIZE` bytes are held back and trailing zeros are only counted (and written as
+/// soon as non-zero data follows), so the padding never has to be buffered.
+#[derive(Default)]
+pub struct Unpadder {
+	held: Vec<u8>,
+	zeros: u64
+}
+impl Unpadder {
+	pub fn new() -> Self {
+		Unpadder{ held: Vec::new(), zeros: 0 }
+	}
+	
+	/// Processes `data` and writes everything that is definitely plaintext to `sink`
+	pub fn write_chunk(&mut self, data: &[u8], sink: &mut FnMut(&[u8]) -> Result<(), Error<CpError>>) -> Result<(), Error<CpError>> {
+		self.held.extend_from_slice(data);
+		if self.held.len() <= TRAILER_SIZE { return Ok(()) }
+		
+		// Write the pending zeros and the data up to the last non-zero byte
+		let releasable = self.held.len() - TRAILER_SIZE;
+		match self.held[.. releasable].iter().rposition(|byte| *byte != 0x00) {
+			Some(last) => {
+				try_err!(write_zeros(self.zeros, sink));
+				try_err!(sink(&self.held[..= last]));
+				self.zeros = (releasable - last - 1) as u64;
+			},
+			None => self.zeros += releasable as u64
+		}
+		self.held.drain(.. releasable);
+		Ok(())
+	}
+	
+	/// Validates the trailer and writes the remaining plaintext-zeros to `sink`
+	pub fn finish(&mut self, sink: &mut FnMut(&[u8]) -> Result<(), Error<CpError>>) -> Result<(), Error<CpError>> {
+		if self.held.len() != TRAILER_SIZE { throw_err!(CpError::InvalidData, "The padding-trailer is missing") }
+		let mut trailer = [0u8; TRAILER_SIZE];
+		trailer.copy_from_slice(&self.held);
+		
+		let padding_length = u64::from_be_bytes(trailer);
+		if padding_length > self.zeros { throw_err!(CpError::InvalidData, "Invalid padding-length") }
+		try_err!(write_zeros(self.zeros - padding_length, sink));
+		self.held.clear();
+		self.zeros = 0;
+		Ok(())
+	}
+	
+	/// Writes everything that has been held back (including the padding) to `sink`; this is used if
+	/// the stream is incomplete and the trailer cannot be located
+	pub fn flush(&mut self, sink: &mut FnMut(&[u8]) -> Result<(), Error<CpError>>) -> Result<(), Error<CpError>> {
+		try_err!(write_zeros(self.zeros, sink));
+		try_err!(sink(&self.held));
+		self.held.clear();
+		self.zeros = 0;
+		Ok(())
+	}
+}
\ No newline at end of file
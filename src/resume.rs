@@ -0,0 +1,167 @@
+//! Resuming an interrupted sealing-operation
+//!
+//! Every chunk-key depends only on the base-key and the chunk-index, so a sealing-operation that was
+//! interrupted can be continued at the first chunk that was not written completely: the base-key is
+//! derived again from the existing stream-header, every written chunk is opened and compared with
+//! the input and the sealing continues after the last valid chunk. Because the chunks are compared
+//! with the input, a modified input is detected instead of producing a stream with mixed contents.
+//!
+//! The chunks are sealed with a fixed nonce, so a torn chunk is only replaced if it is a prefix of
+//! the re-sealed input-chunk; otherwise the same key-stream would have encrypted different data.
+
+use std;
+use std::io::{ Read, Write, Seek, SeekFrom };
+use super::{ Error, CpError };
+use super::{ crypto, io, stream, metadata };
+use super::io::Io;
+use super::asn1_der::IntoDerEncoded;
+
+
+/// Rejects streams that cannot be resumed (parity-blocks, compression and padding span multiple
+/// chunks, appendable streams use a new session per sealing-operation and the seek-index is only
+/// known at the end)
+fn require_resumable(stream_instance: &crypto::StreamInstance) -> Result<(), Error<CpError>> {
+	if stream_instance.parity.is_some() || stream_instance.compression.is_some() || stream_instance.padding.is_some() {
+		throw_err!(CpError::Unsupported, "Streams with parity-blocks, compression or padding cannot be resumed")
+	}
+	if stream_instance.appendable || stream_instance.seek_index || stream_instance.archive {
+		throw_err!(CpError::Unsupported, "Appendable streams, streams with a seek-index and archives cannot be resumed")
+	}
+	Ok(())
+}
+
+/// Rejects the input if its size or modification-time differs from the sealed `metadata` (if the
+/// stream was sealed with them)
+fn verify_input_metadata(metadata: &metadata::Metadata, input: &std::fs::File) -> Result<(), Error<CpError>> {
+	let current = metadata::Metadata::from_fs_metadata(&try_err_from!(input.metadata(), "Failed to read the metadata of the input".to_owned()));
+	let size_changed = metadata.size.is_some() && metadata.size != current.size;
+	let modified_changed = metadata.modified.is_some() && metadata.modified != current.modified;
+	if size_changed || modified_changed { throw_err!(CpError::InvalidParameter, "The input has changed since the sealing-operation started") }
+	Ok(())
+}
+
+/// Reads the next full chunk from `input` into `buffer` and returns the amount of bytes read
+fn read_input_chunk(input: &mut Read, buffer: &mut[u8]) -> Result<usize, Error<CpError>> {
+	let mut length = 0;
+	while length < buffer.len() {
+		match input.read(&mut buffer[length ..]) {
+			Ok(0) => break,
+			Ok(read) => length += read,
+			Err(ref error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+			Err(error) => throw_err!(error.into(), "Failed to read from the input")
+		}
+	}
+	Ok(length)
+}
+
+
+/// Continues the interrupted sealing-operation of `input` into `output`
+///
+/// The configuration is read from the stream-header in `output`; a torn tail is truncated and sealed
+/// again. Returns the amount of chunks that were verified (if the stream is already complete, nothing
+/// is written). The input is rejected with `CpError::InvalidParameter` if a written chunk does not
+/// match it or if the torn tail is not a prefix of the re-sealed input-chunk (in both cases the
+/// output is not modified).
+///
+/// `progress` (if any) is invoked with the position in the sealed stream after every verified and
+/// every newly sealed chunk (see `stream::Encryptor::set_progress`).
+pub fn resume<P: Into<crypto::Password>>(password: P, input: &mut std::fs::File, output: &mut std::fs::File, mut progress: Option<Box<FnMut(&stream::Position)>>) -> Result<u64, Error<CpError>> {
+	// Read the stream-header and the metadata and derive the key
+	try_err_from!(output.seek(SeekFrom::Start(0)), "Failed to seek to the stream-header".to_owned());
+	let (stream_instance, base_key) = {
+		let mut io = io::StreamIo::new(&mut *output, std::io::sink());
+		let stream_instance = try_err!(stream::read_header(&mut io));
+		try_err!(require_resumable(&stream_instance));
+		let base_key = try_err!(stream::derive_opening_key(&stream_instance, password.into()));
+		if let Some(metadata) = try_err!(stream::read_metadata(&mut io, &stream_instance, &base_key)) {
+			try_err!(verify_input_metadata(&metadata, input))
+		}
+		(stream_instance, base_key)
+	};
+	
+	// Verify the written full chunks against the input (a chunk that cannot be opened is torn)
+	let body_offset = stream_instance.as_serialized().into_der_encoded().len() as u64 + stream_instance.metadata_length.unwrap_or(0);
+	let output_length = try_err_from!(output.seek(SeekFrom::End(0)), "Failed to seek to the end of the output".to_owned());
+	let sealed_chunk_length = (stream::CHUNK_DATA_SIZE + stream_instance.auth_enc.overhead()) as u64;
+	try_err_from!(output.seek(SeekFrom::Start(body_offset)), "Failed to seek to the first chunk".to_owned());
+	try_err_from!(input.seek(SeekFrom::Start(0)), "Failed to seek to the start of the input".to_owned());
+	
+	let (mut chunk_buffer, mut input_buffer) = (vec![0u8; sealed_chunk_length as usize], vec![0u8; stream::CHUNK_DATA_SIZE]);
+	let (mut counter, mut offset) = (0u64, body_offset);
+	while offset < output_length {
+		// Read the chunk (the last chunk may be shorter)
+		let chunk_length = std::cmp::min(output_length - offset, sealed_chunk_length) as usize;
+		try_err_from!(output.read_exact(&mut chunk_buffer[.. chunk_length]), format!("Failed to read chunk #{}", counter));
+		let input_length = try_err!(read_input_chunk(input, &mut input_buffer));
+		
+		// Open the chunk as ordinary chunk or as last chunk
+		let backup = chunk_buffer[.. chunk_length].to_vec();
+		let (data_length, is_last) = match stream::open_chunk(&stream_instance, &base_key, counter, false, &mut chunk_buffer, chunk_length) {
+			Ok(data_length) if chunk_length as u64 == sealed_chunk_length => (data_length, false),
+			_ => {
+				chunk_buffer[.. chunk_length].copy_from_slice(&backup);
+				match stream::open_chunk(&stream_instance, &base_key, counter, true, &mut chunk_buffer, chunk_length) {
+					Ok(data_length) => (data_length, true),
+					Err(_) => break
+				}
+			}
+		};
+		
+		// Compare the chunk with the input
+		if chunk_buffer[.. data_length] != input_buffer[.. input_length] { throw_err!(CpError::InvalidParameter, format!("The input does not match chunk #{} (the input has changed)", counter)) }
+		if let Some(ref mut progress) = progress {
+			progress(&stream::Position{
+				chunk_index: counter + 1,
+				ciphertext_offset: offset + chunk_length as u64,
+				plaintext_bytes: counter * stream::CHUNK_DATA_SIZE as u64 + input_length as u64
+			})
+		}
+		if is_last {
+			// The stream is already complete if the input ends with the last chunk
+			if try_err!(read_input_chunk(input, &mut input_buffer[.. 1])) != 0 { throw_err!(CpError::InvalidParameter, "The input is longer than the sealed stream (the input has changed)") }
+			try_err_from!(output.set_len(offset + chunk_length as u64), "Failed to truncate the output".to_owned());
+			return Ok(counter + 1)
+		}
+		counter += 1;
+		offset += chunk_length as u64;
+	}
+	
+	// Re-seal the input-chunk and ensure that the torn tail is a prefix of it
+	let tail_length = output_length - offset;
+	if tail_length > 0 {
+		try_err_from!(input.seek(SeekFrom::Start(counter * stream::CHUNK_DATA_SIZE as u64)), "Failed to seek in the input".to_owned());
+		let (input_length, is_last) = try_err!(io::StreamIo::new(&mut *input, std::io::sink()).read_chunk(&mut chunk_buffer[.. stream::CHUNK_DATA_SIZE]));
+		let sealed_length = try_err!(stream::seal_chunk(&stream_instance, &base_key, counter, is_last, &mut chunk_buffer, input_length));
+		
+		let is_prefix = tail_length <= sealed_length as u64 && {
+			let mut tail = vec![0u8; tail_length as usize];
+			try_err_from!(output.seek(SeekFrom::Start(offset)), "Failed to seek to the torn chunk".to_owned());
+			try_err_from!(output.read_exact(&mut tail), format!("Failed to read chunk #{}", counter));
+			tail == chunk_buffer[.. tail_length as usize]
+		};
+		if !is_prefix { throw_err!(CpError::InvalidParameter, format!("The torn chunk #{} does not match the input (the input has changed or the output is damaged)", counter)) }
+	}
+	
+	// Truncate the torn tail and continue with the next chunk
+	try_err_from!(output.set_len(offset), "Failed to truncate the output".to_owned());
+	try_err_from!(output.seek(SeekFrom::Start(offset)), "Failed to seek to the end of the output".to_owned());
+	try_err_from!(input.seek(SeekFrom::Start(counter * stream::CHUNK_DATA_SIZE as u64)), "Failed to seek in the input".to_owned());
+	{
+		let mut io = io::StreamIo::new(&mut *input, &mut *output);
+		let mut encryptor = stream::Encryptor::with_key(base_key, &mut io, stream_instance);
+		encryptor.set_first_counter(counter);
+		
+		// The encryptor starts at the ciphertext-offset and the plaintext-offset 0
+		if let Some(mut progress) = progress {
+			let plaintext_offset = counter * stream::CHUNK_DATA_SIZE as u64;
+			encryptor.set_progress(Box::new(move |position: &stream::Position| progress(&stream::Position{
+				chunk_index: position.chunk_index,
+				ciphertext_offset: offset + position.ciphertext_offset,
+				plaintext_bytes: plaintext_offset + position.plaintext_bytes
+			})))
+		}
+		try_err!(encryptor.seal_body());
+	}
+	try_err_from!(output.flush(), "Failed to flush the output".to_owned());
+	Ok(counter)
+}
\ No newline at end of file
@@ -0,0 +1,300 @@
+//! Seekable streams with an authenticated index-trailer
+//!
+//! If the header contains the `seek-index`-extension, `stream::Encryptor` writes the trailer
+//! `sealed_index || index_length (u64 BE)` after the last chunk (and its parity-blocks). The index
+//! contains the amount of chunks, the plaintext-length (without padding) and the offset of every
+//! chunk in the stream and is sealed with the key derived from `0 || "#Seek Index"`, so
+//! `DecryptReader` can open any chunk directly. `stream::Decryptor` strips the trailer (see
+//! `TrailerStripper`) and verifies it against the opened chunks.
+
+use std;
+use std::io::{ Read, Seek, SeekFrom };
+use super::{ Error, CpError };
+use super::{ asn1_der, crypto, io, stream };
+use super::error::into_io_error;
+use super::asn1_der::{ FromDerEncoded, FromDerObject, IntoDerEncoded, IntoDerObject };
+
+
+/// The KDF-info-label of the index-key
+pub const SEEK_INDEX_INFO: &str = "#Seek Index";
+/// The length of the `index_length`-field at the end of the stream
+pub const INDEX_LENGTH_SIZE: usize = 8;
+/// The maximum length of the trailer without the chunk-offsets and the authentication-overhead
+const MAX_INDEX_OVERHEAD: usize = 64 + INDEX_LENGTH_SIZE;
+
+
+/// Derives the index-key
+fn derive_key(stream_instance: &crypto::StreamInstance, base_key: &crypto::Key) -> Result<crypto::Key, Error<CpError>> {
+	let mut kdf_buffer = [0u8; 32];
+	stream_instance.kdf.derive(base_key, try_err!(stream::kdf_info(&mut kdf_buffer, 0, SEEK_INDEX_INFO)))
+}
+
+
+
+/// The seek-index of a stream
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct SeekIndex {
+	/// The length of the plaintext (without padding)
+	pub plaintext_length: u64,
+	/// The offset of every chunk in the stream (including the stream-header)
+	pub chunk_offsets: Vec<u64>
+}
+impl SeekIndex {
+	/// Parses a serialized seek-index
+	fn from_serialized(serialized: &[u8]) -> Result<Self, Error<CpError>> {
+		let der_object: asn1_der::DerObject = try_err!(asn1_der::DerObject::from_der_encoded(serialized.to_vec()), CpError::InvalidData);
+		let sequence: Vec<asn1_der::DerObject> = try_err!(Vec::<asn1_der::DerObject>::from_der_object(der_object), CpError::InvalidData);
+		if sequence.len() < 3 { throw_err!(CpError::InvalidData) }
+		
+		// Parse the fields and the serialized chunk-offsets
+		let chunk_count = try_err!(u64::from_der_object(sequence[0].clone()), CpError::InvalidData);
+		let plaintext_length = try_err!(u64::from_der_object(sequence[1].clone()), CpError::InvalidData);
+		let offsets = try_err!(Vec::<u8>::from_der_object(sequence[2].clone()), CpError::InvalidData);
+		if offsets.len() % 8 != 0 || (offsets.len() / 8) as u64 != chunk_count { throw_err!(CpError::InvalidData, "Invalid chunk-count") }
+		
+		let chunk_offsets = offsets.chunks(8).map(|offset| {
+			let mut bytes = [0u8; 8];
+			bytes.copy_from_slice(offset);
+			u64::from_be_bytes(bytes)
+		}).collect();
+		Ok(SeekIndex{ plaintext_length, chunk_offsets })
+	}
+	
+	/// Serializes the seek-index
+	fn serialize(&self) -> Vec<u8> {
+		let mut offsets = Vec::with_capacity(self.chunk_offsets.len() * 8);
+		for offset in self.chunk_offsets.iter() { offsets.extend_from_slice(&offset.to_be_bytes()) }
+		let sequence: Vec<asn1_der::DerObject> = vec![
+			(self.chunk_offsets.len() as u64).into_der_object(),
+			self.plaintext_length.into_der_object(),
+			offsets.into_der_object()
+		];
+		sequence.into_der_object().into_der_encoded()
+	}
+	
+	/// Returns the amount of chunks
+	pub fn chunk_count(&self) -> u64 {
+		self.chunk_offsets.len() as u64
+	}
+	
+	/// Seals the seek-index and returns the trailer (`sealed_index || index_length`)
+	pub fn seal(&self, stream_instance: &crypto::StreamInstance, base_key: &crypto::Key) -> Result<Vec<u8>, Error<CpError>> {
+		let serialized = self.serialize();
+		let mut trailer = serialized.clone();
+		trailer.resize(serialized.len() + stream_instance.auth_enc.overhead(), 0x00);
+		let sealed_length = try_err!(stream_instance.auth_enc.seal(&mut trailer, serialized.len(), try_err!(derive_key(stream_instance, base_key))));
+		
+		trailer.truncate(sealed_length);
+		trailer.extend_from_slice(&(sealed_length as u64).to_be_bytes());
+		Ok(trailer)
+	}
+	
+	/// Opens the sealed index (the trailer without the `index_length`-field)
+	pub fn open(mut sealed: Vec<u8>, stream_instance: &crypto::StreamInstance, base_key: &crypto::Key) -> Result<Self, Error<CpError>> {
+		let sealed_length = sealed.len();
+		let length = try_err!(stream_instance.auth_enc.open(&mut sealed, sealed_length, try_err!(derive_key(stream_instance, base_key))));
+		Self::from_serialized(&sealed[.. length])
+	}
+}
+
+
+
+/// Separates the trailer from the chunks while a seekable stream is read sequentially
+///
+/// The trailer can only be located once the end of the stream is reached, so at least as many
+/// bytes as the largest possible trailer of the stream read so far are held back (the trailer
+/// grows by 8 bytes per chunk).
+pub struct TrailerStripper {
+	pending: Vec<u8>,
+	read: u64,
+	is_eof: bool,
+	overhead: usize,
+	trailer: Option<Vec<u8>>
+}
+impl TrailerStripper {
+	/// Creates a new stripper for a stream with the authentication-overhead `overhead`
+	pub fn new(overhead: usize) -> Self {
+		TrailerStripper{ pending: Vec::new(), read: 0, is_eof: false, overhead, trailer: None }
+	}
+	
+	/// The amount of bytes that must be held back (the trailer has at most one chunk-offset per
+	/// `stream::CHUNK_DATA_SIZE` bytes plus one)
+	fn holdback(&self) -> usize {
+		8 * (self.read as usize / stream::CHUNK_DATA_SIZE + 2) + self.overhead + MAX_INDEX_OVERHEAD
+	}
+	
+	/// Reads the next chunk from `io` into `chunk_buffer` like `io::Io::read_chunk` (but without the
+	/// trailer)
+	pub fn read_chunk(&mut self, io: &mut io::Io, chunk_buffer: &mut[u8]) -> Result<(usize, bool), Error<CpError>> {
+		// Read ahead until the held back data cannot belong to the requested chunk
+		while !self.is_eof && self.pending.len() < chunk_buffer.len() + self.holdback() {
+			let position = self.pending.len();
+			self.pending.resize(position + chunk_buffer.len(), 0x00);
+			let (length, is_last) = try_err!(io.read_chunk(&mut self.pending[position ..]));
+			self.pending.truncate(position + length);
+			self.read += length as u64;
+			self.is_eof = is_last;
+		}
+		
+		// Split the trailer off once the end of the stream is reached
+		if self.is_eof && self.trailer.is_none() {
+			if self.pending.len() < INDEX_LENGTH_SIZE { throw_err!(CpError::InvalidData, "The stream is truncated (the seek-index is missing)") }
+			let mut index_length = [0u8; INDEX_LENGTH_SIZE];
+			index_length.copy_from_slice(&self.pending[self.pending.len() - INDEX_LENGTH_SIZE ..]);
+			let index_length = u64::from_be_bytes(index_length);
+			if index_length > (self.pending.len() - INDEX_LENGTH_SIZE) as u64 { throw_err!(CpError::InvalidData, "Invalid seek-index-length") }
+			
+			let mut trailer = self.pending.split_off(self.pending.len() - INDEX_LENGTH_SIZE - index_length as usize);
+			trailer.truncate(index_length as usize);
+			self.trailer = Some(trailer);
+		}
+		
+		// Copy the next chunk
+		let length = std::cmp::min(chunk_buffer.len(), self.pending.len());
+		chunk_buffer[.. length].copy_from_slice(&self.pending[.. length]);
+		self.pending.drain(.. length);
+		Ok((length, self.is_eof && self.pending.is_empty()))
+	}
+	
+	/// Returns the sealed index (available once the last chunk has been read)
+	pub fn into_sealed_index(self) -> Option<Vec<u8>> {
+		self.trailer
+	}
+}
+
+
+
+/// A `std::io::Read`- and `std::io::Seek`-adapter that opens the chunks of a seekable stream on
+/// demand
+///
+/// Damaged chunks are not repaired from the parity-blocks (use `stream::Decryptor` for that).
+pub struct DecryptReader<R: Read + Seek> {
+	reader: R,
+	stream_instance: crypto::StreamInstance,
+	key: crypto::Key,
+	index: SeekIndex,
+	/// The offset where the chunks (and their parity-blocks) end
+	body_end: u64,
+	position: u64,
+	/// The index and the plaintext of the currently opened chunk
+	chunk: Option<(u64, Vec<u8>)>
+}
+impl<R: Read + Seek> DecryptReader<R> {
+	/// Reads the stream-header, derives the key from `password` and opens the seek-index
+	pub fn open<P: Into<crypto::Password>>(password: P, mut reader: R) -> Result<Self, Error<CpError>> {
+		// Read the header and derive the key
+		try_err_from!(reader.seek(SeekFrom::Start(0)));
+		let stream_instance = try_err!(stream::read_header(&mut io::StreamIo::new(&mut reader, std::io::sink())));
+		if !stream_instance.seek_index { throw_err!(CpError::Unsupported, "The stream has no seek-index") }
+		let key = try_err!(stream::derive_opening_key(&stream_instance, password.into()));
+		
+		// Read the index-length and the sealed index
+		let body_offset = stream_instance.as_serialized().into_der_encoded().len() as u64 + stream_instance.metadata_length.unwrap_or(0);
+		let length = try_err_from!(reader.seek(SeekFrom::End(0)));
+		if length < body_offset + INDEX_LENGTH_SIZE as u64 { throw_err!(CpError::InvalidData, "The stream is truncated") }
+		let mut index_length = [0u8; INDEX_LENGTH_SIZE];
+		try_err_from!(reader.seek(SeekFrom::Start(length - INDEX_LENGTH_SIZE as u64)));
+		try_err_from!(reader.read_exact(&mut index_length), "Failed to read the seek-index-length".to_owned());
+		let index_length = u64::from_be_bytes(index_length);
+		if index_length > length - body_offset - INDEX_LENGTH_SIZE as u64 { throw_err!(CpError::InvalidData, "Invalid seek-index-length") }
+		
+		let body_end = length - INDEX_LENGTH_SIZE as u64 - index_length;
+		let mut sealed = vec![0u8; index_length as usize];
+		try_err_from!(reader.seek(SeekFrom::Start(body_end)));
+		try_err_from!(reader.read_exact(&mut sealed), "Failed to read the seek-index".to_owned());
+		let index = try_err!(SeekIndex::open(sealed, &stream_instance, &key));
+		
+		// Validate the index (every chunk except the last one is a full chunk)
+		let body_end = match stream_instance.parity {
+			Some(parity) => body_end.saturating_sub((parity.parity_chunks * (stream::CHUNK_DATA_SIZE + stream_instance.auth_enc.overhead())) as u64),
+			None => body_end
+		};
+		let is_valid = index.chunk_offsets.first() == Some(&body_offset)
+			&& index.chunk_offsets.windows(2).all(|offsets| offsets[0] < offsets[1])
+			&& index.chunk_offsets.last().map(|offset| *offset < body_end) == Some(true)
+			&& index.plaintext_length <= index.chunk_count() * stream::CHUNK_DATA_SIZE as u64;
+		if !is_valid { throw_err!(CpError::InvalidData, "Invalid seek-index") }
+		Ok(DecryptReader{ reader, stream_instance, key, index, body_end, position: 0, chunk: None })
+	}
+	
+	/// Returns the length of the plaintext
+	pub fn len(&self) -> u64 {
+		self.index.plaintext_length
+	}
+	/// Returns whether the plaintext is empty
+	pub fn is_empty(&self) -> bool {
+		self.index.plaintext_length == 0
+	}
+	/// Returns the seek-index
+	pub fn index(&self) -> &SeekIndex {
+		&self.index
+	}
+	
+	/// Reads and opens the `chunk_index`th chunk
+	fn open_chunk(&mut self, chunk_index: u64) -> Result<Vec<u8>, Error<CpError>> {
+		let is_last = chunk_index + 1 == self.index.chunk_count();
+		let offset = self.index.chunk_offsets[chunk_index as usize];
+		let sealed_length = match is_last {
+			true => self.body_end - offset,
+			false => (stream::CHUNK_DATA_SIZE + self.stream_instance.auth_enc.overhead()) as u64
+		};
+		if sealed_length > (stream::CHUNK_DATA_SIZE + self.stream_instance.auth_enc.overhead()) as u64 { throw_err!(CpError::InvalidData, "Invalid seek-index") }
+		
+		let mut chunk_buffer = vec![0u8; sealed_length as usize];
+		try_err_from!(self.reader.seek(SeekFrom::Start(offset)));
+		try_err_from!(self.reader.read_exact(&mut chunk_buffer), format!("Failed to read chunk #{}", chunk_index));
+		let data_length = try_err!(stream::open_chunk(&self.stream_instance, &self.key, chunk_index, is_last, &mut chunk_buffer, sealed_length as usize));
+		chunk_buffer.truncate(data_length);
+		Ok(chunk_buffer)
+	}
+}
+impl<R: Read + Seek> Read for DecryptReader<R> {
+	fn read(&mut self, buffer: &mut[u8]) -> std::io::Result<usize> {
+		if self.position >= self.index.plaintext_length || buffer.is_empty() { return Ok(0) }
+		
+		// Open the chunk that contains the current position (if it is not already open)
+		let chunk_index = self.position / stream::CHUNK_DATA_SIZE as u64;
+		let is_open = match self.chunk {
+			Some((index, _)) => index == chunk_index,
+			None => false
+		};
+		if !is_open {
+			let chunk = self.open_chunk(chunk_index).map_err(into_io_error)?;
+			self.chunk = Some((chunk_index, chunk));
+		}
+		
+		// Copy the data (the padding after the plaintext-length is never returned)
+		let chunk = match self.chunk {
+			Some((_, ref chunk)) => chunk,
+			None => unreachable!()
+		};
+		let offset = (self.position % stream::CHUNK_DATA_SIZE as u64) as usize;
+		let available = std::cmp::min(chunk.len() as u64, self.index.plaintext_length - chunk_index * stream::CHUNK_DATA_SIZE as u64) as usize;
+		if offset >= available { return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "The chunk is shorter than the seek-index claims")) }
+		let to_copy = std::cmp::min(buffer.len(), available - offset);
+		buffer[.. to_copy].copy_from_slice(&chunk[offset .. offset + to_copy]);
+		self.position += to_copy as u64;
+		Ok(to_copy)
+	}
+}
+impl<R: Read + Seek> Seek for DecryptReader<R> {
+	fn seek(&mut self, position: SeekFrom) -> std::io::Result<u64> {
+		let position = match position {
+			SeekFrom::Start(offset) => Some(offset),
+			SeekFrom::End(offset) => checked_add_signed(self.index.plaintext_length, offset),
+			SeekFrom::Current(offset) => checked_add_signed(self.position, offset)
+		};
+		match position {
+			Some(position) => { self.position = position; Ok(position) },
+			None => Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "Invalid seek to a negative or overflowing position"))
+		}
+	}
+}
+
+/// Adds the signed `offset` to `base`
+fn checked_add_signed(base: u64, offset: i64) -> Option<u64> {
+	match offset < 0 {
+		true => base.checked_sub(offset.unsigned_abs()),
+		false => base.checked_add(offset as u64)
+	}
+}
\ No newline at end of file
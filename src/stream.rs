@@ -1,10 +1,25 @@
 use super::{ Error, CpError };
 use super::crypto;
 use super::io;
+use super::{ fec, compression, padding, metadata, seekable };
 use super::asn1_der::IntoDerEncoded;
 
 
 pub const CHUNK_DATA_SIZE: usize = (1 * 1024 * 1024);
+/// The KDF-info-label of the last chunk
+pub const LAST_CHUNK_INFO: &str = "#Last Chunk";
+/// The KDF-info-label of the key-check-value
+pub const KEY_CHECK_INFO: &str = "#Key Check";
+/// The KDF-info-label of the metadata-chunk
+pub const METADATA_INFO: &str = "#Metadata";
+/// The KDF-info-label of the chunks of an appendable stream
+pub const APPEND_CHUNK_INFO: &str = "#Append";
+/// The KDF-info-label of the last chunk of an appendable stream
+pub const APPEND_LAST_CHUNK_INFO: &str = "#Append Last Chunk";
+/// The length of the session-ID that follows every chunk of an appendable stream
+pub const SESSION_ID_SIZE: usize = 8;
+/// The maximum amount of parity-block-combinations that are tried to repair a group
+pub const MAX_REPAIR_ATTEMPTS: usize = 64;
 
 
 pub fn kdf_info<'a>(buffer: &'a mut[u8], mut counter: u64, info: &str) -> Result<&'a [u8], Error<CpError>> {
@@ -19,105 +34,835 @@ pub fn kdf_info<'a>(buffer: &'a mut[u8], mut counter: u64, info: &str) -> Result
 	Ok(&buffer[.. 8 + info.len()])
 }
 
+/// Derives the key-check-value for `base_key`
+///
+/// The key-check-value is stored in the stream-header so that a wrong password can be detected
+/// before any chunk is opened. It uses its own KDF-info-label, so it is independent from all
+/// chunk-keys. The serialized header (without the key-check-value) is appended to the KDF-info,
+/// so the key-check-value is a MAC over the algorithms and the extensions.
+pub fn key_check_value(stream_instance: &crypto::StreamInstance, base_key: &crypto::Key) -> Result<Vec<u8>, Error<CpError>> {
+	let mut kdf_buffer = [0u8; 32];
+	let mut info = try_err!(kdf_info(&mut kdf_buffer, 0, KEY_CHECK_INFO)).to_vec();
+	info.extend_from_slice(&stream_instance.authenticated_header());
+	
+	let key_check = try_err!(stream_instance.kdf.derive(base_key, &info));
+	Ok(key_check.as_slice().to_vec())
+}
+
+/// Derives the base-key for a new stream from `password` and sets the key-check-value (which must
+/// be updated with `update_key_check` if the header changes afterwards)
+pub fn derive_sealing_key(stream_instance: &mut crypto::StreamInstance, password: crypto::Password) -> Result<crypto::Key, Error<CpError>> {
+	let base_key = try_err!(stream_instance.pbkdf.derive(password));
+	try_err!(update_key_check(stream_instance, &base_key));
+	Ok(base_key)
+}
+
+/// Recomputes the key-check-value of a new stream (e.g. after an extension has been set)
+pub fn update_key_check(stream_instance: &mut crypto::StreamInstance, base_key: &crypto::Key) -> Result<(), Error<CpError>> {
+	stream_instance.key_check = Some(try_err!(key_check_value(stream_instance, base_key)));
+	Ok(())
+}
+
+/// Derives the base-key for a parsed stream from `password` and verifies it against the key-check-
+/// value (if the header contains one)
+pub fn derive_opening_key(stream_instance: &crypto::StreamInstance, password: crypto::Password) -> Result<crypto::Key, Error<CpError>> {
+	let base_key = try_err!(stream_instance.pbkdf.derive(password));
+	if let Some(ref key_check) = stream_instance.key_check {
+		if !crypto::backend::compare_constant_time(key_check, &try_err!(key_check_value(stream_instance, &base_key))) {
+			throw_err!(CpError::WrongPassword, "Wrong password or tampered header (the key-check-value does not match)")
+		}
+	}
+	Ok(base_key)
+}
+
+/// Derives the key for the `counter`th chunk and seals `chunk_buffer[.. chunk_length]` in place
+///
+/// Returns the length of the sealed chunk
+pub fn seal_chunk(stream_instance: &crypto::StreamInstance, base_key: &crypto::Key, counter: u64, is_last: bool, chunk_buffer: &mut[u8], chunk_length: usize) -> Result<usize, Error<CpError>> {
+	// Compute KDF-info and key
+	let mut kdf_buffer = [0u8; 32];
+	let chunk_info = if is_last { LAST_CHUNK_INFO } else { "" };
+	let key = try_err!(stream_instance.kdf.derive(base_key, try_err!(kdf_info(&mut kdf_buffer, counter, chunk_info))));
+	
+	// Seal chunk
+	stream_instance.auth_enc.seal(chunk_buffer, chunk_length, key)
+}
+
+/// Derives the key for the `counter`th chunk and opens `chunk_buffer[.. chunk_length]` in place
+///
+/// Returns the length of the opened data
+pub fn open_chunk(stream_instance: &crypto::StreamInstance, base_key: &crypto::Key, counter: u64, is_last: bool, chunk_buffer: &mut[u8], chunk_length: usize) -> Result<usize, Error<CpError>> {
+	// Compute KDF-info and key
+	let mut kdf_buffer = [0u8; 32];
+	let chunk_info = if is_last { LAST_CHUNK_INFO } else { "" };
+	let key = try_err!(stream_instance.kdf.derive(base_key, try_err!(kdf_info(&mut kdf_buffer, counter, chunk_info))));
+	
+	// Open chunk
+	stream_instance.auth_enc.open(chunk_buffer, chunk_length, key)
+}
+
+/// Returns the overhead of a sealed chunk (the overhead of the authenticated encryption and the
+/// session-ID if the stream is appendable)
+pub fn chunk_overhead(stream_instance: &crypto::StreamInstance) -> usize {
+	match stream_instance.appendable {
+		true => stream_instance.auth_enc.overhead() + SESSION_ID_SIZE,
+		false => stream_instance.auth_enc.overhead()
+	}
+}
+
+/// Derives the key for the `counter`th chunk of an appendable stream that is sealed by the session
+/// `session_id` (the KDF-info is `counter || session_id || label`)
+fn appendable_chunk_key(stream_instance: &crypto::StreamInstance, base_key: &crypto::Key, counter: u64, session_id: u64, is_last: bool) -> Result<crypto::Key, Error<CpError>> {
+	let mut kdf_buffer = [0u8; 64];
+	let chunk_info = if is_last { APPEND_LAST_CHUNK_INFO } else { APPEND_CHUNK_INFO };
+	
+	// Serialize the counter in front of `session_id || label`
+	let info_length = try_err!(kdf_info(&mut kdf_buffer[8 ..], session_id, chunk_info)).len();
+	try_err!(kdf_info(&mut kdf_buffer[.. 8], counter, ""));
+	stream_instance.kdf.derive(base_key, &kdf_buffer[.. 8 + info_length])
+}
+
+/// Seals the `counter`th chunk of an appendable stream in place and appends `session_id` (every
+/// sealing-operation uses a new random session-ID, so no chunk-key is ever used twice)
+///
+/// Returns the length of the sealed chunk
+pub fn seal_appendable_chunk(stream_instance: &crypto::StreamInstance, base_key: &crypto::Key, counter: u64, session_id: u64, is_last: bool, chunk_buffer: &mut[u8], chunk_length: usize) -> Result<usize, Error<CpError>> {
+	let key = try_err!(appendable_chunk_key(stream_instance, base_key, counter, session_id, is_last));
+	let sealed_length = try_err!(stream_instance.auth_enc.seal(chunk_buffer, chunk_length, key));
+	if chunk_buffer.len() < sealed_length + SESSION_ID_SIZE { throw_err!(CpError::InvalidParameter) }
+	chunk_buffer[sealed_length .. sealed_length + SESSION_ID_SIZE].copy_from_slice(&session_id.to_be_bytes());
+	Ok(sealed_length + SESSION_ID_SIZE)
+}
+
+/// Opens the `counter`th chunk of an appendable stream in place (with the key of the session whose
+/// ID follows the chunk)
+///
+/// Returns the length of the opened data
+pub fn open_appendable_chunk(stream_instance: &crypto::StreamInstance, base_key: &crypto::Key, counter: u64, is_last: bool, chunk_buffer: &mut[u8], chunk_length: usize) -> Result<usize, Error<CpError>> {
+	if chunk_length < SESSION_ID_SIZE || chunk_length > chunk_buffer.len() { throw_err!(CpError::InvalidData, "Invalid chunk-length") }
+	let mut session_id = [0u8; SESSION_ID_SIZE];
+	session_id.copy_from_slice(&chunk_buffer[chunk_length - SESSION_ID_SIZE .. chunk_length]);
+	
+	let key = try_err!(appendable_chunk_key(stream_instance, base_key, counter, u64::from_be_bytes(session_id), is_last));
+	stream_instance.auth_enc.open(chunk_buffer, chunk_length - SESSION_ID_SIZE, key)
+}
+
+/// Opens the `counter`th chunk of a stream (appendable or not) in place
+///
+/// Returns the length of the opened data
+fn open_stream_chunk(stream_instance: &crypto::StreamInstance, base_key: &crypto::Key, counter: u64, is_last: bool, chunk_buffer: &mut[u8], chunk_length: usize) -> Result<usize, Error<CpError>> {
+	match stream_instance.appendable {
+		true => open_appendable_chunk(stream_instance, base_key, counter, is_last, chunk_buffer, chunk_length),
+		false => open_chunk(stream_instance, base_key, counter, is_last, chunk_buffer, chunk_length)
+	}
+}
+
+/// Rejects streams whose chunks cannot be opened one by one (streams with parity-blocks,
+/// compression, padding or metadata can only be opened by `Decryptor`)
+pub fn require_plain_chunks(stream_instance: &crypto::StreamInstance) -> Result<(), Error<CpError>> {
+	if stream_instance.parity.is_some() { throw_err!(CpError::Unsupported, "Streams with parity-blocks can only be opened with `Decryptor`") }
+	if stream_instance.compression.is_some() { throw_err!(CpError::Unsupported, "Compressed streams can only be opened with `Decryptor`") }
+	if stream_instance.padding.is_some() { throw_err!(CpError::Unsupported, "Padded streams can only be opened with `Decryptor`") }
+	if stream_instance.metadata_length.is_some() { throw_err!(CpError::Unsupported, "Streams with metadata can only be opened with `Decryptor`") }
+	if stream_instance.archive { throw_err!(CpError::Unsupported, "Archives can only be opened with `archive::ArchiveReader`") }
+	if stream_instance.appendable { throw_err!(CpError::Unsupported, "Appendable streams can only be opened with `Decryptor`") }
+	if stream_instance.seek_index { throw_err!(CpError::Unsupported, "Streams with a seek-index can only be opened with `Decryptor` or `seekable::DecryptReader`") }
+	Ok(())
+}
+
+/// Seals `metadata` as metadata-chunk (which uses its own KDF-info-label, so it cannot be confused
+/// with a data-chunk)
+pub fn seal_metadata(stream_instance: &crypto::StreamInstance, base_key: &crypto::Key, metadata: &metadata::Metadata) -> Result<Vec<u8>, Error<CpError>> {
+	// Serialize and validate the metadata
+	let serialized = metadata.serialize();
+	if serialized.len() > metadata::MAX_METADATA_SIZE { throw_err!(CpError::InvalidParameter, "The metadata is too large") }
+	
+	// Compute the key and seal the metadata
+	let mut kdf_buffer = [0u8; 32];
+	let key = try_err!(stream_instance.kdf.derive(base_key, try_err!(kdf_info(&mut kdf_buffer, 0, METADATA_INFO))));
+	let mut chunk_buffer = serialized.clone();
+	chunk_buffer.resize(serialized.len() + stream_instance.auth_enc.overhead(), 0x00);
+	let sealed_length = try_err!(stream_instance.auth_enc.seal(&mut chunk_buffer, serialized.len(), key));
+	chunk_buffer.truncate(sealed_length);
+	Ok(chunk_buffer)
+}
+
+/// Reads and opens the metadata-chunk that follows the stream-header (if the stream has one)
+pub fn read_metadata(io: &mut io::Io, stream_instance: &crypto::StreamInstance, base_key: &crypto::Key) -> Result<Option<metadata::Metadata>, Error<CpError>> {
+	let sealed_length = match stream_instance.metadata_length {
+		Some(sealed_length) => sealed_length as usize,
+		None => return Ok(None)
+	};
+	
+	// Read and open the metadata-chunk
+	let mut chunk_buffer = vec![0u8; sealed_length];
+	try_err!(io.read_exact(&mut chunk_buffer));
+	let mut kdf_buffer = [0u8; 32];
+	let key = try_err!(stream_instance.kdf.derive(base_key, try_err!(kdf_info(&mut kdf_buffer, 0, METADATA_INFO))));
+	let length = try_err!(stream_instance.auth_enc.open(&mut chunk_buffer, sealed_length, key));
+	Ok(Some(try_err!(metadata::Metadata::from_serialized(&chunk_buffer[.. length]))))
+}
+
+/// Reads and parses the stream-header from `io`
+pub fn read_header(io: &mut io::Io) -> Result<crypto::StreamInstance, Error<CpError>> {
+	// Determine stream-header-length
+	let (mut header_data, mut header_pos) = (vec![0u8; 0], 0);
+	let header_length = 'read_header_loop: loop {
+		if let Some(length) = try_err!(crypto::StreamInstance::try_parse_length(&header_data)) {
+			break 'read_header_loop length
+		} else {
+			header_data.resize(header_pos + 1, 0x00);
+			try_err!(io.read_exact(&mut header_data[header_pos .. header_pos + 1]));
+			header_pos += 1;
+		}
+	};
+	
+	// Read remaining header-data
+	header_data.resize(header_length, 0x00);
+	try_err!(io.read_exact(&mut header_data[header_pos ..]));
+	
+	crypto::StreamInstance::from_serialized(header_data)
+}
+
 
 
 pub struct Encryptor<'a> {
 	io: &'a mut io::Io,
 	key: crypto::Key,
-	stream_instance: crypto::StreamInstance
+	stream_instance: crypto::StreamInstance,
+	metadata: Option<metadata::Metadata>,
+	first_counter: u64,
+	position: Position,
+	/// The chunk-offsets and the plaintext-length (if the stream has a seek-index)
+	seek_index: seekable::SeekIndex,
+	progress: Option<Box<FnMut(&Position) + 'a>>
 }
 impl<'a> Encryptor<'a> {
-	pub fn new(password: String, io: &'a mut io::Io, stream_instance: crypto::StreamInstance) -> Result<Self, Error<CpError>> {
+	pub fn new<P: Into<crypto::Password>>(password: P, io: &'a mut io::Io, mut stream_instance: crypto::StreamInstance) -> Result<Self, Error<CpError>> {
 		// Generate key from password
-		let key = try_err!(stream_instance.pbkdf.derive(password));
-		Ok(Encryptor{ io, key, stream_instance })
+		let key = try_err!(derive_sealing_key(&mut stream_instance, password.into()));
+		Ok(Self::with_key(key, io, stream_instance))
+	}
+	/// Creates an encryptor that seals the chunks with an already derived `key` (see `seal_body`)
+	pub fn with_key(key: crypto::Key, io: &'a mut io::Io, stream_instance: crypto::StreamInstance) -> Self {
+		Encryptor{ io, key, stream_instance, metadata: None, first_counter: 0, position: Position::default(), seek_index: seekable::SeekIndex::default(), progress: None }
+	}
+	
+	/// Sets the metadata that is sealed as first chunk (see `metadata`)
+	pub fn set_metadata(&mut self, metadata: metadata::Metadata) {
+		self.metadata = Some(metadata)
+	}
+	/// Sets the index of the first chunk that is sealed by `seal_body` (to continue an appendable
+	/// stream; see `append`)
+	pub fn set_first_counter(&mut self, counter: u64) {
+		self.first_counter = counter
+	}
+	/// Sets a callback that is invoked with the current position after every sealed chunk (and its
+	/// parity-blocks)
+	pub fn set_progress(&mut self, progress: Box<FnMut(&Position) + 'a>) {
+		self.progress = Some(progress)
+	}
+	
+	/// Returns the current position (`plaintext_bytes` is the amount of plaintext-bytes that have been
+	/// read)
+	pub fn position(&self) -> Position {
+		self.position
 	}
 	
 	pub fn runloop(&mut self) -> Result<(), Error<CpError>> {
-		// Serialize stream-instance and write it as header
-		try_err!(self.io.write_exact(&self.stream_instance.as_serialized().into_der_encoded()));
+		// Seal the metadata (the header contains the length of the metadata-chunk)
+		let sealed_metadata = match self.metadata {
+			Some(ref metadata) => Some(try_err!(seal_metadata(&self.stream_instance, &self.key, metadata))),
+			None => None
+		};
+		self.stream_instance.metadata_length = sealed_metadata.as_ref().map(|sealed| sealed.len() as u64);
+		try_err!(update_key_check(&mut self.stream_instance, &self.key));
 		
-		// Initialize KDF-counter and -buffer and chunk-buffer
-		let mut counter = 0u64;
-		let (mut kdf_buffer, mut chunk_buffer) = ([0u8; 32], vec![0u8; CHUNK_DATA_SIZE + self.stream_instance.auth_enc.overhead()]);
+		// Serialize stream-instance and write it as header (followed by the metadata-chunk if any)
+		let header = self.stream_instance.as_serialized().into_der_encoded();
+		try_err!(self.io.write_exact(&header));
+		if let Some(ref sealed_metadata) = sealed_metadata { try_err!(self.io.write_exact(sealed_metadata)) }
+		self.position.ciphertext_offset = header.len() as u64 + self.stream_instance.metadata_length.unwrap_or(0);
+		try_err!(self.seal_body());
+		
+		// Write the seek-index after the last chunk (see `seekable`)
+		if self.stream_instance.seek_index {
+			let trailer = try_err!(self.seek_index.seal(&self.stream_instance, &self.key));
+			try_err!(self.io.write_exact(&trailer));
+		}
+		Ok(())
+	}
+	
+	/// Seals the entire input of `io` as chunk-sequence without header (the stream-instance
+	/// determines the parity, the compression and the padding)
+	pub fn seal_body(&mut self) -> Result<(), Error<CpError>> {
+		// Initialize KDF-counter, chunk-buffer and the parity-group (if any)
+		let mut counter = self.first_counter;
+		let shard_length = CHUNK_DATA_SIZE + self.stream_instance.auth_enc.overhead();
+		let mut chunk_buffer = vec![0u8; CHUNK_DATA_SIZE + chunk_overhead(&self.stream_instance)];
+		let reed_solomon = match self.stream_instance.parity {
+			Some(parity) => Some((try_err!(fec::ReedSolomon::with_parity(parity)), parity)),
+			None => None
+		};
+		let mut group: Vec<Vec<u8>> = Vec::new();
+		let mut compressor = self.stream_instance.compression.map(compression::Compressor::new);
+		let mut padder = self.stream_instance.padding.map(padding::Padder::new);
+		
+		// Every sealing-operation of an appendable stream uses a new random session-ID
+		let session_id = match self.stream_instance.appendable {
+			true => {
+				let mut session_id = [0u8; SESSION_ID_SIZE];
+				crypto::random(&mut session_id);
+				Some(u64::from_be_bytes(session_id))
+			},
+			false => None
+		};
 		
 		// Process chunks
+		self.position.chunk_index = counter;
+		let mut source_bytes = 0u64;
 		loop {
-			// Read chunk
-			let (chunk_length, is_last) = try_err!(self.io.read_chunk(&mut chunk_buffer[.. CHUNK_DATA_SIZE]));
-			
-			// Compute KDF-info and key
-			let chunk_info = if is_last { "#Last Chunk" } else { "" };
-			let key = try_err!(self.stream_instance.kdf.derive(&self.key, try_err!(kdf_info(&mut kdf_buffer, counter, chunk_info))));
-			counter += 1;
+			// Read chunk (through the compressor and the padder if any)
+			let (chunk_length, is_last) = {
+				let (io, source_bytes) = (&mut *self.io, &mut source_bytes);
+				let mut read = |buffer: &mut[u8]| {
+					let (length, is_last) = try_err!(match compressor {
+						Some(ref mut compressor) => compressor.read_chunk(&mut *io, buffer),
+						None => io.read_chunk(buffer)
+					});
+					*source_bytes += length as u64;
+					Ok((length, is_last))
+				};
+				match padder {
+					Some(ref mut padder) => try_err!(padder.read_chunk(&mut chunk_buffer[.. CHUNK_DATA_SIZE], &mut read)),
+					None => try_err!(read(&mut chunk_buffer[.. CHUNK_DATA_SIZE]))
+				}
+			};
 			
 			// Seal and print chunk
-			let sealed_length = try_err!(self.stream_instance.auth_enc.seal(&mut chunk_buffer, chunk_length, key));
+			let sealed_length = match session_id {
+				Some(session_id) => try_err!(seal_appendable_chunk(&self.stream_instance, &self.key, counter, session_id, is_last, &mut chunk_buffer, chunk_length)),
+				None => try_err!(seal_chunk(&self.stream_instance, &self.key, counter, is_last, &mut chunk_buffer, chunk_length))
+			};
 			try_err!(self.io.write_chunk(&chunk_buffer[.. sealed_length]));
+			if self.stream_instance.seek_index { self.seek_index.chunk_offsets.push(self.position.ciphertext_offset) }
+			counter += 1;
 			
-			// Return after last chunk
-			if is_last { return Ok(()) }
+			// Advance the position (the compressor reads the plaintext ahead)
+			self.position.chunk_index = counter;
+			self.position.ciphertext_offset += sealed_length as u64;
+			self.position.plaintext_bytes = match compressor {
+				Some(ref compressor) => compressor.plaintext_bytes(),
+				None => source_bytes
+			};
+			
+			// Append the parity-blocks after every complete group and after the last chunk
+			if let Some((ref reed_solomon, parity)) = reed_solomon {
+				let mut shard = chunk_buffer[.. sealed_length].to_vec();
+				shard.resize(shard_length, 0x00);
+				group.push(shard);
+				
+				if group.len() == parity.data_chunks || is_last {
+					let mut parity_blocks = vec![vec![0u8; shard_length]; parity.parity_chunks];
+					{
+						let shards: Vec<&[u8]> = group.iter().map(|shard| shard.as_slice()).collect();
+						try_err!(reed_solomon.encode(&shards, &mut parity_blocks));
+					}
+					for parity_block in parity_blocks.iter() { try_err!(self.io.write_chunk(parity_block)) }
+					self.position.ciphertext_offset += (parity_blocks.len() * shard_length) as u64;
+					group.clear();
+				}
+			}
+			if let Some(ref mut progress) = self.progress { progress(&self.position) }
+			
+			// Return after last chunk (the seek-index contains the plaintext-length without padding)
+			if is_last {
+				self.seek_index.plaintext_length = source_bytes;
+				return Ok(())
+			}
 		}
 	}
 }
 
 
 
+/// The position of an `Encryptor` or a `Decryptor` in the sealed stream
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct Position {
+	/// The index of the current chunk
+	pub chunk_index: u64,
+	/// The offset of the current chunk in the sealed stream (including the stream-header)
+	pub ciphertext_offset: u64,
+	/// The amount of plaintext-bytes that have already been written (or read by an `Encryptor`)
+	pub plaintext_bytes: u64
+}
+
+
+
+/// How a `Decryptor` handles chunks that fail authentication
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Salvage {
+	/// Abort with `CpError::InvalidData` (the default)
+	Off,
+	/// Skip the chunk and write zeros in place of its plaintext
+	Zeros,
+	/// Skip the chunk and write nothing in place of its plaintext
+	Skip
+}
+
+/// A chunk that was skipped in salvage-mode
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct DamagedChunk {
+	/// The index of the chunk
+	pub chunk_index: u64,
+	/// The offset of the chunk in the sealed stream
+	pub ciphertext_offset: u64,
+	/// The offset of the missing plaintext in the original plaintext
+	pub plaintext_offset: u64,
+	/// The length of the missing plaintext
+	pub plaintext_length: u64
+}
+
+/// The chunks that were skipped in salvage-mode
+#[derive(Debug, Default, Clone, Eq, PartialEq)]
+pub struct SalvageReport {
+	/// The skipped chunks in stream-order
+	pub damaged: Vec<DamagedChunk>,
+	/// Whether the stream ends without a valid last chunk (the end of the plaintext is missing)
+	pub truncated: bool
+}
+impl SalvageReport {
+	/// Returns whether all chunks could be recovered
+	pub fn is_complete(&self) -> bool {
+		self.damaged.is_empty() && !self.truncated
+	}
+}
+
+
+
+/// A group of sealed chunks and its parity-blocks
+struct ParityGroup<'g> {
+	/// The sealed chunks (zero-padded to the length of a full sealed chunk)
+	shards: Vec<Vec<u8>>,
+	parity_blocks: Vec<&'g [u8]>,
+	/// The length of the last sealed chunk in the group
+	last_length: usize,
+	/// Whether this is the last group of the stream
+	is_last: bool
+}
+
+/// A chunk that was reconstructed from the parity-blocks
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct RepairedChunk {
+	/// The index of the chunk
+	pub chunk_index: u64,
+	/// The offset of the chunk in the sealed stream
+	pub ciphertext_offset: u64
+}
+
+
+
 pub struct Decryptor<'a> {
 	io: &'a mut io::Io,
-	password: Option<String>
+	password: Option<crypto::Password>,
+	position: Position,
+	salvage: Salvage,
+	report: SalvageReport,
+	repaired: Vec<RepairedChunk>,
+	max_compression_ratio: u64,
+	decompressor: Option<compression::Decompressor>,
+	unpadder: Option<padding::Unpadder>,
+	metadata: Option<metadata::Metadata>,
+	key_verifier: Option<Box<FnMut(&crypto::StreamInstance, &crypto::Key) -> Result<(), Error<CpError>> + 'a>>,
+	/// Separates the seek-index from the chunks (if the stream has one)
+	trailer: Option<seekable::TrailerStripper>,
+	/// The offsets of the opened chunks (if the stream has a seek-index)
+	chunk_offsets: Vec<u64>,
+	progress: Option<Box<FnMut(&Position) + 'a>>
 }
 impl<'a> Decryptor<'a> {
-	pub fn new(password: String, io: &'a mut io::Io) -> Result<Self, Error<CpError>> {
-		Ok(Decryptor{ io, password: Some(password) })
+	pub fn new<P: Into<crypto::Password>>(password: P, io: &'a mut io::Io) -> Result<Self, Error<CpError>> {
+		let mut decryptor = Self::without_password(io);
+		decryptor.password = Some(password.into());
+		Ok(decryptor)
+	}
+	/// Creates a decryptor that can only open chunk-sequences with an already derived key (see
+	/// `open_body`; `runloop` fails)
+	pub fn without_password(io: &'a mut io::Io) -> Self {
+		Decryptor {
+			io, password: None, position: Position::default(),
+			salvage: Salvage::Off, report: SalvageReport::default(), repaired: Vec::new(),
+			max_compression_ratio: compression::DEFAULT_MAX_COMPRESSION_RATIO, decompressor: None, unpadder: None, metadata: None,
+			key_verifier: None, trailer: None, chunk_offsets: Vec::new(), progress: None
+		}
+	}
+	
+	/// Sets the salvage-mode (chunks are always separated on the fixed sealed chunk-boundaries, so
+	/// the chunks after a damaged chunk can still be opened)
+	pub fn set_salvage(&mut self, salvage: Salvage) {
+		self.salvage = salvage
+	}
+	/// Sets the maximum ratio between the decompressed and the compressed data of compressed streams
+	/// (see `compression::Decompressor`)
+	pub fn set_max_compression_ratio(&mut self, max_ratio: u64) {
+		self.max_compression_ratio = max_ratio
+	}
+	/// Sets a callback that is invoked by `runloop` with the stream-instance and the base-key before
+	/// any chunk is opened (e.g. to authenticate the volume-headers; see `volume::VolumeSet::verify`)
+	pub fn set_key_verifier(&mut self, verifier: Box<FnMut(&crypto::StreamInstance, &crypto::Key) -> Result<(), Error<CpError>> + 'a>) {
+		self.key_verifier = Some(verifier)
+	}
+	/// Sets a callback that is invoked with the current position after every opened chunk (or after
+	/// every parity-group if the stream has parity-blocks)
+	pub fn set_progress(&mut self, progress: Box<FnMut(&Position) + 'a>) {
+		self.progress = Some(progress)
+	}
+	/// Returns the metadata of the stream (available once `runloop` has opened the metadata-chunk)
+	pub fn metadata(&self) -> Option<&metadata::Metadata> {
+		self.metadata.as_ref()
+	}
+	/// Returns the chunks that were skipped in salvage-mode
+	pub fn salvage_report(&self) -> &SalvageReport {
+		&self.report
+	}
+	/// Returns the chunks that were reconstructed from the parity-blocks (if the stream has any)
+	pub fn repaired_chunks(&self) -> &[RepairedChunk] {
+		&self.repaired
+	}
+	
+	/// Returns the current position (if `runloop` fails, this is the position of the erroneous
+	/// chunk)
+	pub fn position(&self) -> Position {
+		self.position
 	}
 	
 	pub fn runloop(&mut self) -> Result<(), Error<CpError>> {
-		// Create stream-instance
-		let stream_instance = {
-			// Determine stream-header-length
-			let (mut header_data, mut header_pos) = (vec![0u8; 0], 0);
-			let header_length = 'read_header_loop: loop {
-				if let Some(length) = try_err!(crypto::StreamInstance::try_parse_length(&header_data)) {
-					break 'read_header_loop length
-				} else {
-					header_data.resize(header_pos + 1, 0x00);
-					try_err!(self.io.read_exact(&mut header_data[header_pos .. header_pos + 1]));
-					header_pos += 1;
-				}
-			};
-			
-			// Read remaining header-data
-			header_data.resize(header_length, 0x00);
-			try_err!(self.io.read_exact(&mut header_data[header_pos ..]));
-			
-			try_err!(crypto::StreamInstance::from_serialized(header_data))
-		};
+		// Read the stream-header (the DER-encoding is canonical, so the re-encoded header has the same
+		// length)
+		let stream_instance = try_err!(read_header(self.io));
+		self.position.ciphertext_offset = stream_instance.as_serialized().into_der_encoded().len() as u64;
 		
-		// Compute base-key and initialize KDF-counter and -buffer and chunk-buffer
+		// Compute base-key
 		let password = if let Some(password) = self.password.take() { password }
-			else { throw_err!(CpError::Unsupported, "`runloop()` cannot be invoked twice on the same instance") };
-		let base_key = try_err!(stream_instance.pbkdf.derive(password));
+			else { throw_err!(CpError::Unsupported, "`runloop()` requires a password and cannot be invoked twice on the same instance") };
+		let base_key = try_err!(derive_opening_key(&stream_instance, password));
+		if let Some(ref mut verifier) = self.key_verifier { try_err!(verifier(&stream_instance, &base_key)) }
 		
-		let mut counter = 0u64;
-		let (mut kdf_buffer, mut chunk_buffer) = ([0u8; 32], vec![0u8; CHUNK_DATA_SIZE + stream_instance.auth_enc.overhead()]);
+		// Open the metadata-chunk (if any)
+		if stream_instance.archive { throw_err!(CpError::Unsupported, "The stream is an archive (use `archive::ArchiveReader` or `open-dir`)") }
+		self.metadata = try_err!(read_metadata(self.io, &stream_instance, &base_key));
+		self.position.ciphertext_offset += stream_instance.metadata_length.unwrap_or(0);
+		if stream_instance.seek_index { self.trailer = Some(seekable::TrailerStripper::new(stream_instance.auth_enc.overhead())) }
+		try_err!(self.open_body(&stream_instance, &base_key));
 		
-		// Process chunks
+		// Verify the seek-index against the opened chunks (if chunks are missing, it cannot match)
+		if let Some(trailer) = self.trailer.take() {
+			if self.report.is_complete() {
+				let sealed_index = match trailer.into_sealed_index() {
+					Some(sealed_index) => sealed_index,
+					None => throw_err!(CpError::InvalidData, "The stream is truncated (the seek-index is missing)")
+				};
+				let index = try_err!(seekable::SeekIndex::open(sealed_index, &stream_instance, &base_key));
+				if index.chunk_offsets != self.chunk_offsets || index.plaintext_length != self.position.plaintext_bytes {
+					throw_err!(CpError::InvalidData, "The seek-index does not match the stream")
+				}
+			}
+		}
+		Ok(())
+	}
+	
+	/// Opens a chunk-sequence without header that has been sealed with `base_key` (see
+	/// `Encryptor::seal_body`)
+	pub fn open_body(&mut self, stream_instance: &crypto::StreamInstance, base_key: &crypto::Key) -> Result<(), Error<CpError>> {
+		// Initialize the decompressor (a skipped chunk would break the record-stream)
+		if let Some(compression) = stream_instance.compression {
+			if self.salvage != Salvage::Off { throw_err!(CpError::Unsupported, "Compressed streams cannot be salvaged") }
+			self.decompressor = Some(compression::Decompressor::new(compression, self.max_compression_ratio, stream_instance.auth_enc.overhead()));
+		}
+		if stream_instance.padding.is_some() { self.unpadder = Some(padding::Unpadder::new()) }
+		
+		try_err!(match stream_instance.parity {
+			Some(parity) => self.open_groups(stream_instance, base_key, parity),
+			None => self.open_chunks(stream_instance, base_key)
+		});
+		
+		// Strip the padding (if chunks are missing, the trailer cannot be located and the padding is
+		// written as well)
+		if self.unpadder.is_some() {
+			let complete = self.report.is_complete();
+			let written = try_err!(self.write_output(|unpadder, sink| match *unpadder {
+				Some(ref mut unpadder) if complete => unpadder.finish(sink),
+				Some(ref mut unpadder) => unpadder.flush(sink),
+				None => Ok(())
+			}));
+			self.position.plaintext_bytes += written as u64;
+		}
+		if let Some(ref decompressor) = self.decompressor { try_err!(decompressor.finish()) }
+		Ok(())
+	}
+	
+	/// Writes opened `data` (strips the padding and decompresses it if necessary) and returns the
+	/// amount of plaintext-bytes written
+	fn write_plaintext(&mut self, data: &[u8]) -> Result<usize, Error<CpError>> {
+		self.write_output(|unpadder, sink| match *unpadder {
+			Some(ref mut unpadder) => unpadder.write_chunk(data, sink),
+			None => sink(data)
+		})
+	}
+	
+	/// Calls `write` with the unpadder and a sink that decompresses the data (if the stream is
+	/// compressed) and writes it to `io`; returns the amount of plaintext-bytes written
+	fn write_output<F>(&mut self, write: F) -> Result<usize, Error<CpError>>
+		where F: FnOnce(&mut Option<padding::Unpadder>, &mut FnMut(&[u8]) -> Result<(), Error<CpError>>) -> Result<(), Error<CpError>>
+	{
+		let (io, decompressor, mut written) = (&mut *self.io, &mut self.decompressor, 0);
+		{
+			let mut sink = |data: &[u8]| -> Result<(), Error<CpError>> {
+				written += match *decompressor {
+					Some(ref mut decompressor) => try_err!(decompressor.write_chunk(&mut *io, data)),
+					None => { try_err!(io.write_chunk(data)); data.len() }
+				};
+				Ok(())
+			};
+			try_err!(write(&mut self.unpadder, &mut sink));
+		}
+		Ok(written)
+	}
+	
+	/// Reads the next sealed chunk (without the seek-index if the stream has one) like
+	/// `io::Io::read_chunk`
+	fn read_body_chunk(&mut self, chunk_buffer: &mut[u8]) -> Result<(usize, bool), Error<CpError>> {
+		match self.trailer {
+			Some(ref mut trailer) => trailer.read_chunk(&mut *self.io, chunk_buffer),
+			None => self.io.read_chunk(chunk_buffer)
+		}
+	}
+	
+	/// Records the offset of the current chunk (if the stream has a seek-index)
+	fn record_chunk_offset(&mut self, ciphertext_offset: u64) {
+		if self.trailer.is_some() { self.chunk_offsets.push(ciphertext_offset) }
+	}
+	
+	/// Invokes the progress-callback (if any) with the current position
+	fn report_progress(&mut self) {
+		if let Some(ref mut progress) = self.progress { progress(&self.position) }
+	}
+	
+	/// Opens a stream without parity-blocks chunk by chunk
+	fn open_chunks(&mut self, stream_instance: &crypto::StreamInstance, base_key: &crypto::Key) -> Result<(), Error<CpError>> {
+		let mut chunk_buffer = vec![0u8; CHUNK_DATA_SIZE + chunk_overhead(stream_instance)];
 		loop {
 			// Read chunk
-			let (chunk_length, is_last) = try_err!(self.io.read_chunk(&mut chunk_buffer));
+			let (chunk_length, is_last) = try_err!(self.read_body_chunk(&mut chunk_buffer));
 			
-			// Compute KDF-info and key
-			let chunk_info = if is_last { "#Last Chunk" } else { "" };
-			let key = try_err!(stream_instance.kdf.derive(&base_key, try_err!(kdf_info(&mut kdf_buffer, counter, chunk_info))));
-			counter += 1;
+			// Open and print chunk
+			let data_length = match self.salvage == Salvage::Off {
+				true => try_err!(open_stream_chunk(stream_instance, base_key, self.position.chunk_index, is_last, &mut chunk_buffer, chunk_length)),
+				false => try_err!(self.salvage_chunk(stream_instance, base_key, is_last, &mut chunk_buffer, chunk_length))
+			};
+			let written = try_err!(self.write_plaintext(&chunk_buffer[.. data_length]));
 			
-			// Seal and print chunk
-			let data_length = try_err!(stream_instance.auth_enc.open(&mut chunk_buffer, chunk_length, key));
-			try_err!(self.io.write_chunk(&chunk_buffer[.. data_length]));
+			// Advance the position
+			let ciphertext_offset = self.position.ciphertext_offset;
+			self.record_chunk_offset(ciphertext_offset);
+			self.position.chunk_index += 1;
+			self.position.ciphertext_offset += chunk_length as u64;
+			self.position.plaintext_bytes += written as u64;
+			self.report_progress();
 			
 			// Return after last chunk
 			if is_last { return Ok(()) }
 		}
 	}
+	
+	/// Opens a stream with parity-blocks group by group and repairs damaged chunks
+	///
+	/// Every group consists of `parity.data_chunks` sealed chunks followed by `parity.parity_chunks`
+	/// parity-blocks; the last group may contain fewer chunks (the amount is determined by its
+	/// length because the parity-blocks always have the length of a full sealed chunk).
+	fn open_groups(&mut self, stream_instance: &crypto::StreamInstance, base_key: &crypto::Key, parity: fec::Parity) -> Result<(), Error<CpError>> {
+		let reed_solomon = try_err!(fec::ReedSolomon::with_parity(parity));
+		let shard_length = CHUNK_DATA_SIZE + stream_instance.auth_enc.overhead();
+		let mut group = vec![0u8; (parity.data_chunks + parity.parity_chunks) * shard_length];
+		let mut opened = vec![0u8; parity.data_chunks * shard_length];
+		let mut chunk_buffer = vec![0u8; shard_length];
+		
+		loop {
+			// Read the group
+			let (mut group_length, mut is_last) = (0, false);
+			while group_length < group.len() && !is_last {
+				let (length, last) = try_err!(self.read_body_chunk(&mut group[group_length .. group_length + shard_length]));
+				group_length += length;
+				is_last = last;
+			}
+			
+			// Compute the amount of chunks and the length of the last chunk in the group
+			let (chunk_count, last_length) = match is_last {
+				true => {
+					let shards = (group_length + shard_length - 1) / shard_length;
+					if shards <= parity.parity_chunks { throw_err!(CpError::InvalidData, "The stream is truncated (incomplete parity-group)") }
+					(shards - parity.parity_chunks, group_length - (shards - 1) * shard_length)
+				},
+				false => (parity.data_chunks, shard_length)
+			};
+			let chunk_length = |index: usize| if index + 1 == chunk_count { last_length } else { shard_length };
+			let parity_offset = (chunk_count - 1) * shard_length + last_length;
+			
+			// Open all chunks and repair the damaged ones
+			let (mut data_lengths, mut damaged) = (Vec::new(), Vec::new());
+			for index in 0..chunk_count {
+				let (counter, chunk) = (self.position.chunk_index + index as u64, &mut opened[index * shard_length .. (index + 1) * shard_length]);
+				chunk[.. chunk_length(index)].copy_from_slice(&group[index * shard_length .. index * shard_length + chunk_length(index)]);
+				match open_chunk(stream_instance, base_key, counter, is_last && index + 1 == chunk_count, chunk, chunk_length(index)) {
+					Ok(data_length) => data_lengths.push(Some(data_length)),
+					Err(ref error) if error.kind == CpError::InvalidData => { data_lengths.push(None); damaged.push(index) },
+					Err(error) => return Err(error)
+				}
+			}
+			if !damaged.is_empty() {
+				let repaired = {
+					let parity_group = ParityGroup {
+						shards: (0..chunk_count).map(|index| {
+							let mut shard = group[index * shard_length .. index * shard_length + chunk_length(index)].to_vec();
+							shard.resize(shard_length, 0x00);
+							shard
+						}).collect(),
+						parity_blocks: (0..parity.parity_chunks)
+							.map(|index| &group[parity_offset + index * shard_length .. parity_offset + (index + 1) * shard_length])
+							.collect(),
+						last_length, is_last
+					};
+					try_err!(self.repair(stream_instance, base_key, &reed_solomon, &parity_group, &damaged))
+				};
+				if let Some(repaired) = repaired {
+					for &index in damaged.iter() {
+						group[index * shard_length .. index * shard_length + chunk_length(index)].copy_from_slice(&repaired[index][.. chunk_length(index)]);
+						self.repaired.push(RepairedChunk {
+							chunk_index: self.position.chunk_index + index as u64,
+							ciphertext_offset: self.position.ciphertext_offset + (index * shard_length) as u64
+						});
+					}
+				}
+			}
+			
+			// Print the chunks (and open the repaired or skip the damaged ones)
+			for index in 0..chunk_count {
+				let written = if let Some(data_length) = data_lengths[index] {
+					try_err!(self.write_plaintext(&opened[index * shard_length .. index * shard_length + data_length]))
+				} else {
+					let is_last_chunk = is_last && index + 1 == chunk_count;
+					chunk_buffer[.. chunk_length(index)].copy_from_slice(&group[index * shard_length .. index * shard_length + chunk_length(index)]);
+					let data_length = match open_chunk(stream_instance, base_key, self.position.chunk_index, is_last_chunk, &mut chunk_buffer, chunk_length(index)) {
+						Ok(data_length) => data_length,
+						Err(error) => {
+							let plaintext_length = chunk_length(index).saturating_sub(stream_instance.auth_enc.overhead());
+							try_err!(self.skip_damaged(error, &mut chunk_buffer, plaintext_length))
+						}
+					};
+					try_err!(self.write_plaintext(&chunk_buffer[.. data_length]))
+				};
+				
+				// Advance the position
+				let ciphertext_offset = self.position.ciphertext_offset;
+				self.record_chunk_offset(ciphertext_offset);
+				self.position.chunk_index += 1;
+				self.position.ciphertext_offset += chunk_length(index) as u64;
+				self.position.plaintext_bytes += written as u64;
+			}
+			self.position.ciphertext_offset += (parity.parity_chunks * shard_length) as u64;
+			self.report_progress();
+			
+			// Return after last group
+			if is_last { return Ok(()) }
+		}
+	}
+	
+	/// Tries to reconstruct the `damaged` chunks of `group` from its parity-blocks
+	///
+	/// Because the parity-blocks are not authenticated, the combinations of parity-blocks are tried
+	/// (up to `MAX_REPAIR_ATTEMPTS`) until all reconstructed chunks pass authentication. Returns the
+	/// repaired shards or `None` if the chunks cannot be repaired.
+	fn repair(&self, stream_instance: &crypto::StreamInstance, base_key: &crypto::Key, reed_solomon: &fec::ReedSolomon, group: &ParityGroup, damaged: &[usize])
+		-> Result<Option<Vec<Vec<u8>>>, Error<CpError>>
+	{
+		if damaged.len() > group.parity_blocks.len() { return Ok(None) }
+		let chunk_count = group.shards.len();
+		
+		// Iterate over the combinations of `damaged.len()` parity-blocks
+		let mut selection: Vec<usize> = (0..damaged.len()).collect();
+		for _ in 0..MAX_REPAIR_ATTEMPTS {
+			// Reconstruct the damaged chunks
+			let mut repaired = group.shards.clone();
+			let parity: Vec<(usize, &[u8])> = selection.iter().map(|index| (*index, group.parity_blocks[*index])).collect();
+			try_err!(reed_solomon.reconstruct(&mut repaired, damaged, &parity));
+			
+			// Authenticate the reconstructed chunks
+			let mut is_valid = true;
+			for &index in damaged.iter() {
+				let is_last_chunk = index + 1 == chunk_count;
+				let length = if is_last_chunk { group.last_length } else { repaired[index].len() };
+				let mut chunk_buffer = repaired[index].clone();
+				let counter = self.position.chunk_index + index as u64;
+				if open_chunk(stream_instance, base_key, counter, group.is_last && is_last_chunk, &mut chunk_buffer, length).is_err() { is_valid = false }
+			}
+			if is_valid { return Ok(Some(repaired)) }
+			
+			// Select the next combination
+			let mut position = selection.len();
+			while position > 0 && selection[position - 1] == group.parity_blocks.len() - selection.len() + position - 1 { position -= 1 }
+			if position == 0 { break }
+			selection[position - 1] += 1;
+			for i in position .. selection.len() { selection[i] = selection[i - 1] + 1 }
+		}
+		Ok(None)
+	}
+	
+	/// Opens a chunk in salvage-mode and returns the length of the data to write
+	///
+	/// If the last chunk can only be opened as an ordinary chunk, the stream has been truncated on a
+	/// chunk-boundary; if it is shorter than a full chunk and cannot be opened at all, the stream may
+	/// have been truncated within the chunk.
+	fn salvage_chunk(&mut self, stream_instance: &crypto::StreamInstance, base_key: &crypto::Key, is_last: bool, chunk_buffer: &mut[u8], chunk_length: usize) -> Result<usize, Error<CpError>> {
+		let backup = if is_last { Some(chunk_buffer[.. chunk_length].to_vec()) } else { None };
+		
+		// Try to open the chunk (and to open the last chunk as ordinary chunk if it fails)
+		let error = match open_stream_chunk(stream_instance, base_key, self.position.chunk_index, is_last, chunk_buffer, chunk_length) {
+			Ok(data_length) => return Ok(data_length),
+			Err(error) => error
+		};
+		if let Some(backup) = backup {
+			chunk_buffer[.. chunk_length].copy_from_slice(&backup);
+			if let Ok(data_length) = open_stream_chunk(stream_instance, base_key, self.position.chunk_index, false, chunk_buffer, chunk_length) {
+				self.report.truncated = true;
+				return Ok(data_length)
+			}
+		}
+		
+		if is_last && chunk_length < CHUNK_DATA_SIZE + chunk_overhead(stream_instance) { self.report.truncated = true }
+		let plaintext_length = chunk_length.saturating_sub(chunk_overhead(stream_instance));
+		self.skip_damaged(error, chunk_buffer, plaintext_length)
+	}
+	
+	/// Handles a chunk that failed to open with `error` and returns the length of the data to write
+	///
+	/// Outside of salvage-mode (or if `error` is not an integrity-error), `error` is returned;
+	/// otherwise the chunk is recorded and replaced by `plaintext_length` zeros or by nothing.
+	fn skip_damaged(&mut self, error: Error<CpError>, chunk_buffer: &mut[u8], plaintext_length: usize) -> Result<usize, Error<CpError>> {
+		if self.salvage == Salvage::Off || error.kind != CpError::InvalidData { return Err(error) }
+		
+		// Record the damaged chunk and replace its plaintext
+		self.report.damaged.push(DamagedChunk {
+			chunk_index: self.position.chunk_index,
+			ciphertext_offset: self.position.ciphertext_offset,
+			plaintext_offset: self.position.chunk_index * CHUNK_DATA_SIZE as u64,
+			plaintext_length: plaintext_length as u64
+		});
+		match self.salvage {
+			Salvage::Zeros => {
+				for byte in chunk_buffer[.. plaintext_length].iter_mut() { *byte = 0x00 }
+				Ok(plaintext_length)
+			},
+			_ => Ok(0)
+		}
+	}
 }
\ No newline at end of file
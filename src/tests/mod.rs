@@ -2,8 +2,52 @@ mod memory_io;
 mod test_predefined_stream;
 mod test_random_streams;
 mod test_invalid_streams;
+mod test_adapters;
+mod test_selftest;
+mod test_password;
+mod test_parity;
+mod test_compression;
+mod test_padding;
+mod test_metadata;
+mod test_archive;
+mod test_armor;
+mod test_volume;
+mod test_append;
+mod test_seekable;
+mod test_resume;
+mod test_progress;
+#[cfg(feature = "async")] mod test_async_adapters;
+
+use super::{ crypto, io, stream, CpError, SealBuilder, OpenBuilder };
+
+/// The plaintext-length of the builder-tests (three full chunks and a partial last chunk)
+const PLAINTEXT_LENGTH: usize = 3 * stream::CHUNK_DATA_SIZE + 4711;
 
 fn estimate_sealed_size(plain_size: usize, overhead: usize) -> usize {
 	let block_count = (plain_size / super::stream::CHUNK_DATA_SIZE) + 1;
 	256 + (super::stream::CHUNK_DATA_SIZE * block_count) + (overhead * block_count)
+}
+
+/// Creates `length` bytes of random plaintext
+fn random_plaintext(length: usize) -> Vec<u8> {
+	let mut plaintext = vec![0u8; length];
+	crypto::random(&mut plaintext);
+	plaintext
+}
+
+/// Seals `plaintext` with `password` and cheap PBKDF-parameters (and the options set by `configure`)
+fn seal_with<F: FnOnce(SealBuilder) -> SealBuilder>(password: &str, plaintext: &[u8], configure: F) -> Vec<u8> {
+	let mut io = io::StreamIo::new(plaintext, Vec::new());
+	configure(SealBuilder::new(password).argon2i(4, 512, 5)).seal(&mut io).unwrap();
+	io.into_inner().1
+}
+
+/// Opens `sealed` with `password` (and the options set by `configure`) and returns the plaintext or
+/// the error-kind
+fn open_with<F: FnOnce(OpenBuilder) -> OpenBuilder>(password: &str, sealed: &[u8], configure: F) -> Result<Vec<u8>, CpError> {
+	let mut io = io::StreamIo::new(sealed, Vec::new());
+	match configure(OpenBuilder::new(password)).open(&mut io) {
+		Ok(()) => Ok(io.into_inner().1),
+		Err(error) => Err(error.kind)
+	}
 }
\ No newline at end of file
@@ -0,0 +1,90 @@
+use std::io::{ Read, Write };
+use super::super::crypto;
+use super::super::{ stream, padding };
+use super::super::adapters::{ SealingWriter, OpeningReader, OpeningWriter };
+use super::memory_io::MemoryIo;
+
+const ADAPTER_PASSWORD: &str = "Adapter password";
+const PBKDF: (&'static[u8], u32, u32, u32) = (b"This is an 32-byte nonce-text :P", 4, 8, 1);
+
+fn stream_instance() -> crypto::StreamInstance {
+	crypto::StreamInstance::new(
+		crypto::pbkdf::Argon2i::with_nonce(PBKDF.0.to_vec(), PBKDF.1, PBKDF.2, PBKDF.3),
+		crypto::kdf::HmacSha2512::new(),
+		crypto::auth_enc::ChaCha20Poly1305::new()
+	)
+}
+
+
+
+struct Test {
+	random_size: usize,
+	write_size: usize
+}
+impl Test {
+	pub fn test(&self) {
+		// Create random plain-text-stream
+		let mut random_plain = vec![0u8; self.random_size];
+		crypto::random(&mut random_plain);
+		
+		// Seal the data using the writer with `write_size`d writes
+		let sealed = {
+			let mut writer = SealingWriter::new(ADAPTER_PASSWORD.to_string(), Vec::new(), stream_instance()).unwrap();
+			for chunk in random_plain.chunks(self.write_size) { writer.write_all(chunk).unwrap() }
+			writer.finish().unwrap()
+		};
+		
+		// The writer must produce exactly the same stream as the `Encryptor`
+		let encrypted = {
+			let mut io = MemoryIo::new(
+				random_plain.clone(),
+				super::estimate_sealed_size(random_plain.len(), 16)
+			);
+			stream::Encryptor::new(ADAPTER_PASSWORD.to_string(), &mut io, stream_instance()).unwrap().runloop().unwrap();
+			io.stdout()
+		};
+		assert_eq!(sealed, encrypted);
+		
+		// Open the data using the reader
+		let mut opened = Vec::new();
+		OpeningReader::new(ADAPTER_PASSWORD.to_string(), sealed.as_slice()).unwrap().read_to_end(&mut opened).unwrap();
+		assert_eq!(random_plain, opened);
+		
+		// Open the data using the push-based writer with `write_size`d writes
+		let opened = {
+			let mut writer = OpeningWriter::new(ADAPTER_PASSWORD.to_string(), Vec::new());
+			for chunk in sealed.chunks(self.write_size) { writer.write_all(chunk).unwrap() }
+			writer.finish().unwrap()
+		};
+		assert_eq!(random_plain, opened);
+		
+		// A truncated stream must not be opened
+		if sealed.len() > stream::CHUNK_DATA_SIZE {
+			let mut reader = OpeningReader::new(ADAPTER_PASSWORD.to_string(), &sealed[.. sealed.len() - 17]).unwrap();
+			assert!(reader.read_to_end(&mut Vec::new()).is_err())
+		}
+	}
+}
+
+#[test]
+fn batch() {
+	let tests = [
+		Test{ random_size: 0, write_size: 1 },
+		Test{ random_size: 7789, write_size: 17 },
+		Test{ random_size: 1 * 1024 * 1024, write_size: 1 * 1024 * 1024 },
+		Test{ random_size: 2 * 1024 * 1024, write_size: 65537 },
+		Test{ random_size: 8396411, write_size: 3 * 1024 * 1024 }
+	];
+	for test in tests.iter() { test.test() }
+}
+
+#[test]
+fn sealing_writer_rejects_extensions() {
+	// The writer seals plain chunks only, so it must not write a header that announces padding
+	let mut stream_instance = stream_instance();
+	stream_instance.padding = Some(padding::Padding::Padme);
+	
+	let mut written = Vec::new();
+	assert!(SealingWriter::new(ADAPTER_PASSWORD.to_string(), &mut written, stream_instance).is_err());
+	assert!(written.is_empty())
+}
\ No newline at end of file
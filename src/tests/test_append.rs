@@ -0,0 +1,136 @@
+use std;
+use std::io::Read;
+use super::super::CpError;
+use super::super::{ crypto, io, stream, padding, append, SealBuilder, OpenBuilder };
+
+const APPEND_PASSWORD: &str = "Append password";
+
+
+/// Seals `plaintext` as appendable stream
+fn seal(plaintext: &[u8]) -> Vec<u8> {
+	let mut io = io::StreamIo::new(plaintext, Vec::new());
+	SealBuilder::new(APPEND_PASSWORD).argon2i(4, 512, 5).appendable(true).seal(&mut io).unwrap();
+	io.into_inner().1
+}
+
+/// Opens `sealed` and returns the plaintext
+fn open(sealed: &[u8]) -> Result<Vec<u8>, CpError> {
+	let mut io = io::StreamIo::new(sealed, Vec::new());
+	match OpenBuilder::new(APPEND_PASSWORD).open(&mut io) {
+		Ok(()) => Ok(io.into_inner().1),
+		Err(error) => Err(error.kind)
+	}
+}
+
+/// Writes `sealed` to a temporary file for the test `name`, appends `data` with `password` and
+/// returns the result and the contents of the file
+fn try_append<R: Read>(name: &str, sealed: &[u8], password: &str, data: R) -> (Result<(), CpError>, Vec<u8>) {
+	let path = std::env::temp_dir().join(format!("crypto_pipe_test_append_{}_{}", name, std::process::id()));
+	std::fs::write(&path, sealed).unwrap();
+	let result = {
+		let mut file = std::fs::OpenOptions::new().read(true).write(true).open(&path).unwrap();
+		append::append(password, &mut file, data).map_err(|error| error.kind)
+	};
+	
+	let appended = std::fs::read(&path).unwrap();
+	std::fs::remove_file(&path).unwrap();
+	(result, appended)
+}
+
+/// Appends `data` to `sealed`
+fn append_data(name: &str, sealed: Vec<u8>, data: &[u8]) -> Vec<u8> {
+	let (result, appended) = try_append(name, &sealed, APPEND_PASSWORD, data);
+	result.unwrap();
+	appended
+}
+
+/// A reader that always fails
+struct FailingReader;
+impl Read for FailingReader {
+	fn read(&mut self, _: &mut[u8]) -> std::io::Result<usize> {
+		Err(std::io::ErrorKind::BrokenPipe.into())
+	}
+}
+
+/// Returns the session-ID of the last chunk
+fn last_session_id(sealed: &[u8]) -> &[u8] {
+	&sealed[sealed.len() - stream::SESSION_ID_SIZE ..]
+}
+
+
+#[test]
+fn roundtrip() {
+	let mut data = vec![0u8; (stream::CHUNK_DATA_SIZE * 2) + 4711];
+	crypto::random(&mut data);
+	
+	// Start with an empty stream, a partial chunk and a full chunk
+	for initial in [0, 4711, stream::CHUNK_DATA_SIZE].iter() {
+		let mut sealed = seal(&data[.. *initial]);
+		let mut expected = data[.. *initial].to_vec();
+		for length in [1, stream::CHUNK_DATA_SIZE + 13, 4711].iter() {
+			sealed = append_data("roundtrip", sealed, &data[.. *length]);
+			expected.extend_from_slice(&data[.. *length]);
+			assert_eq!(open(&sealed).unwrap(), expected);
+		}
+	}
+}
+
+#[test]
+fn new_session_per_append() {
+	let sealed = seal(b"Testolope");
+	
+	// Appending different data to the same state uses different chunk-keys
+	let (appended_0, appended_1) = (append_data("new_session_per_append", sealed.clone(), b"0"), append_data("new_session_per_append", sealed.clone(), b"1"));
+	assert_eq!(appended_0.len(), appended_1.len());
+	assert_ne!(last_session_id(&appended_0), last_session_id(&sealed));
+	assert_ne!(last_session_id(&appended_0), last_session_id(&appended_1));
+	assert_eq!(open(&appended_0).unwrap(), b"Testolope0");
+	assert_eq!(open(&appended_1).unwrap(), b"Testolope1");
+}
+
+#[test]
+fn invalid_streams() {
+	let sealed = append_data("invalid_streams", seal(&vec![0x17u8; stream::CHUNK_DATA_SIZE + 4711]), b"Testolope");
+	
+	// A stream without its last chunk cannot be appended to or opened
+	let truncated = sealed[.. sealed.len() - (4711 + 9 + 16 + stream::SESSION_ID_SIZE)].to_vec();
+	let (result, appended) = try_append("invalid_streams", &truncated, APPEND_PASSWORD, &b"Testolope"[..]);
+	assert_eq!(result.unwrap_err(), CpError::InvalidData);
+	assert_eq!(appended, truncated);
+	assert_eq!(open(&truncated).unwrap_err(), CpError::InvalidData);
+	
+	// A wrong password is detected before the stream is modified
+	let (result, appended) = try_append("invalid_streams", &sealed, "Wrong password", &b"Testolope"[..]);
+	assert_eq!(result.unwrap_err(), CpError::WrongPassword);
+	assert_eq!(appended, sealed);
+	
+	// A stream that is not appendable
+	let mut io = io::StreamIo::new(&b"Testolope"[..], Vec::new());
+	SealBuilder::new(APPEND_PASSWORD).argon2i(4, 512, 5).seal(&mut io).unwrap();
+	let (result, _) = try_append("invalid_streams", &io.into_inner().1, APPEND_PASSWORD, &b"Testolope"[..]);
+	assert_eq!(result.unwrap_err(), CpError::Unsupported);
+}
+
+#[test]
+fn interrupted_input() {
+	let sealed = seal(&vec![0x17u8; 4711]);
+	
+	// Appending nothing is rejected before the stream is modified
+	let (result, appended) = try_append("interrupted_input", &sealed, APPEND_PASSWORD, &b""[..]);
+	assert_eq!(result.unwrap_err(), CpError::InvalidParameter);
+	assert_eq!(appended, sealed);
+	
+	// If the input fails after some chunks have been written, the original last chunk is restored
+	let data = vec![0x42u8; 2 * stream::CHUNK_DATA_SIZE + 13];
+	let (result, appended) = try_append("interrupted_input", &sealed, APPEND_PASSWORD, data.as_slice().chain(FailingReader));
+	assert!(result.is_err());
+	assert_eq!(appended, sealed);
+	assert_eq!(open(&appended).unwrap(), vec![0x17u8; 4711]);
+}
+
+#[test]
+fn invalid_options() {
+	let mut io = io::StreamIo::new(&b"Testolope"[..], Vec::new());
+	let error = SealBuilder::new(APPEND_PASSWORD).appendable(true).padding(padding::Padding::Padme).seal(&mut io).unwrap_err();
+	assert_eq!(error.kind, CpError::InvalidParameter);
+}
\ No newline at end of file
@@ -0,0 +1,121 @@
+use std;
+use std::io::Cursor;
+use super::super::CpError;
+use super::super::{ crypto, stream, metadata, archive };
+use super::memory_io::MemoryIo;
+
+const ARCHIVE_PASSWORD: &str = "Archive password";
+
+
+/// Creates a stream-instance with cheap PBKDF-parameters
+fn stream_instance() -> crypto::StreamInstance {
+	crypto::StreamInstance::new(
+		crypto::pbkdf::Argon2i::new(4, 512, 5),
+		crypto::kdf::HmacSha2512::new(),
+		crypto::auth_enc::ChaCha20Poly1305::new()
+	)
+}
+
+/// Creates some entries (one of them spans multiple chunks)
+fn entries() -> Vec<(&'static str, Vec<u8>)> {
+	let mut large = vec![0u8; stream::CHUNK_DATA_SIZE + 4711];
+	crypto::random(&mut large);
+	vec![("README.txt", b"Testolope".to_vec()), ("data/large.bin", large), ("data/empty", Vec::new())]
+}
+
+/// Seals `entries` as archive
+fn seal(entries: &[(&'static str, Vec<u8>)]) -> Vec<u8> {
+	let mut archive_writer = archive::ArchiveWriter::new(ARCHIVE_PASSWORD, Vec::new(), stream_instance()).unwrap();
+	for &(path, ref data) in entries.iter() {
+		let permissions = if cfg!(unix) { Some(0o4750) } else { None };
+		let metadata = metadata::Metadata{ size: Some(data.len() as u64), modified: Some(1_531_000_000), permissions, ..metadata::Metadata::default() };
+		archive_writer.add(path, data.as_slice(), metadata).unwrap();
+	}
+	archive_writer.finish().unwrap()
+}
+
+
+#[test]
+fn roundtrip() {
+	let entries = entries();
+	let mut archive_reader = archive::ArchiveReader::open(ARCHIVE_PASSWORD, Cursor::new(seal(&entries))).unwrap();
+	
+	// Validate the index and extract every entry
+	let paths: Vec<String> = archive_reader.entries().iter().map(|entry| entry.path.clone()).collect();
+	assert_eq!(paths, vec!["README.txt", "data/large.bin", "data/empty"]);
+	for (index, &(_, ref data)) in entries.iter().enumerate() {
+		assert_eq!(archive_reader.entries()[index].metadata.size, Some(data.len() as u64));
+		let mut extracted = Vec::new();
+		archive_reader.extract(index, &mut extracted).unwrap();
+		assert_eq!(&extracted, data);
+	}
+}
+
+#[test]
+fn extract_single() {
+	let entries = entries();
+	let mut archive = seal(&entries);
+	
+	// Damage the first entry; the other entries can still be extracted
+	let offset = archive::ArchiveReader::open(ARCHIVE_PASSWORD, Cursor::new(archive.clone())).unwrap().entries()[0].offset as usize;
+	archive[offset + 4] ^= 0x01;
+	let mut archive_reader = archive::ArchiveReader::open(ARCHIVE_PASSWORD, Cursor::new(archive)).unwrap();
+	
+	let mut extracted = Vec::new();
+	archive_reader.extract(1, &mut extracted).unwrap();
+	assert_eq!(extracted, entries[1].1);
+	assert_eq!(archive_reader.extract(0, &mut Vec::new()).unwrap_err().kind, CpError::InvalidData);
+}
+
+#[test]
+fn extract_to() {
+	let dir = std::env::temp_dir().join(format!("crypto_pipe_test_archive_{}", std::process::id()));
+	let mut archive_reader = archive::ArchiveReader::open(ARCHIVE_PASSWORD, Cursor::new(seal(&entries()))).unwrap();
+	
+	// Extract the directory "data" only
+	let extracted = archive_reader.extract_to(&dir, "data").unwrap();
+	assert_eq!(extracted, vec!["data/large.bin", "data/empty"]);
+	assert!(!dir.join("README.txt").exists());
+	assert_eq!(std::fs::metadata(dir.join("data").join("large.bin")).unwrap().len(), stream::CHUNK_DATA_SIZE as u64 + 4711);
+	assert_eq!(metadata::Metadata::from_file(dir.join("data").join("empty")).unwrap().modified, Some(1_531_000_000));
+	#[cfg(unix)] assert_eq!(metadata::Metadata::from_file(dir.join("data").join("empty")).unwrap().permissions, Some(0o750));
+	
+	assert_eq!(archive_reader.extract_to(&dir, "dat").unwrap_err().kind, CpError::InvalidParameter);
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn invalid_paths() {
+	for path in ["", "/etc/passwd", "../escape", "data/../../escape", "data//file", "./file", "data\\..\\escape"].iter() {
+		assert_eq!(archive::validate_path(path).unwrap_err().kind, CpError::InvalidParameter);
+	}
+	archive::validate_path("data/.hidden/file").unwrap();
+	
+	// Invalid and duplicate paths are rejected by the writer
+	let mut archive_writer = archive::ArchiveWriter::new(ARCHIVE_PASSWORD, Vec::new(), stream_instance()).unwrap();
+	assert_eq!(archive_writer.add("../escape", &b"Testolope"[..], metadata::Metadata::default()).unwrap_err().kind, CpError::InvalidParameter);
+	archive_writer.add("file", &b"Testolope"[..], metadata::Metadata::default()).unwrap();
+	assert_eq!(archive_writer.add("file", &b"Testolope"[..], metadata::Metadata::default()).unwrap_err().kind, CpError::InvalidParameter);
+}
+
+#[test]
+fn tampered_index() {
+	let mut archive = seal(&entries());
+	
+	// The sealed index directly precedes the trailer
+	let index_end = archive.len() - archive::TRAILER_SIZE;
+	archive[index_end - 1] ^= 0x01;
+	assert_eq!(archive::ArchiveReader::open(ARCHIVE_PASSWORD, Cursor::new(archive.clone())).err().unwrap().kind, CpError::InvalidData);
+	
+	// A truncated archive has no valid trailer
+	archive.truncate(index_end + 4);
+	assert_eq!(archive::ArchiveReader::open(ARCHIVE_PASSWORD, Cursor::new(archive)).err().unwrap().kind, CpError::InvalidData);
+}
+
+#[test]
+fn open_rejects_archive() {
+	let archive = seal(&entries()[.. 1]);
+	let mut io = MemoryIo::new(archive.clone(), archive.len());
+	let error = stream::Decryptor::new(ARCHIVE_PASSWORD, &mut io).unwrap().runloop().unwrap_err();
+	assert_eq!(error.kind, CpError::Unsupported);
+}
\ No newline at end of file
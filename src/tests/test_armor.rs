@@ -0,0 +1,108 @@
+use std;
+use std::io::{ Read, Write };
+use super::super::CpError;
+use super::super::{ crypto, io, stream, armor };
+
+const ARMOR_PASSWORD: &str = "Armor password";
+
+
+/// Armors `data` with `line_length`
+fn armor(data: &[u8], line_length: usize) -> Vec<u8> {
+	let mut writer = armor::ArmoredWriter::new(Vec::new(), line_length).unwrap();
+	// Write in odd pieces to cover the pending quantum
+	for piece in data.chunks(7) { writer.write_all(piece).unwrap() }
+	writer.finish().unwrap()
+}
+
+/// Reads `data` through a `DearmoringReader`
+fn dearmor(data: &[u8]) -> Result<Vec<u8>, std::io::Error> {
+	let mut dearmored = Vec::new();
+	armor::DearmoringReader::new(data).read_to_end(&mut dearmored)?;
+	Ok(dearmored)
+}
+
+
+#[test]
+fn roundtrip() {
+	let mut data = vec![0u8; 4711];
+	crypto::random(&mut data);
+	for length in 0..16 {
+		for line_length in [1, 4, 63, 64, 76].iter() {
+			let armored = armor(&data[.. length * 293], *line_length);
+			assert!(armored.starts_with(format!("{}\n", armor::BEGIN_MARKER).as_bytes()));
+			assert!(armored.ends_with(format!("{}\n", armor::END_MARKER).as_bytes()));
+			assert!(armored.split(|byte| *byte == b'\n').all(|line| line.len() <= *line_length || line.starts_with(b"-----")));
+			assert_eq!(dearmor(&armored).unwrap(), &data[.. length * 293]);
+		}
+	}
+}
+
+#[test]
+fn known_answer() {
+	let armored = armor(b"Testolope!", 8);
+	let expected = format!("{}\nVGVzdG9s\nb3BlIQ==\n{}\n", armor::BEGIN_MARKER, armor::END_MARKER);
+	assert_eq!(String::from_utf8(armored).unwrap(), expected);
+}
+
+#[test]
+fn whitespace() {
+	// CRLF-line-endings, indentation and blank lines are ignored
+	let armored = format!("\r\n  {}\r\n VGVz dG9s\r\n\r\n\tb3BlIQ==  \r\n{} \r\n", armor::BEGIN_MARKER, armor::END_MARKER);
+	assert_eq!(dearmor(armored.as_bytes()).unwrap(), b"Testolope!");
+}
+
+#[test]
+fn binary_passthrough() {
+	let mut data = vec![0u8; 100_000];
+	crypto::random(&mut data);
+	data[0] = 0x30;
+	assert_eq!(dearmor(&data).unwrap(), data);
+	assert_eq!(dearmor(b"-----").unwrap(), b"-----");
+	assert_eq!(dearmor(b"").unwrap(), b"");
+}
+
+#[test]
+fn invalid_armor() {
+	let invalid = [
+		format!("{}\nVGVzdG9s\n", armor::BEGIN_MARKER),
+		format!("{}\nVGVz*G9s\n{}\n", armor::BEGIN_MARKER, armor::END_MARKER),
+		format!("{}\nb3BlIQ==VGVz\n{}\n", armor::BEGIN_MARKER, armor::END_MARKER),
+		format!("-----BEGIN SOMETHING ELSE-----\nVGVzdG9s\n{}\n", armor::END_MARKER),
+		format!("{}\nVGVzdG9s\n-----END SOMETHING ELSE-----\n", armor::BEGIN_MARKER)
+	];
+	for armored in invalid.iter() {
+		assert_eq!(dearmor(armored.as_bytes()).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+	}
+}
+
+#[test]
+fn sealed_stream() {
+	let mut plaintext = vec![0u8; stream::CHUNK_DATA_SIZE + 4711];
+	crypto::random(&mut plaintext);
+	let stream_instance = crypto::StreamInstance::new(
+		crypto::pbkdf::Argon2i::new(4, 512, 5),
+		crypto::kdf::HmacSha2512::new(),
+		crypto::auth_enc::ChaCha20Poly1305::new()
+	);
+	
+	// Seal and armor the plaintext
+	let mut sealing_io = io::StreamIo::new(plaintext.as_slice(), armor::ArmoredWriter::new(Vec::new(), armor::DEFAULT_LINE_LENGTH).unwrap());
+	stream::Encryptor::new(ARMOR_PASSWORD, &mut sealing_io, stream_instance).unwrap().runloop().unwrap();
+	let armored = sealing_io.into_inner().1.finish().unwrap();
+	assert!(armored.iter().all(|byte| byte.is_ascii_graphic() || *byte == b' ' || *byte == b'\n'));
+	
+	// Open the armored stream with CRLF-line-endings
+	let armored = String::from_utf8(armored).unwrap().replace("\n", "\r\n");
+	let mut opening_io = io::StreamIo::new(armor::DearmoringReader::new(armored.as_bytes()), Vec::new());
+	stream::Decryptor::new(ARMOR_PASSWORD, &mut opening_io).unwrap().runloop().unwrap();
+	assert_eq!(opening_io.into_inner().1, plaintext);
+	
+	// A truncated armored stream is rejected
+	let truncated = &armored[.. armored.len() / 2];
+	let mut opening_io = io::StreamIo::new(armor::DearmoringReader::new(truncated.as_bytes()), Vec::new());
+	let error = stream::Decryptor::new(ARMOR_PASSWORD, &mut opening_io).unwrap().runloop().unwrap_err();
+	match error.kind {
+		CpError::IOError(kind, _) => assert_eq!(kind, std::io::ErrorKind::InvalidData),
+		kind => panic!("Unexpected error-kind {:?}", kind)
+	}
+}
\ No newline at end of file
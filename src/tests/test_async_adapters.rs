@@ -0,0 +1,91 @@
+use std;
+use tokio;
+use tokio::io::{ AsyncReadExt, AsyncWriteExt };
+use super::super::{ crypto, padding };
+use super::super::async_adapters::{ AsyncSealingWriter, AsyncOpeningReader };
+
+const ASYNC_PASSWORD: &str = "Async password";
+const PIPE_CAPACITY: usize = 64 * 1024;
+
+fn stream_instance() -> crypto::StreamInstance {
+	crypto::StreamInstance::new(
+		crypto::pbkdf::Argon2i::new(4, 8, 1),
+		crypto::kdf::HmacSha2512::new(),
+		crypto::auth_enc::ChaCha20Poly1305::new()
+	)
+}
+
+
+
+struct Test {
+	random_size: usize,
+	modificator: fn(&mut Vec<u8>),
+	is_valid: bool
+}
+impl Test {
+	pub fn test(&self, runtime: &tokio::runtime::Runtime) {
+		// Create random plain-text-stream
+		let mut random_plain = vec![0u8; self.random_size];
+		crypto::random(&mut random_plain);
+		
+		// Seal the data into a duplex-pipe on another thread and collect the sealed stream
+		let mut sealed = {
+			let (sealing_end, mut collecting_end) = tokio::io::duplex(PIPE_CAPACITY);
+			let (handle, to_seal) = (runtime.handle().clone(), random_plain.clone());
+			let sealer = std::thread::spawn(move || {
+				let mut writer = AsyncSealingWriter::new(ASYNC_PASSWORD.to_string(), sealing_end, stream_instance()).unwrap();
+				handle.block_on(writer.write_all(&to_seal)).unwrap();
+				handle.block_on(writer.shutdown()).unwrap();
+			});
+			
+			let mut sealed = Vec::new();
+			runtime.block_on(collecting_end.read_to_end(&mut sealed)).unwrap();
+			sealer.join().unwrap();
+			sealed
+		};
+		(self.modificator)(&mut sealed);
+		
+		// Feed the sealed stream into another duplex-pipe and open it
+		let (mut feeding_end, opening_end) = tokio::io::duplex(PIPE_CAPACITY);
+		let handle = runtime.handle().clone();
+		let feeder = std::thread::spawn(move || {
+			// The opening-end may be dropped early if the stream is invalid
+			let _ = handle.block_on(feeding_end.write_all(&sealed));
+			let _ = handle.block_on(feeding_end.shutdown());
+		});
+		
+		let mut opened = Vec::new();
+		let result = runtime.block_on(AsyncOpeningReader::new(ASYNC_PASSWORD.to_string(), opening_end).read_to_end(&mut opened));
+		if self.is_valid {
+			feeder.join().unwrap();
+			result.unwrap();
+			assert_eq!(random_plain, opened)
+		} else {
+			assert!(result.is_err())
+		}
+	}
+}
+
+#[test]
+fn batch() {
+	let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build().unwrap();
+	let tests = [
+		Test{ random_size: 0, modificator: |_: &mut Vec<u8>| {}, is_valid: true },
+		Test{ random_size: 7789, modificator: |_: &mut Vec<u8>| {}, is_valid: true },
+		Test{ random_size: 1 * 1024 * 1024, modificator: |_: &mut Vec<u8>| {}, is_valid: true },
+		Test{ random_size: 3 * 1024 * 1024 + 4099, modificator: |_: &mut Vec<u8>| {}, is_valid: true },
+		// Damage MAC
+		Test{ random_size: 2 * 1024 * 1024, modificator: |x: &mut Vec<u8>| { let len = x.len(); x[len - 1] ^= 0x40 }, is_valid: false },
+		// Truncate last chunk
+		Test{ random_size: 2 * 1024 * 1024, modificator: |x: &mut Vec<u8>| { let len = x.len(); x.truncate(len - 17) }, is_valid: false }
+	];
+	for test in tests.iter() { test.test(&runtime) }
+}
+
+#[test]
+fn sealing_writer_rejects_extensions() {
+	// The writer seals plain chunks only, so it must not accept a stream-instance with padding
+	let mut stream_instance = stream_instance();
+	stream_instance.padding = Some(padding::Padding::Padme);
+	assert!(AsyncSealingWriter::new(ASYNC_PASSWORD.to_string(), std::io::Cursor::new(Vec::new()), stream_instance).is_err())
+}
\ No newline at end of file
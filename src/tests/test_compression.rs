@@ -0,0 +1,118 @@
+use super::super::CpError;
+use super::super::{ crypto, io, stream, compression, OpenBuilder };
+use super::memory_io::MemoryIo;
+
+const COMPRESSION_STREAM_PASSWORD: &str = "Compression password";
+/// The length of the plaintext (three full compression-blocks and a partial last block)
+const PLAINTEXT_LENGTH: usize = 3 * compression::COMPRESSION_BLOCK_SIZE + 4711;
+
+
+/// Creates a compressible plaintext (random words from a small dictionary separated by spaces)
+fn plaintext() -> Vec<u8> {
+	let (mut dictionary, mut choices) = (vec![0u8; 64 * 8], vec![0u8; PLAINTEXT_LENGTH / 9 + 1]);
+	crypto::random(&mut dictionary);
+	crypto::random(&mut choices);
+	
+	(0..PLAINTEXT_LENGTH).map(|i| match i % 9 {
+		8 => b' ',
+		letter => b'a' + dictionary[(choices[i / 9] % 64) as usize * 8 + letter] % 26
+	}).collect()
+}
+
+/// Seals `plaintext` with `compression`
+fn seal(plaintext: &[u8], compression: compression::Compression) -> Vec<u8> {
+	let mut stream_instance = crypto::StreamInstance::new(
+		crypto::pbkdf::Argon2i::new(4, 512, 5),
+		crypto::kdf::HmacSha2512::new(),
+		crypto::auth_enc::ChaCha20Poly1305::new()
+	);
+	stream_instance.compression = Some(compression);
+	
+	let mut io = MemoryIo::new(plaintext.to_vec(), super::estimate_sealed_size(2 * plaintext.len(), 16));
+	stream::Encryptor::new(COMPRESSION_STREAM_PASSWORD, &mut io, stream_instance).unwrap().runloop().unwrap();
+	io.stdout()
+}
+
+/// Opens `sealed` with `max_ratio` and returns the plaintext
+fn open(sealed: &[u8], max_ratio: u64) -> Result<Vec<u8>, CpError> {
+	let mut io = MemoryIo::new(sealed.to_vec(), 2 * PLAINTEXT_LENGTH);
+	{
+		let mut decryptor = stream::Decryptor::new(COMPRESSION_STREAM_PASSWORD, &mut io).map_err(|e| e.kind)?;
+		decryptor.set_max_compression_ratio(max_ratio);
+		decryptor.runloop().map_err(|e| e.kind)?;
+	}
+	Ok(io.stdout())
+}
+
+
+#[test]
+fn header() {
+	let compression = compression::Compression::new(compression::Algorithm::Zstd, 19).unwrap();
+	assert_eq!(compression::Compression::from_serialized(compression.serialize()).unwrap(), compression);
+	
+	assert_eq!(compression::Compression::new(compression::Algorithm::Zstd, 0).unwrap_err().kind, CpError::InvalidParameter);
+	assert_eq!(compression::Compression::new(compression::Algorithm::Lz4, 1).unwrap_err().kind, CpError::InvalidParameter);
+	assert_eq!(compression::Algorithm::from_name("brotli").unwrap_err().kind, CpError::Unsupported);
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn zstd() {
+	let plaintext = plaintext();
+	let sealed = seal(&plaintext, compression::Compression::new(compression::Algorithm::Zstd, compression::DEFAULT_ZSTD_LEVEL).unwrap());
+	assert!(sealed.len() < plaintext.len() / 2);
+	assert_eq!(open(&sealed, compression::DEFAULT_MAX_COMPRESSION_RATIO).unwrap(), plaintext);
+}
+
+#[cfg(feature = "lz4")]
+#[test]
+fn lz4() {
+	let plaintext = plaintext();
+	let sealed = seal(&plaintext, compression::Compression::new(compression::Algorithm::Lz4, 0).unwrap());
+	assert!(sealed.len() < plaintext.len());
+	assert_eq!(open(&sealed, compression::DEFAULT_MAX_COMPRESSION_RATIO).unwrap(), plaintext);
+}
+
+#[cfg(feature = "zstd")]
+#[test]
+fn ratio_limit() {
+	// Zeros compress far better than 8:1
+	let plaintext = vec![0u8; PLAINTEXT_LENGTH];
+	let sealed = seal(&plaintext, compression::Compression::new(compression::Algorithm::Zstd, 19).unwrap());
+	assert_eq!(open(&sealed, 8).unwrap_err(), CpError::InvalidData);
+	assert_eq!(open(&sealed, u64::max_value()).unwrap(), plaintext);
+}
+
+#[test]
+fn zeros_with_defaults() {
+	// 1 MiB of zeros compresses to a few bytes, which must not be mistaken for a compression-bomb
+	let plaintext = vec![0u8; PLAINTEXT_LENGTH];
+	let mut compressions = Vec::new();
+	if cfg!(feature = "zstd") { compressions.push(compression::Compression::new(compression::Algorithm::Zstd, 19).unwrap()) }
+	if cfg!(feature = "lz4") { compressions.push(compression::Compression::new(compression::Algorithm::Lz4, 0).unwrap()) }
+	for compression in compressions {
+		let sealed = seal(&plaintext, compression);
+		let mut io = io::StreamIo::new(sealed.as_slice(), Vec::new());
+		OpenBuilder::new(COMPRESSION_STREAM_PASSWORD).open(&mut io).unwrap();
+		assert_eq!(io.into_inner().1, plaintext);
+	}
+}
+
+#[test]
+fn bomb_with_defaults() {
+	// Records that claim 1 MiB each from 4 bytes must be rejected before they are decompressed
+	let mut bomb = Vec::new();
+	for _ in 0..64 {
+		bomb.extend_from_slice(&4u32.to_be_bytes());
+		bomb.extend_from_slice(&(compression::COMPRESSION_BLOCK_SIZE as u32).to_be_bytes());
+		bomb.extend_from_slice(&[0u8; 4]);
+	}
+	
+	let compression = compression::Compression::new(compression::Algorithm::Lz4, 0).unwrap();
+	let mut decompressor = compression::Decompressor::new(compression, compression::DEFAULT_MAX_COMPRESSION_RATIO, 16);
+	let mut io = MemoryIo::new(Vec::new(), 0);
+	let error = decompressor.write_chunk(&mut io, &bomb).unwrap_err();
+	assert_eq!(error.kind, CpError::InvalidData);
+	assert_eq!(error.description, format!("The decompression-ratio exceeds the limit of {}:1 (compression-bomb?)", compression::DEFAULT_MAX_COMPRESSION_RATIO));
+	assert!(io.stdout().is_empty())
+}
\ No newline at end of file
@@ -2,9 +2,13 @@ use std::io::ErrorKind;
 use super::super::CpError;
 use super::super::crypto;
 use super::super::stream;
+use super::super::{ io, compression, padding, SealBuilder, OpenBuilder };
+use super::super::asn1_der::IntoDerEncoded;
 use super::memory_io::MemoryIo;
 
 const INVALID_STREAM_PASSWORD: &str = "Invalid password";
+/// The length of the v2-stream-header with the test-parameters
+const HEADER_LENGTH: usize = 194;
 
 
 
@@ -81,7 +85,7 @@ fn batch() {
 		// Truncate chunk
 		Test {
 			random_size: 2 * 1024 * 1024,
-			modificator: &|x: &mut Vec<u8>| x.truncate(158 + 16),
+			modificator: &|x: &mut Vec<u8>| x.truncate(HEADER_LENGTH + 16),
 			error_type_description: (CpError::InvalidData, "Invalid authentication-tag".to_owned())
 		},
 		// Remove last chunk
@@ -99,7 +103,7 @@ fn batch() {
 		// Remove everything except the stream-header
 		Test {
 			random_size: 8 * 1024 * 1024,
-			modificator: &|x: &mut Vec<u8>| x.truncate(158),
+			modificator: &|x: &mut Vec<u8>| x.truncate(HEADER_LENGTH),
 			error_type_description: (CpError::InvalidData, "Invalid authentication-tag".to_owned())
 		},
 		// Truncate the stream-header
@@ -115,4 +119,150 @@ fn batch() {
 		test.argon2i_hmacsha512_chachapoly((8, 256, 4));
 		test.argon2i_hmacsha512_chachapoly((4, 512, 5));
 	}
+}
+
+#[test]
+fn error_position() {
+	// Seal three chunks
+	let random_plain = vec![0u8; 3 * stream::CHUNK_DATA_SIZE];
+	let mut encrypted = {
+		let stream_instance = crypto::StreamInstance::new(
+			crypto::pbkdf::Argon2i::new(4, 512, 5),
+			crypto::kdf::HmacSha2512::new(),
+			crypto::auth_enc::ChaCha20Poly1305::new()
+		);
+		let mut io = MemoryIo::new(
+			random_plain.clone(),
+			super::estimate_sealed_size(random_plain.len(), stream_instance.auth_enc.overhead())
+		);
+		stream::Encryptor::new(INVALID_STREAM_PASSWORD, &mut io, stream_instance).unwrap().runloop().unwrap();
+		io.stdout()
+	};
+	
+	// Damage the third chunk
+	let chunk_offset = HEADER_LENGTH + 2 * (stream::CHUNK_DATA_SIZE + 16);
+	encrypted[chunk_offset + 7] ^= 0x01;
+	
+	// Open the stream and validate the position
+	let mut io = MemoryIo::new(encrypted.clone(), encrypted.len());
+	let mut decryptor = stream::Decryptor::new(INVALID_STREAM_PASSWORD, &mut io).unwrap();
+	assert_eq!(decryptor.runloop().unwrap_err().kind, CpError::InvalidData);
+	assert_eq!(decryptor.position(), stream::Position {
+		chunk_index: 2,
+		ciphertext_offset: chunk_offset as u64,
+		plaintext_bytes: 2 * stream::CHUNK_DATA_SIZE as u64
+	});
+}
+
+#[test]
+fn salvage() {
+	// Seal four chunks with distinguishable data
+	let mut random_plain = vec![0u8; 3 * stream::CHUNK_DATA_SIZE + 4711];
+	crypto::random(&mut random_plain);
+	let mut encrypted = {
+		let stream_instance = crypto::StreamInstance::new(
+			crypto::pbkdf::Argon2i::new(4, 512, 5),
+			crypto::kdf::HmacSha2512::new(),
+			crypto::auth_enc::ChaCha20Poly1305::new()
+		);
+		let mut io = MemoryIo::new(
+			random_plain.clone(),
+			super::estimate_sealed_size(random_plain.len(), stream_instance.auth_enc.overhead())
+		);
+		stream::Encryptor::new(INVALID_STREAM_PASSWORD, &mut io, stream_instance).unwrap().runloop().unwrap();
+		io.stdout()
+	};
+	
+	// Damage the second chunk
+	let chunk_offset = HEADER_LENGTH + stream::CHUNK_DATA_SIZE + 16;
+	encrypted[chunk_offset + 4] ^= 0x01;
+	
+	// Open the stream with zero-fill
+	let mut io = MemoryIo::new(encrypted.clone(), encrypted.len());
+	let report = {
+		let mut decryptor = stream::Decryptor::new(INVALID_STREAM_PASSWORD, &mut io).unwrap();
+		decryptor.set_salvage(stream::Salvage::Zeros);
+		decryptor.runloop().unwrap();
+		decryptor.salvage_report().clone()
+	};
+	assert_eq!(report.damaged, vec![stream::DamagedChunk {
+		chunk_index: 1,
+		ciphertext_offset: chunk_offset as u64,
+		plaintext_offset: stream::CHUNK_DATA_SIZE as u64,
+		plaintext_length: stream::CHUNK_DATA_SIZE as u64
+	}]);
+	assert!(!report.truncated);
+	
+	// Validate the salvaged data
+	let mut expected = random_plain.clone();
+	for byte in expected[stream::CHUNK_DATA_SIZE .. 2 * stream::CHUNK_DATA_SIZE].iter_mut() { *byte = 0x00 }
+	assert_eq!(io.stdout(), expected);
+	
+	// Remove the last chunk and open the stream without the damaged chunk
+	let len = encrypted.len();
+	encrypted.truncate(len - (4711 + 16));
+	let mut io = MemoryIo::new(encrypted.clone(), encrypted.len());
+	let report = {
+		let mut decryptor = stream::Decryptor::new(INVALID_STREAM_PASSWORD, &mut io).unwrap();
+		decryptor.set_salvage(stream::Salvage::Skip);
+		decryptor.runloop().unwrap();
+		decryptor.salvage_report().clone()
+	};
+	assert_eq!(report.damaged.len(), 1);
+	assert!(report.truncated);
+	
+	let mut expected = random_plain[.. stream::CHUNK_DATA_SIZE].to_vec();
+	expected.extend_from_slice(&random_plain[2 * stream::CHUNK_DATA_SIZE .. 3 * stream::CHUNK_DATA_SIZE]);
+	assert_eq!(io.stdout(), expected);
+	
+	// Cut the stream within the third chunk (which cannot be opened as ordinary or as last chunk)
+	let len = encrypted.len();
+	encrypted.truncate(len - 4711);
+	let mut io = MemoryIo::new(encrypted.clone(), encrypted.len());
+	let report = {
+		let mut decryptor = stream::Decryptor::new(INVALID_STREAM_PASSWORD, &mut io).unwrap();
+		decryptor.set_salvage(stream::Salvage::Skip);
+		decryptor.runloop().unwrap();
+		decryptor.salvage_report().clone()
+	};
+	assert_eq!(report.damaged.len(), 2);
+	assert_eq!(report.damaged[1].chunk_index, 2);
+	assert_eq!(report.damaged[1].plaintext_length, (stream::CHUNK_DATA_SIZE - 4711) as u64);
+	assert!(report.truncated);
+	assert_eq!(io.stdout(), &random_plain[.. stream::CHUNK_DATA_SIZE]);
+}
+
+#[test]
+fn tampered_header() {
+	let plaintext = vec![0x2a; 4711];
+	let padding = padding::Padding::Padme;
+	let compression = compression::Compression::new(compression::Algorithm::Lz4, 0).unwrap();
+	
+	// Remove or add the padding or the compression (the key-check-value is kept)
+	let mut tamperings: Vec<(Box<Fn(SealBuilder) -> SealBuilder>, Box<Fn(&mut crypto::StreamInstance)>)> = vec![
+		(Box::new(move |builder| builder.padding(padding)), Box::new(|header| header.padding = None)),
+		(Box::new(|builder| builder), Box::new(move |header| header.padding = Some(padding))),
+		(Box::new(|builder| builder), Box::new(move |header| header.compression = Some(compression)))
+	];
+	if cfg!(feature = "lz4") {
+		tamperings.push((Box::new(move |builder| builder.compression(compression)), Box::new(|header| header.compression = None)))
+	}
+	
+	for &(ref configure, ref tamper) in tamperings.iter() {
+		let mut io = io::StreamIo::new(plaintext.as_slice(), Vec::new());
+		configure(SealBuilder::new(INVALID_STREAM_PASSWORD).argon2i(4, 512, 5)).seal(&mut io).unwrap();
+		let sealed = io.into_inner().1;
+		
+		// Replace the stream-header with the tampered header
+		let header_length = crypto::StreamInstance::try_parse_length(&sealed).unwrap().unwrap();
+		let mut header = crypto::StreamInstance::from_serialized(sealed[.. header_length].to_vec()).unwrap();
+		tamper(&mut header);
+		let mut tampered = header.as_serialized().into_der_encoded();
+		tampered.extend_from_slice(&sealed[header_length ..]);
+		
+		// The tampered header must be detected before any chunk is opened
+		let mut io = io::StreamIo::new(tampered.as_slice(), Vec::new());
+		assert_eq!(OpenBuilder::new(INVALID_STREAM_PASSWORD).open(&mut io).unwrap_err().kind, CpError::WrongPassword);
+		assert!(io.into_inner().1.is_empty());
+	}
 }
\ No newline at end of file
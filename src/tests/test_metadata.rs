@@ -0,0 +1,113 @@
+use std;
+use super::super::CpError;
+use super::super::{ crypto, stream, metadata };
+use super::memory_io::MemoryIo;
+
+const METADATA_STREAM_PASSWORD: &str = "Metadata password";
+
+
+/// Creates metadata with every field set
+fn metadata() -> metadata::Metadata {
+	metadata::Metadata {
+		filename: Some("Report 2018.pdf".to_string()),
+		size: Some(4711),
+		modified: Some(1_531_000_000),
+		permissions: Some(0o640),
+		content_type: Some("application/pdf".to_string()),
+		tags: vec![("project".to_string(), "CryptoPipe".to_string()), ("project".to_string(), "Backup".to_string())]
+	}
+}
+
+/// Seals `plaintext` with `metadata`
+fn seal(plaintext: &[u8], metadata: metadata::Metadata) -> Vec<u8> {
+	let stream_instance = crypto::StreamInstance::new(
+		crypto::pbkdf::Argon2i::new(4, 512, 5),
+		crypto::kdf::HmacSha2512::new(),
+		crypto::auth_enc::ChaCha20Poly1305::new()
+	);
+	
+	let mut io = MemoryIo::new(plaintext.to_vec(), super::estimate_sealed_size(plaintext.len() + metadata::MAX_METADATA_SIZE, 16));
+	{
+		let mut encryptor = stream::Encryptor::new(METADATA_STREAM_PASSWORD, &mut io, stream_instance).unwrap();
+		encryptor.set_metadata(metadata);
+		encryptor.runloop().unwrap();
+	}
+	io.stdout()
+}
+
+
+#[test]
+fn serialize() {
+	let metadata = metadata();
+	assert_eq!(metadata::Metadata::from_serialized(&metadata.serialize()).unwrap(), metadata);
+	assert_eq!(metadata::Metadata::from_serialized(&metadata::Metadata::default().serialize()).unwrap(), metadata::Metadata::default());
+	assert_eq!(metadata::Metadata::from_serialized(b"Not DER").unwrap_err().kind, CpError::InvalidData);
+}
+
+#[test]
+fn roundtrip() {
+	let mut plaintext = vec![0u8; stream::CHUNK_DATA_SIZE + 4711];
+	crypto::random(&mut plaintext);
+	let sealed = seal(&plaintext, metadata());
+	
+	// Open the stream
+	let mut io = MemoryIo::new(sealed.clone(), sealed.len());
+	let opened_metadata = {
+		let mut decryptor = stream::Decryptor::new(METADATA_STREAM_PASSWORD, &mut io).unwrap();
+		decryptor.runloop().unwrap();
+		decryptor.metadata().cloned()
+	};
+	assert_eq!(io.stdout(), plaintext);
+	assert_eq!(opened_metadata, Some(metadata()));
+	
+	// Read only the header and the metadata
+	let mut io = MemoryIo::new(sealed, 0);
+	let stream_instance = stream::read_header(&mut io).unwrap();
+	let base_key = stream::derive_opening_key(&stream_instance, METADATA_STREAM_PASSWORD.into()).unwrap();
+	assert_eq!(stream::read_metadata(&mut io, &stream_instance, &base_key).unwrap(), Some(metadata()));
+}
+
+#[test]
+fn damaged_metadata() {
+	let mut sealed = seal(b"Testolope", metadata());
+	
+	// The metadata-chunk directly follows the header
+	let header_length = sealed.len() - (9 + 16) - (metadata().serialize().len() + 16);
+	sealed[header_length + 4] ^= 0x01;
+	
+	let mut io = MemoryIo::new(sealed.clone(), sealed.len());
+	let error = stream::Decryptor::new(METADATA_STREAM_PASSWORD, &mut io).unwrap().runloop().unwrap_err();
+	assert_eq!(error.kind, CpError::InvalidData);
+}
+
+#[test]
+fn apply() {
+	// Create a file and apply the metadata
+	let path = std::env::temp_dir().join(format!("crypto_pipe_test_metadata_{}", std::process::id()));
+	let file = std::fs::File::create(&path).unwrap();
+	metadata().apply(&file, false).unwrap();
+	drop(file);
+	
+	// Read the metadata back
+	let restored = metadata::Metadata::from_file(&path).unwrap();
+	std::fs::remove_file(&path).unwrap();
+	assert_eq!(restored.modified, metadata().modified);
+	#[cfg(unix)] assert_eq!(restored.permissions, metadata().permissions);
+	assert_eq!(restored.size, Some(0));
+}
+
+#[test]
+#[cfg(unix)]
+fn special_bits() {
+	let path = std::env::temp_dir().join(format!("crypto_pipe_test_special_bits_{}", std::process::id()));
+	let metadata = metadata::Metadata{ permissions: Some(0o4750), ..metadata::Metadata::default() };
+	
+	// The setuid-bit is only restored on request
+	for &(special_bits, expected) in [(false, 0o750), (true, 0o4750)].iter() {
+		let file = std::fs::File::create(&path).unwrap();
+		metadata.apply(&file, special_bits).unwrap();
+		drop(file);
+		assert_eq!(metadata::Metadata::from_file(&path).unwrap().permissions, Some(expected));
+	}
+	std::fs::remove_file(&path).unwrap();
+}
\ No newline at end of file
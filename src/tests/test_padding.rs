@@ -0,0 +1,103 @@
+use super::super::CpError;
+use super::super::{ crypto, stream, padding };
+use super::memory_io::MemoryIo;
+
+const PADDING_STREAM_PASSWORD: &str = "Padding password";
+
+
+/// Seals `plaintext` with `padding`
+fn seal(plaintext: &[u8], padding: padding::Padding) -> Vec<u8> {
+	let mut stream_instance = crypto::StreamInstance::new(
+		crypto::pbkdf::Argon2i::new(4, 512, 5),
+		crypto::kdf::HmacSha2512::new(),
+		crypto::auth_enc::ChaCha20Poly1305::new()
+	);
+	stream_instance.padding = Some(padding);
+	
+	let padded_length = padding.padded_length(plaintext.len() as u64).unwrap() as usize;
+	let mut io = MemoryIo::new(plaintext.to_vec(), super::estimate_sealed_size(padded_length, 16));
+	stream::Encryptor::new(PADDING_STREAM_PASSWORD, &mut io, stream_instance).unwrap().runloop().unwrap();
+	io.stdout()
+}
+
+/// Opens `sealed` and returns the plaintext
+fn open(sealed: &[u8]) -> Vec<u8> {
+	let mut io = MemoryIo::new(sealed.to_vec(), sealed.len());
+	stream::Decryptor::new(PADDING_STREAM_PASSWORD, &mut io).unwrap().runloop().unwrap();
+	io.stdout()
+}
+
+
+#[test]
+fn padded_length() {
+	assert_eq!(padding::padme(1000), Some(1024));
+	assert_eq!(padding::padme(1025), Some(1088));
+	assert_eq!(padding::padme(1024 * 1024 + 1), Some(1024 * 1024 + 32768));
+	
+	assert_eq!(padding::Padding::Padme.padded_length(1016).unwrap(), 1024);
+	assert_eq!(padding::Padding::Bucket(4096).padded_length(0).unwrap(), 4096);
+	assert_eq!(padding::Padding::Bucket(4096).padded_length(4088).unwrap(), 4096);
+	assert_eq!(padding::Padding::Bucket(4096).padded_length(4089).unwrap(), 8192);
+	
+	// Lengths that cannot be padded without overflowing
+	assert_eq!(padding::padme(u64::max_value() - 7), None);
+	assert_eq!(padding::Padding::Padme.padded_length(u64::max_value() - 4711).unwrap_err().kind, CpError::InvalidParameter);
+	assert_eq!(padding::Padding::Bucket(4096).padded_length(u64::max_value() - 7).unwrap_err().kind, CpError::InvalidParameter);
+	assert_eq!(padding::Padding::Bucket(u64::max_value()).padded_length(1).unwrap_err().kind, CpError::InvalidParameter);
+	assert_eq!(padding::Padding::Bucket(0).padded_length(1).unwrap_err().kind, CpError::InvalidParameter);
+}
+
+#[test]
+fn spec() {
+	assert_eq!(padding::Padding::from_spec("padme").unwrap(), padding::Padding::Padme);
+	assert_eq!(padding::Padding::from_spec("bucket:4711").unwrap(), padding::Padding::Bucket(4711));
+	assert_eq!(padding::Padding::from_spec("bucket:2M").unwrap(), padding::Padding::Bucket(2 * 1024 * 1024));
+	
+	assert_eq!(padding::Padding::from_spec("bucket:0").unwrap_err().kind, CpError::InvalidParameter);
+	assert_eq!(padding::Padding::from_spec("bucket:lots").unwrap_err().kind, CpError::InvalidParameter);
+	assert_eq!(padding::Padding::from_spec("bucket:18446744073709551615").unwrap_err().kind, CpError::InvalidParameter);
+	assert_eq!(padding::Padding::from_spec("bucket:1025G").unwrap_err().kind, CpError::InvalidParameter);
+	assert_eq!(padding::Padding::from_spec("random").unwrap_err().kind, CpError::Unsupported);
+	
+	let padding = padding::Padding::Bucket(65536);
+	assert_eq!(padding::Padding::from_serialized(padding.serialize()).unwrap(), padding);
+}
+
+#[test]
+fn size_classes() {
+	// Different plaintext-lengths in the same bucket result in the same sealed length
+	let bucket = padding::Padding::Bucket(3 * 1024 * 1024);
+	let (mut short, mut long) = (vec![0u8; 4711], vec![0u8; 2 * stream::CHUNK_DATA_SIZE + 4711]);
+	crypto::random(&mut short);
+	crypto::random(&mut long);
+	
+	let (sealed_short, sealed_long) = (seal(&short, bucket), seal(&long, bucket));
+	assert_eq!(sealed_short.len(), sealed_long.len());
+	assert_eq!(open(&sealed_short), short);
+	assert_eq!(open(&sealed_long), long);
+}
+
+#[test]
+fn trailer_across_chunks() {
+	// The padded length ends 4 bytes after a chunk-boundary, so the trailer spans two chunks; the
+	// trailing zeros of the plaintext must not be stripped with the padding
+	let bucket = padding::Padding::Bucket(stream::CHUNK_DATA_SIZE as u64 + 4);
+	let mut plaintext = vec![0u8; stream::CHUNK_DATA_SIZE - 4711];
+	crypto::random(&mut plaintext[.. 4711]);
+	
+	assert_eq!(open(&seal(&plaintext, bucket)), plaintext);
+	assert_eq!(open(&seal(&[], padding::Padding::Padme)), Vec::<u8>::new());
+}
+
+#[test]
+fn invalid_trailer() {
+	let mut output = Vec::new();
+	let mut unpadder = padding::Unpadder::new();
+	{
+		let mut sink = |data: &[u8]| { output.extend_from_slice(data); Ok(()) };
+		unpadder.write_chunk(&[0x01, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], &mut sink).unwrap();
+		unpadder.write_chunk(&[0x00, 0x00, 0x00, 0x03], &mut sink).unwrap();
+		assert_eq!(unpadder.finish(&mut sink).unwrap_err().kind, CpError::InvalidData);
+	}
+	assert_eq!(output, vec![0x01, 0x02]);
+}
\ No newline at end of file
@@ -0,0 +1,116 @@
+use super::super::CpError;
+use super::super::{ crypto, stream, fec };
+use super::super::asn1_der::IntoDerObject;
+use super::memory_io::MemoryIo;
+
+const PARITY_STREAM_PASSWORD: &str = "Parity password";
+/// The length of the plaintext (three full chunks and a partial last chunk)
+const PLAINTEXT_LENGTH: usize = 3 * stream::CHUNK_DATA_SIZE + 4711;
+/// The length of a full sealed chunk
+const SHARD_LENGTH: usize = stream::CHUNK_DATA_SIZE + 16;
+
+
+#[test]
+fn reconstruct() {
+	// Create random shards
+	let data: Vec<Vec<u8>> = (0..7).map(|_| { let mut shard = vec![0u8; 509]; crypto::random(&mut shard); shard }).collect();
+	let reed_solomon = fec::ReedSolomon::new(10, 4).unwrap();
+	let mut parity = vec![vec![0u8; 509]; 4];
+	{
+		let shards: Vec<&[u8]> = data.iter().map(|shard| shard.as_slice()).collect();
+		reed_solomon.encode(&shards, &mut parity).unwrap();
+	}
+	
+	// Erase up to four shards and reconstruct them with different parity-shards
+	for &(ref erased, ref parity_indices) in [(vec![0], vec![3]), (vec![6, 2], vec![0, 1]), (vec![1, 3, 4, 5], vec![3, 2, 1, 0])].iter() {
+		let mut damaged = data.clone();
+		for index in erased.iter() { for byte in damaged[*index].iter_mut() { *byte ^= 0x42 } }
+		
+		let parity: Vec<(usize, &[u8])> = parity_indices.iter().map(|index| (*index, parity[*index].as_slice())).collect();
+		reed_solomon.reconstruct(&mut damaged, erased, &parity).unwrap();
+		assert_eq!(damaged, data);
+	}
+}
+
+
+/// Seals `plaintext` with 10% parity (=> two parity-blocks for the only group)
+fn seal(plaintext: &[u8]) -> Vec<u8> {
+	let mut stream_instance = crypto::StreamInstance::new(
+		crypto::pbkdf::Argon2i::new(4, 512, 5),
+		crypto::kdf::HmacSha2512::new(),
+		crypto::auth_enc::ChaCha20Poly1305::new()
+	);
+	stream_instance.parity = Some(fec::Parity::from_percent(10).unwrap());
+	
+	let mut io = MemoryIo::new(plaintext.to_vec(), super::estimate_sealed_size(plaintext.len(), 16) + 2 * SHARD_LENGTH);
+	stream::Encryptor::new(PARITY_STREAM_PASSWORD, &mut io, stream_instance).unwrap().runloop().unwrap();
+	io.stdout()
+}
+
+#[test]
+fn repair() {
+	let mut plaintext = vec![0u8; PLAINTEXT_LENGTH];
+	crypto::random(&mut plaintext);
+	let mut sealed = seal(&plaintext);
+	
+	// Damage the second and the last chunk
+	let header_length = sealed.len() - (5 * SHARD_LENGTH + 4711 + 16);
+	sealed[header_length + SHARD_LENGTH + 7] ^= 0x01;
+	sealed[header_length + 3 * SHARD_LENGTH + 9] ^= 0x01;
+	
+	// Open the stream and validate the repairs
+	let mut io = MemoryIo::new(sealed.clone(), sealed.len());
+	let repaired = {
+		let mut decryptor = stream::Decryptor::new(PARITY_STREAM_PASSWORD, &mut io).unwrap();
+		decryptor.runloop().unwrap();
+		decryptor.repaired_chunks().to_vec()
+	};
+	assert_eq!(io.stdout(), plaintext);
+	assert_eq!(repaired, vec![
+		stream::RepairedChunk{ chunk_index: 1, ciphertext_offset: (header_length + SHARD_LENGTH) as u64 },
+		stream::RepairedChunk{ chunk_index: 3, ciphertext_offset: (header_length + 3 * SHARD_LENGTH) as u64 }
+	]);
+}
+
+#[test]
+fn repair_with_damaged_parity() {
+	let mut plaintext = vec![0u8; PLAINTEXT_LENGTH];
+	crypto::random(&mut plaintext);
+	let mut sealed = seal(&plaintext);
+	
+	// Damage the first chunk and the first parity-block
+	let header_length = sealed.len() - (5 * SHARD_LENGTH + 4711 + 16);
+	sealed[header_length + 3] ^= 0x01;
+	sealed[header_length + 3 * SHARD_LENGTH + 4711 + 16 + 3] ^= 0x01;
+	
+	let mut io = MemoryIo::new(sealed.clone(), sealed.len());
+	stream::Decryptor::new(PARITY_STREAM_PASSWORD, &mut io).unwrap().runloop().unwrap();
+	assert_eq!(io.stdout(), plaintext);
+}
+
+#[test]
+fn too_much_damage() {
+	let mut plaintext = vec![0u8; PLAINTEXT_LENGTH];
+	crypto::random(&mut plaintext);
+	let mut sealed = seal(&plaintext);
+	
+	// Damage three chunks
+	let header_length = sealed.len() - (5 * SHARD_LENGTH + 4711 + 16);
+	for index in 0..3 { sealed[header_length + index * SHARD_LENGTH] ^= 0x01 }
+	
+	let mut io = MemoryIo::new(sealed.clone(), sealed.len());
+	let mut decryptor = stream::Decryptor::new(PARITY_STREAM_PASSWORD, &mut io).unwrap();
+	assert_eq!(decryptor.runloop().unwrap_err().kind, CpError::InvalidData);
+	assert_eq!(decryptor.position().chunk_index, 0);
+}
+
+#[test]
+fn invalid_configuration() {
+	let serialize = |data_chunks: u64, parity_chunks: u64| vec![data_chunks.into_der_object(), parity_chunks.into_der_object()].into_der_object();
+	assert_eq!(fec::Parity::from_serialized(serialize(10, 2)).unwrap(), fec::Parity{ data_chunks: 10, parity_chunks: 2 });
+	
+	// Invalid amounts (the sum must not overflow)
+	for &(data_chunks, parity_chunks) in [(0, 2), (10, 0), (200, 57), (u64::max_value(), 2), (2, u64::max_value())].iter() {
+		assert_eq!(fec::Parity::from_serialized(serialize(data_chunks, parity_chunks)).unwrap_err().kind, CpError::InvalidData);
+	}
+}
\ No newline at end of file
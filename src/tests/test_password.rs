@@ -0,0 +1,43 @@
+use super::super::{ CpError, SealBuilder };
+use super::super::builder::DEFAULT_MIN_PASSWORD_ENTROPY;
+use super::super::crypto::{ Password, passphrase };
+use super::super::io::StreamIo;
+
+#[test]
+fn estimate_entropy() {
+	// Weak passwords
+	for password in ["", "a", "aaaaaaaaaaaaaaaaaaaa", "abcdefghijklmnopqrst", "123456789", "Password1"].iter() {
+		let entropy = Password::new(password).estimate_entropy();
+		assert!(entropy < DEFAULT_MIN_PASSWORD_ENTROPY, "\"{}\" is estimated with {} bits", password, entropy)
+	}
+	
+	// Strong passwords
+	for password in ["Tr0ub4dor&3", "correct horse battery staple", "aöZ7#pQ-9xV!"].iter() {
+		let entropy = Password::new(password).estimate_entropy();
+		assert!(entropy >= DEFAULT_MIN_PASSWORD_ENTROPY, "\"{}\" is estimated with {} bits", password, entropy)
+	}
+}
+
+#[test]
+fn reject_weak_password() {
+	let mut io = StreamIo::new(b"Testolope".as_ref(), Vec::new());
+	let error = SealBuilder::new("Password1").argon2i(4, 8, 1).min_password_entropy(DEFAULT_MIN_PASSWORD_ENTROPY)
+		.seal(&mut io).unwrap_err();
+	assert_eq!(error.kind, CpError::InvalidParameter);
+	assert!(io.into_inner().1.is_empty())
+}
+#[test]
+fn generate_passphrase() {
+	let wordlist = passphrase::wordlist();
+	assert_eq!(wordlist.len(), 2048);
+	assert_eq!(passphrase::entropy(8), 88.0);
+	
+	let generated = passphrase::generate(8, "-");
+	let words: Vec<&str> = generated.as_str().split('-').collect();
+	assert_eq!(words.len(), 8);
+	assert!(words.iter().all(|word| wordlist.contains(word)));
+	assert!(generated.estimate_entropy() >= DEFAULT_MIN_PASSWORD_ENTROPY);
+	
+	// Two passphrases must (practically) never be equal
+	assert_ne!(generated.as_str(), passphrase::generate(8, "-").as_str())
+}
\ No newline at end of file
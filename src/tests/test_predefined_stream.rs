@@ -32,8 +32,16 @@ fn test_encryption_argon2i_hmacsha512_chachapoly() {
 		io.stdout()
 	};
 	
-	// Compare data
-	assert_eq!(SEALED_ARGON2I_HMACSHA512_CHACHAPOLY, encrypted.as_slice())
+	// The predefined stream uses a v1-header, so only the sealed chunks must be equal
+	let header_length = |data: &[u8]| crypto::StreamInstance::try_parse_length(data).unwrap().unwrap();
+	assert_eq!(
+		&SEALED_ARGON2I_HMACSHA512_CHACHAPOLY[header_length(SEALED_ARGON2I_HMACSHA512_CHACHAPOLY) ..],
+		&encrypted[header_length(&encrypted) ..]
+	);
+	
+	// The v2-header must contain the key-check-value
+	let header = crypto::StreamInstance::from_serialized(encrypted[.. header_length(&encrypted)].to_vec()).unwrap();
+	assert!(header.key_check.is_some())
 }
 
 #[test]
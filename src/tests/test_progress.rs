@@ -0,0 +1,75 @@
+use std::rc::Rc;
+use std::cell::RefCell;
+use super::super::{ stream, fec, compression, SealBuilder };
+use super::super::stream::Position;
+use super::{ PLAINTEXT_LENGTH, random_plaintext };
+
+const PROGRESS_PASSWORD: &str = "Progress password";
+
+
+/// Creates a progress-callback and the list of the reported positions
+fn recorder() -> (Box<FnMut(&Position)>, Rc<RefCell<Vec<Position>>>) {
+	let positions = Rc::new(RefCell::new(Vec::new()));
+	let recorded = positions.clone();
+	(Box::new(move |position: &Position| recorded.borrow_mut().push(*position)), positions)
+}
+
+/// Seals `plaintext` (with the options set by `configure`) and returns the sealed stream and the
+/// reported positions
+fn seal<F: FnOnce(SealBuilder) -> SealBuilder>(plaintext: &[u8], configure: F) -> (Vec<u8>, Vec<Position>) {
+	let (progress, positions) = recorder();
+	let sealed = super::seal_with(PROGRESS_PASSWORD, plaintext, |builder| configure(builder).progress(progress));
+	let positions = positions.borrow().clone();
+	(sealed, positions)
+}
+
+/// Opens `sealed` and returns the plaintext and the reported positions
+fn open(sealed: &[u8]) -> (Vec<u8>, Vec<Position>) {
+	let (progress, positions) = recorder();
+	let opened = super::open_with(PROGRESS_PASSWORD, sealed, |builder| builder.progress(progress)).unwrap();
+	let positions = positions.borrow().clone();
+	(opened, positions)
+}
+
+
+#[test]
+fn every_chunk() {
+	let data = random_plaintext(PLAINTEXT_LENGTH);
+	let (sealed, sealed_positions) = seal(&data, |builder| builder);
+	
+	// One position per chunk that ends after the chunk
+	assert_eq!(sealed_positions.len(), 4);
+	for (index, position) in sealed_positions.iter().enumerate() {
+		assert_eq!(position.chunk_index, index as u64 + 1);
+		assert_eq!(position.plaintext_bytes, std::cmp::min((index + 1) * stream::CHUNK_DATA_SIZE, PLAINTEXT_LENGTH) as u64);
+	}
+	assert_eq!(sealed_positions.last().unwrap().ciphertext_offset, sealed.len() as u64);
+	
+	// Opening reports the same positions
+	let (opened, opened_positions) = open(&sealed);
+	assert_eq!(opened, data);
+	assert_eq!(opened_positions, sealed_positions);
+}
+
+#[test]
+fn compressed_and_parity() {
+	let data = vec![0x2a; PLAINTEXT_LENGTH];
+	let compression = compression::Compression::new(compression::Algorithm::Zstd, 19).unwrap();
+	let configurations: [Box<Fn(SealBuilder) -> SealBuilder>; 2] = [
+		Box::new(move |builder| builder.compression(compression)),
+		Box::new(|builder| builder.parity(fec::Parity::from_percent(10).unwrap()))
+	];
+	for configure in configurations.iter() {
+		// The plaintext-bytes count the input (and not the compressed data)
+		let (sealed, sealed_positions) = seal(&data, configure);
+		let last = *sealed_positions.last().unwrap();
+		assert_eq!(last.plaintext_bytes, PLAINTEXT_LENGTH as u64);
+		assert_eq!(last.ciphertext_offset, sealed.len() as u64);
+		assert!(sealed_positions.windows(2).all(|pair| pair[0].ciphertext_offset < pair[1].ciphertext_offset));
+		
+		// The last position includes the parity-blocks
+		let (opened, opened_positions) = open(&sealed);
+		assert_eq!(opened, data);
+		assert_eq!(*opened_positions.last().unwrap(), last);
+	}
+}
\ No newline at end of file
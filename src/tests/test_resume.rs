@@ -0,0 +1,157 @@
+use std;
+use std::rc::Rc;
+use std::cell::RefCell;
+use std::path::PathBuf;
+use super::super::CpError;
+use super::super::{ crypto, io, stream, padding, metadata, resume, SealBuilder, OpenBuilder };
+
+const RESUME_PASSWORD: &str = "Resume password";
+/// The length of the plaintext (three full chunks and a partial last chunk)
+const PLAINTEXT_LENGTH: usize = 3 * stream::CHUNK_DATA_SIZE + 4711;
+/// The length of a full sealed chunk
+const SEALED_CHUNK_LENGTH: usize = stream::CHUNK_DATA_SIZE + 16;
+
+
+/// Creates an empty temporary directory for the test `name` that contains the random input-file
+/// "input"
+fn temp_dir(name: &str) -> (PathBuf, Vec<u8>) {
+	let dir = std::env::temp_dir().join(format!("crypto_pipe_test_resume_{}_{}", name, std::process::id()));
+	if dir.exists() { std::fs::remove_dir_all(&dir).unwrap() }
+	std::fs::create_dir_all(&dir).unwrap();
+	
+	let mut plaintext = vec![0u8; PLAINTEXT_LENGTH];
+	crypto::random(&mut plaintext);
+	std::fs::write(dir.join("input"), &plaintext).unwrap();
+	(dir, plaintext)
+}
+
+/// Seals the input-file in `dir` to "sealed" and returns the sealed stream
+fn seal(dir: &PathBuf, builder: SealBuilder) -> Vec<u8> {
+	let mut io = io::StreamIo::new(std::fs::File::open(dir.join("input")).unwrap(), std::fs::File::create(dir.join("sealed")).unwrap());
+	builder.argon2i(4, 512, 5).seal(&mut io).unwrap();
+	std::fs::read(dir.join("sealed")).unwrap()
+}
+
+/// Resumes the sealing-operation of the input-file in `dir` into "sealed"
+fn resume(dir: &PathBuf, password: &str) -> Result<u64, CpError> {
+	let mut input = std::fs::File::open(dir.join("input")).unwrap();
+	let mut output = std::fs::OpenOptions::new().read(true).write(true).open(dir.join("sealed")).unwrap();
+	resume::resume(password, &mut input, &mut output, None).map_err(|error| error.kind)
+}
+
+/// Opens "sealed" in `dir` and returns the plaintext
+fn open(dir: &PathBuf) -> Vec<u8> {
+	let mut io = io::StreamIo::new(std::fs::File::open(dir.join("sealed")).unwrap(), Vec::new());
+	OpenBuilder::new(RESUME_PASSWORD).open(&mut io).unwrap();
+	io.into_inner().1
+}
+
+
+#[test]
+fn resume_interrupted() {
+	let (dir, plaintext) = temp_dir("resume_interrupted");
+	let sealed = seal(&dir, SealBuilder::new(RESUME_PASSWORD));
+	let header_length = sealed.len() - (3 * SEALED_CHUNK_LENGTH + 4711 + 16);
+	
+	// Interrupt the operation after the stream-header, within the first chunk, on a chunk-boundary and within the last chunk
+	let interruptions = [(header_length, 0), (header_length + 7, 0), (header_length + 2 * SEALED_CHUNK_LENGTH, 2), (sealed.len() - 1, 3)];
+	for &(length, verified) in interruptions.iter() {
+		std::fs::write(dir.join("sealed"), &sealed[.. length]).unwrap();
+		assert_eq!(resume(&dir, RESUME_PASSWORD).unwrap(), verified);
+		
+		// The chunk-keys depend only on the base-key and the chunk-index, so the stream is identical
+		assert_eq!(std::fs::read(dir.join("sealed")).unwrap(), sealed);
+		assert_eq!(open(&dir), plaintext);
+	}
+	
+	// A torn tail that contains garbage (e.g. a preallocated block) is not a prefix of the re-sealed
+	// chunk, so the output is not modified
+	let mut torn = sealed[.. header_length + SEALED_CHUNK_LENGTH].to_vec();
+	torn.extend_from_slice(&vec![0u8; 2 * SEALED_CHUNK_LENGTH]);
+	std::fs::write(dir.join("sealed"), &torn).unwrap();
+	assert_eq!(resume(&dir, RESUME_PASSWORD).unwrap_err(), CpError::InvalidParameter);
+	assert_eq!(std::fs::read(dir.join("sealed")).unwrap(), torn);
+	
+	// A complete stream is not modified
+	std::fs::write(dir.join("sealed"), &sealed).unwrap();
+	assert_eq!(resume(&dir, RESUME_PASSWORD).unwrap(), 4);
+	assert_eq!(std::fs::read(dir.join("sealed")).unwrap(), sealed);
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn progress() {
+	let (dir, plaintext) = temp_dir("progress");
+	let sealed = seal(&dir, SealBuilder::new(RESUME_PASSWORD));
+	let header_length = sealed.len() - (3 * SEALED_CHUNK_LENGTH + 4711 + 16);
+	std::fs::write(dir.join("sealed"), &sealed[.. header_length + 2 * SEALED_CHUNK_LENGTH + 7]).unwrap();
+	
+	// Record the positions
+	let positions = Rc::new(RefCell::new(Vec::new()));
+	let recorded = positions.clone();
+	let mut input = std::fs::File::open(dir.join("input")).unwrap();
+	let mut output = std::fs::OpenOptions::new().read(true).write(true).open(dir.join("sealed")).unwrap();
+	let progress: Box<FnMut(&stream::Position)> = Box::new(move |position: &stream::Position| recorded.borrow_mut().push(*position));
+	assert_eq!(resume::resume(RESUME_PASSWORD, &mut input, &mut output, Some(progress)).unwrap(), 2);
+	
+	// The verified and the newly sealed chunks are reported with their positions in the whole stream
+	let positions = positions.borrow();
+	assert_eq!(positions.len(), 4);
+	for (index, position) in positions.iter().enumerate() {
+		assert_eq!(position.chunk_index, index as u64 + 1);
+		assert_eq!(position.ciphertext_offset, std::cmp::min(header_length + (index + 1) * SEALED_CHUNK_LENGTH, sealed.len()) as u64);
+		assert_eq!(position.plaintext_bytes, std::cmp::min((index + 1) * stream::CHUNK_DATA_SIZE, plaintext.len()) as u64);
+	}
+	assert_eq!(std::fs::read(dir.join("sealed")).unwrap(), sealed);
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn changed_input() {
+	let (dir, mut plaintext) = temp_dir("changed_input");
+	let sealed = seal(&dir, SealBuilder::new(RESUME_PASSWORD));
+	let interrupted = &sealed[.. sealed.len() - 4711];
+	
+	// A modified chunk is detected and the output is not modified
+	plaintext[stream::CHUNK_DATA_SIZE + 17] ^= 0x01;
+	std::fs::write(dir.join("input"), &plaintext).unwrap();
+	std::fs::write(dir.join("sealed"), interrupted).unwrap();
+	assert_eq!(resume(&dir, RESUME_PASSWORD).unwrap_err(), CpError::InvalidParameter);
+	assert_eq!(std::fs::read(dir.join("sealed")).unwrap(), interrupted);
+	
+	// A modified torn chunk is detected (re-sealing it would reuse its key-stream for different data)
+	plaintext[stream::CHUNK_DATA_SIZE + 17] ^= 0x01;
+	plaintext[3 * stream::CHUNK_DATA_SIZE + 5] ^= 0x01;
+	std::fs::write(dir.join("input"), &plaintext).unwrap();
+	assert_eq!(resume(&dir, RESUME_PASSWORD).unwrap_err(), CpError::InvalidParameter);
+	assert_eq!(std::fs::read(dir.join("sealed")).unwrap(), interrupted);
+	
+	// Data appended to the input after the stream is complete
+	plaintext[3 * stream::CHUNK_DATA_SIZE + 5] ^= 0x01;
+	plaintext.extend_from_slice(b"Testolope");
+	std::fs::write(dir.join("input"), &plaintext).unwrap();
+	std::fs::write(dir.join("sealed"), &sealed).unwrap();
+	assert_eq!(resume(&dir, RESUME_PASSWORD).unwrap_err(), CpError::InvalidParameter);
+	
+	// A modification after the written chunks is detected by the stored modification-time
+	let sealed = seal(&dir, SealBuilder::new(RESUME_PASSWORD).metadata(metadata::Metadata::from_file(dir.join("input")).unwrap()));
+	std::fs::write(dir.join("sealed"), &sealed[.. sealed.len() - 4711]).unwrap();
+	let input = std::fs::OpenOptions::new().write(true).open(dir.join("input")).unwrap();
+	input.set_modified(std::time::UNIX_EPOCH + std::time::Duration::from_secs(4711)).unwrap();
+	assert_eq!(resume(&dir, RESUME_PASSWORD).unwrap_err(), CpError::InvalidParameter);
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn invalid_streams() {
+	let (dir, _) = temp_dir("invalid_streams");
+	
+	// A wrong password
+	seal(&dir, SealBuilder::new(RESUME_PASSWORD));
+	assert_eq!(resume(&dir, "Wrong password").unwrap_err(), CpError::WrongPassword);
+	
+	// A stream whose chunks depend on each other
+	seal(&dir, SealBuilder::new(RESUME_PASSWORD).padding(padding::Padding::Padme));
+	assert_eq!(resume(&dir, RESUME_PASSWORD).unwrap_err(), CpError::Unsupported);
+	std::fs::remove_dir_all(&dir).unwrap();
+}
\ No newline at end of file
@@ -0,0 +1,113 @@
+use std::io::{ Cursor, Read, Seek, SeekFrom };
+use super::super::CpError;
+use super::super::{ io, stream, fec, compression, padding, seekable, SealBuilder };
+use super::{ PLAINTEXT_LENGTH, random_plaintext };
+
+const SEEKABLE_PASSWORD: &str = "Seekable password";
+
+
+/// Seals `plaintext` with a seek-index (and the options set by `configure`)
+fn seal<F: FnOnce(SealBuilder) -> SealBuilder>(plaintext: &[u8], configure: F) -> Vec<u8> {
+	super::seal_with(SEEKABLE_PASSWORD, plaintext, |builder| configure(builder.seek_index(true)))
+}
+
+/// Opens `sealed` sequentially and returns the plaintext
+fn open(sealed: &[u8]) -> Result<Vec<u8>, CpError> {
+	super::open_with(SEEKABLE_PASSWORD, sealed, |builder| builder)
+}
+
+/// Opens `sealed` with a `DecryptReader`
+fn open_seekable(sealed: &[u8]) -> Result<seekable::DecryptReader<Cursor<&[u8]>>, CpError> {
+	seekable::DecryptReader::open(SEEKABLE_PASSWORD, Cursor::new(sealed)).map_err(|error| error.kind)
+}
+
+
+#[test]
+fn roundtrip() {
+	let data = random_plaintext(PLAINTEXT_LENGTH);
+	for length in [0, 4711, stream::CHUNK_DATA_SIZE, PLAINTEXT_LENGTH].iter() {
+		let sealed = seal(&data[.. *length], |builder| builder);
+		assert_eq!(open(&sealed).unwrap(), &data[.. *length]);
+		
+		// Read the whole plaintext with a `DecryptReader`
+		let mut reader = open_seekable(&sealed).unwrap();
+		assert_eq!(reader.len(), *length as u64);
+		assert_eq!(reader.index().chunk_count(), (*length / stream::CHUNK_DATA_SIZE) as u64 + 1);
+		let mut opened = Vec::new();
+		reader.read_to_end(&mut opened).unwrap();
+		assert_eq!(opened, &data[.. *length]);
+	}
+}
+
+#[test]
+fn random_access() {
+	let data = random_plaintext(PLAINTEXT_LENGTH);
+	let configurations: [fn(SealBuilder) -> SealBuilder; 3] = [
+		|builder| builder,
+		|builder| builder.padding(padding::Padding::Padme),
+		|builder| builder.parity(fec::Parity::from_percent(10).unwrap())
+	];
+	for configure in configurations.iter() {
+		let sealed = seal(&data, configure);
+		assert_eq!(open(&sealed).unwrap(), data);
+		let mut reader = open_seekable(&sealed).unwrap();
+		
+		// Read ranges that start in, end in or span multiple chunks
+		let ranges = [(0, 17), (stream::CHUNK_DATA_SIZE - 9, 4711), (2 * stream::CHUNK_DATA_SIZE + 1, stream::CHUNK_DATA_SIZE), (PLAINTEXT_LENGTH - 4711, 4711), (7, 3)];
+		for &(offset, length) in ranges.iter() {
+			let mut buffer = vec![0u8; length];
+			assert_eq!(reader.seek(SeekFrom::Start(offset as u64)).unwrap(), offset as u64);
+			reader.read_exact(&mut buffer).unwrap();
+			assert_eq!(buffer.as_slice(), &data[offset .. offset + length]);
+		}
+		
+		// Seek relative to the end and the current position (the padding is never returned)
+		reader.seek(SeekFrom::End(-4)).unwrap();
+		let mut tail = Vec::new();
+		reader.read_to_end(&mut tail).unwrap();
+		assert_eq!(tail.as_slice(), &data[PLAINTEXT_LENGTH - 4 ..]);
+		assert_eq!(reader.seek(SeekFrom::Current(-(PLAINTEXT_LENGTH as i64))).unwrap(), 0);
+		assert_eq!(reader.seek(SeekFrom::Current(-1)).unwrap_err().kind(), std::io::ErrorKind::InvalidInput);
+		assert_eq!(reader.seek(SeekFrom::End(13)).unwrap(), PLAINTEXT_LENGTH as u64 + 13);
+		assert_eq!(reader.read(&mut [0u8; 16]).unwrap(), 0);
+	}
+}
+
+#[test]
+fn tampered_index() {
+	let sealed = seal(&random_plaintext(PLAINTEXT_LENGTH), |builder| builder);
+	
+	// A tampered sealed index
+	let mut tampered = sealed.clone();
+	let position = tampered.len() - seekable::INDEX_LENGTH_SIZE - 3;
+	tampered[position] ^= 0x01;
+	assert_eq!(open_seekable(&tampered).unwrap_err(), CpError::InvalidData);
+	assert_eq!(open(&tampered).unwrap_err(), CpError::InvalidData);
+	
+	// A tampered index-length
+	let mut tampered = sealed.clone();
+	let position = tampered.len() - 1;
+	tampered[position] ^= 0x01;
+	assert_eq!(open_seekable(&tampered).unwrap_err(), CpError::InvalidData);
+	assert_eq!(open(&tampered).unwrap_err(), CpError::InvalidData);
+	
+	// A missing trailer
+	let truncated = &sealed[.. sealed.len() - 5];
+	assert_eq!(open_seekable(truncated).unwrap_err(), CpError::InvalidData);
+	assert_eq!(open(truncated).unwrap_err(), CpError::InvalidData);
+}
+
+#[test]
+fn invalid_options() {
+	// A stream without seek-index
+	let mut io = io::StreamIo::new(&b"Testolope"[..], Vec::new());
+	SealBuilder::new(SEEKABLE_PASSWORD).argon2i(4, 512, 5).seal(&mut io).unwrap();
+	assert_eq!(open_seekable(&io.into_inner().1).unwrap_err(), CpError::Unsupported);
+	
+	// The seek-index cannot be combined with compression or the appendable chunk-format
+	let compression = compression::Compression::new(compression::Algorithm::Zstd, 19).unwrap();
+	for builder in vec![SealBuilder::new(SEEKABLE_PASSWORD).compression(compression), SealBuilder::new(SEEKABLE_PASSWORD).appendable(true)] {
+		let mut io = io::StreamIo::new(&b"Testolope"[..], Vec::new());
+		assert_eq!(builder.seek_index(true).seal(&mut io).unwrap_err().kind, CpError::InvalidParameter);
+	}
+}
\ No newline at end of file
@@ -0,0 +1,8 @@
+use super::super::crypto;
+
+#[test]
+fn known_answers() {
+	for &(primitive, test) in crypto::selftest::TESTS.iter() {
+		if let Err(error) = test() { panic!("{}: {}", primitive, error) }
+	}
+}
\ No newline at end of file
@@ -0,0 +1,128 @@
+use std;
+use std::path::PathBuf;
+use super::super::CpError;
+use super::super::{ stream, volume, SealBuilder, OpenBuilder };
+use super::{ PLAINTEXT_LENGTH, random_plaintext };
+
+const VOLUME_PASSWORD: &str = "Volume password";
+/// A volume-size that holds exactly one sealed chunk besides the headers
+const VOLUME_SIZE: u64 = (stream::CHUNK_DATA_SIZE + 16 + 512) as u64;
+
+
+/// Creates an empty temporary directory for the test `name`
+fn temp_dir(name: &str) -> PathBuf {
+	let dir = std::env::temp_dir().join(format!("crypto_pipe_test_volume_{}_{}", name, std::process::id()));
+	if dir.exists() { std::fs::remove_dir_all(&dir).unwrap() }
+	std::fs::create_dir_all(&dir).unwrap();
+	dir
+}
+
+/// Seals `plaintext` into volumes at `prefix` and returns the amount of volumes
+fn seal(plaintext: &[u8], prefix: &PathBuf) -> u64 {
+	SealBuilder::new(VOLUME_PASSWORD).argon2i(4, 512, 5).seal_volumes(plaintext, prefix, VOLUME_SIZE).unwrap()
+}
+
+/// Opens `volumes` and returns the plaintext or the error-kind
+fn open(volumes: &volume::VolumeSet) -> Result<Vec<u8>, CpError> {
+	let mut plaintext = Vec::new();
+	match OpenBuilder::new(VOLUME_PASSWORD).open_volumes(volumes, &mut plaintext) {
+		Ok(()) => Ok(plaintext),
+		Err(error) => Err(error.kind)
+	}
+}
+
+
+#[test]
+fn roundtrip() {
+	let (dir, plaintext) = (temp_dir("roundtrip"), random_plaintext(PLAINTEXT_LENGTH));
+	let prefix = dir.join("sealed");
+	let total = seal(&plaintext, &prefix);
+	assert!(total >= 4);
+	
+	// Every volume respects the volume-size
+	for index in 0..total {
+		assert!(std::fs::metadata(volume::volume_path(&prefix, index)).unwrap().len() <= VOLUME_SIZE);
+	}
+	let volumes = volume::VolumeSet::from_prefix(&prefix).unwrap();
+	assert_eq!(volumes.len() as u64, total);
+	assert_eq!(open(&volumes).unwrap(), plaintext);
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn any_order() {
+	let (dir, plaintext) = (temp_dir("any_order"), random_plaintext(PLAINTEXT_LENGTH));
+	let prefix = dir.join("sealed");
+	let total = seal(&plaintext, &prefix);
+	
+	// The volumes are ordered by their headers, not by their paths
+	let paths: Vec<PathBuf> = (0..total).map(|index| volume::volume_path(&prefix, index)).rev().collect();
+	assert_eq!(open(&volume::VolumeSet::from_paths(&paths).unwrap()).unwrap(), plaintext);
+	
+	// Renamed volumes are accepted too
+	std::fs::rename(&paths[0], dir.join("temp")).unwrap();
+	std::fs::rename(&paths[1], &paths[0]).unwrap();
+	std::fs::rename(dir.join("temp"), &paths[1]).unwrap();
+	assert_eq!(open(&volume::VolumeSet::from_prefix(&prefix).unwrap()).unwrap(), plaintext);
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn incomplete_set() {
+	let (dir, plaintext) = (temp_dir("incomplete_set"), random_plaintext(PLAINTEXT_LENGTH));
+	let prefix = dir.join("sealed");
+	let total = seal(&plaintext, &prefix);
+	
+	// A missing volume
+	let paths: Vec<PathBuf> = (0..total).map(|index| volume::volume_path(&prefix, index)).collect();
+	assert_eq!(volume::VolumeSet::from_paths(&paths[1 ..]).unwrap_err().kind, CpError::InvalidData);
+	assert_eq!(volume::VolumeSet::from_paths(&paths[.. paths.len() - 1]).unwrap_err().kind, CpError::InvalidData);
+	
+	// A duplicate volume
+	let mut duplicate = paths.clone();
+	duplicate.push(paths[1].clone());
+	assert_eq!(volume::VolumeSet::from_paths(&duplicate).unwrap_err().kind, CpError::InvalidData);
+	
+	// A volume of another stream
+	let other_prefix = dir.join("other");
+	seal(&plaintext, &other_prefix);
+	std::fs::copy(volume::volume_path(&other_prefix, 1), &paths[1]).unwrap();
+	assert_eq!(volume::VolumeSet::from_prefix(&prefix).unwrap_err().kind, CpError::InvalidData);
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn tampered_volumes() {
+	let (dir, plaintext) = (temp_dir("tampered_volumes"), random_plaintext(PLAINTEXT_LENGTH));
+	let prefix = dir.join("sealed");
+	seal(&plaintext, &prefix);
+	let (path_1, path_2) = (volume::volume_path(&prefix, 1), volume::volume_path(&prefix, 2));
+	let (volume_1, volume_2) = (std::fs::read(&path_1).unwrap(), std::fs::read(&path_2).unwrap());
+	let (_, header_length) = volume::VolumeHeader::read(&mut volume_1.as_slice()).unwrap();
+	let header_length = header_length as usize;
+	
+	// Swapping the headers swaps the order of the contents, which is detected by the chunk-authentication
+	let mut swapped = (volume_1.clone(), volume_2.clone());
+	swapped.0[.. header_length].copy_from_slice(&volume_2[.. header_length]);
+	swapped.1[.. header_length].copy_from_slice(&volume_1[.. header_length]);
+	std::fs::write(&path_1, &swapped.0).unwrap();
+	std::fs::write(&path_2, &swapped.1).unwrap();
+	assert_eq!(open(&volume::VolumeSet::from_prefix(&prefix).unwrap()).unwrap_err(), CpError::InvalidData);
+	
+	// A tampered sealed copy is detected before any chunk is opened
+	let mut tampered = volume_1.clone();
+	tampered[header_length - 1] ^= 0x01;
+	std::fs::write(&path_1, &tampered).unwrap();
+	std::fs::write(&path_2, &volume_2).unwrap();
+	assert_eq!(open(&volume::VolumeSet::from_prefix(&prefix).unwrap()).unwrap_err(), CpError::InvalidData);
+	std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn invalid_volume_size() {
+	let dir = temp_dir("invalid_volume_size");
+	let error = SealBuilder::new(VOLUME_PASSWORD).argon2i(4, 512, 5).seal_volumes(&b"Testolope"[..], dir.join("sealed"), stream::CHUNK_DATA_SIZE as u64).unwrap_err();
+	assert_eq!(error.kind, CpError::InvalidParameter);
+	assert!(!volume::volume_path(dir.join("sealed"), 0).exists());
+	std::fs::remove_dir_all(&dir).unwrap();
+}
\ No newline at end of file
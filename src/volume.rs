@@ -0,0 +1,360 @@
+//! Splitting sealed streams into fixed-size volumes
+//!
+//! Every volume starts with a volume-header followed by the next part of the sealed stream; the
+//! stream is only split between two writes of `stream::Encryptor` (i.e. on sealed chunk-boundaries).
+//! The volume-header contains the stream-ID, the volume-index and the total amount of volumes in
+//! plain and as sealed copy, so the volumes can be ordered without a password and are authenticated
+//! once the base-key is known (see `VolumeSet::verify`).
+
+use std;
+use std::io::{ Read, Write, Seek, SeekFrom };
+use std::path::{ Path, PathBuf };
+use super::{ Error, CpError };
+use super::{ asn1_der, crypto, io, stream };
+use super::io::Io;
+use super::asn1_der::{ FromDerEncoded, FromDerObject, IntoDerEncoded, IntoDerObject };
+
+
+/// The magic-number of a volume-header
+pub const VOLUME_MAGIC: &str = "de.KizzyCode.CryptoPipe.Volume.v1";
+/// The KDF-info-label of the volume-key
+pub const VOLUME_KEY_INFO: &str = "#Volume Key";
+/// The KDF-info-label of the volume-header-keys (the counter is the volume-index)
+pub const VOLUME_INFO: &str = "#Volume";
+/// The length of the stream-ID
+pub const STREAM_ID_SIZE: usize = 16;
+/// The length of the authenticated volume-fields (`stream_id || index || total`)
+const FIELDS_SIZE: usize = STREAM_ID_SIZE + 8 + 8;
+/// The maximum length of a volume-header
+const MAX_HEADER_SIZE: usize = 4096;
+
+
+/// Returns the path of the `index`th volume (`<prefix>.000`, `<prefix>.001` etc.)
+pub fn volume_path<P: AsRef<Path>>(prefix: P, index: u64) -> PathBuf {
+	let mut path = prefix.as_ref().as_os_str().to_os_string();
+	path.push(format!(".{:03}", index));
+	PathBuf::from(path)
+}
+
+/// Derives the volume-key from the base-key of the stream
+pub fn derive_volume_key(stream_instance: &crypto::StreamInstance, base_key: &crypto::Key) -> Result<crypto::Key, Error<CpError>> {
+	let mut kdf_buffer = [0u8; 32];
+	stream_instance.kdf.derive(base_key, try_err!(stream::kdf_info(&mut kdf_buffer, 0, VOLUME_KEY_INFO)))
+}
+
+
+
+/// A volume-header
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct VolumeHeader {
+	/// The random ID of the stream the volume belongs to
+	pub stream_id: [u8; STREAM_ID_SIZE],
+	/// The index of the volume
+	pub index: u64,
+	/// The total amount of volumes
+	pub total: u64,
+	/// The sealed copy of the fields
+	sealed: Vec<u8>
+}
+impl VolumeHeader {
+	/// Serializes the authenticated fields
+	fn fields(&self) -> [u8; FIELDS_SIZE] {
+		let mut fields = [0u8; FIELDS_SIZE];
+		fields[.. STREAM_ID_SIZE].copy_from_slice(&self.stream_id);
+		fields[STREAM_ID_SIZE .. STREAM_ID_SIZE + 8].copy_from_slice(&self.index.to_be_bytes());
+		fields[STREAM_ID_SIZE + 8 ..].copy_from_slice(&self.total.to_be_bytes());
+		fields
+	}
+	
+	/// Derives the key of the volume-header
+	fn key(&self, stream_instance: &crypto::StreamInstance, volume_key: &crypto::Key) -> Result<crypto::Key, Error<CpError>> {
+		let mut kdf_buffer = [0u8; 32];
+		stream_instance.kdf.derive(volume_key, try_err!(stream::kdf_info(&mut kdf_buffer, self.index, VOLUME_INFO)))
+	}
+	
+	/// Seals the fields with the volume-key
+	fn seal(&mut self, stream_instance: &crypto::StreamInstance, volume_key: &crypto::Key) -> Result<(), Error<CpError>> {
+		let mut sealed = self.fields().to_vec();
+		sealed.resize(FIELDS_SIZE + stream_instance.auth_enc.overhead(), 0x00);
+		let sealed_length = try_err!(stream_instance.auth_enc.seal(&mut sealed, FIELDS_SIZE, try_err!(self.key(stream_instance, volume_key))));
+		sealed.truncate(sealed_length);
+		self.sealed = sealed;
+		Ok(())
+	}
+	
+	/// Opens the sealed copy of the fields and compares it with the plain fields
+	fn verify(&self, stream_instance: &crypto::StreamInstance, volume_key: &crypto::Key) -> Result<(), Error<CpError>> {
+		let mut sealed = self.sealed.clone();
+		let length = try_err!(stream_instance.auth_enc.open(&mut sealed, self.sealed.len(), try_err!(self.key(stream_instance, volume_key))));
+		if !crypto::backend::compare_constant_time(&sealed[.. length], &self.fields()) {
+			throw_err!(CpError::InvalidData, format!("The header of volume #{} has been tampered with", self.index))
+		}
+		Ok(())
+	}
+	
+	/// Parses a serialized volume-header
+	pub fn from_serialized(serialized: Vec<u8>) -> Result<Self, Error<CpError>> {
+		let der_object: asn1_der::DerObject = try_err!(asn1_der::DerObject::from_der_encoded(serialized), CpError::InvalidData);
+		let sequence: Vec<asn1_der::DerObject> = try_err!(Vec::<asn1_der::DerObject>::from_der_object(der_object), CpError::InvalidData);
+		if sequence.len() < 5 { throw_err!(CpError::InvalidData) }
+		
+		// Validate the magic-number and parse the fields
+		let magic: String = try_err!(String::from_der_object(sequence[0].clone()), CpError::InvalidData);
+		if magic != VOLUME_MAGIC { throw_err!(CpError::Unsupported, format!("Unsupported volume-version ({})", magic)) }
+		let mut fields = Vec::new();
+		for field in sequence[1 .. 4].iter() { fields.extend(try_err!(Vec::<u8>::from_der_object(field.clone()), CpError::InvalidData)) }
+		if fields.len() != FIELDS_SIZE { throw_err!(CpError::InvalidData) }
+		
+		let (mut stream_id, mut index, mut total) = ([0u8; STREAM_ID_SIZE], [0u8; 8], [0u8; 8]);
+		stream_id.copy_from_slice(&fields[.. STREAM_ID_SIZE]);
+		index.copy_from_slice(&fields[STREAM_ID_SIZE .. STREAM_ID_SIZE + 8]);
+		total.copy_from_slice(&fields[STREAM_ID_SIZE + 8 ..]);
+		Ok(VolumeHeader {
+			stream_id, index: u64::from_be_bytes(index), total: u64::from_be_bytes(total),
+			sealed: try_err!(Vec::<u8>::from_der_object(sequence[4].clone()), CpError::InvalidData)
+		})
+	}
+	
+	/// Serializes the volume-header (the length depends only on the overhead of the sealed copy)
+	pub fn serialize(&self) -> Vec<u8> {
+		let sequence: Vec<asn1_der::DerObject> = vec![
+			VOLUME_MAGIC.to_string().into_der_object(),
+			self.stream_id.to_vec().into_der_object(),
+			self.index.to_be_bytes().to_vec().into_der_object(),
+			self.total.to_be_bytes().to_vec().into_der_object(),
+			self.sealed.clone().into_der_object()
+		];
+		sequence.into_der_object().into_der_encoded()
+	}
+	
+	/// Reads the volume-header from `reader` and returns it together with its length
+	pub fn read(reader: &mut Read) -> Result<(Self, u64), Error<CpError>> {
+		// Determine the header-length (like `stream::read_header`)
+		let mut header_data = Vec::new();
+		let header_length = loop {
+			if let Some(length) = try_err!(crypto::StreamInstance::try_parse_length(&header_data)) { break length }
+			header_data.push(0x00);
+			let position = header_data.len() - 1;
+			try_err_from!(reader.read_exact(&mut header_data[position ..]), "Failed to read the volume-header".to_owned());
+		};
+		
+		if header_length > MAX_HEADER_SIZE { throw_err!(CpError::InvalidData, "Invalid volume-header-length") }
+		let position = header_data.len();
+		header_data.resize(header_length, 0x00);
+		try_err_from!(reader.read_exact(&mut header_data[position ..]), "Failed to read the volume-header".to_owned());
+		Ok((try_err!(Self::from_serialized(header_data)), header_length as u64))
+	}
+}
+
+
+
+/// Writes the sealed stream into volumes of at most `volume_size` bytes
+pub struct VolumeWriter {
+	prefix: PathBuf,
+	volume_size: u64,
+	header: VolumeHeader,
+	volume: Option<(std::fs::File, u64)>
+}
+impl VolumeWriter {
+	/// Creates a new volume-writer (the volumes are only created once data is written; `overhead` is
+	/// the overhead of the authenticated encryption)
+	pub fn new<P: AsRef<Path>>(prefix: P, volume_size: u64, overhead: usize) -> Result<Self, Error<CpError>> {
+		let mut header = VolumeHeader{ stream_id: [0u8; STREAM_ID_SIZE], index: 0, total: 0, sealed: vec![0u8; FIELDS_SIZE + overhead] };
+		crypto::random(&mut header.stream_id);
+		
+		// Every volume must be able to hold at least one sealed chunk or parity-block
+		let min_size = header.serialize().len() as u64 + (stream::CHUNK_DATA_SIZE + overhead) as u64;
+		if volume_size < min_size { throw_err!(CpError::InvalidParameter, format!("The volume-size must be at least {} bytes", min_size)) }
+		Ok(VolumeWriter{ prefix: prefix.as_ref().to_path_buf(), volume_size, header, volume: None })
+	}
+	
+	/// Writes `data` to the current volume or starts a new volume if `data` does not fit
+	pub fn write(&mut self, data: &[u8]) -> Result<(), Error<CpError>> {
+		// Start a new volume if necessary
+		let header_length = self.header.serialize().len() as u64;
+		if header_length + data.len() as u64 > self.volume_size { throw_err!(CpError::InvalidParameter, "The data does not fit into a volume") }
+		let start_volume = match self.volume {
+			Some((_, length)) => length + data.len() as u64 > self.volume_size,
+			None => true
+		};
+		if start_volume {
+			if self.volume.is_some() { self.header.index += 1 }
+			let path = volume_path(&self.prefix, self.header.index);
+			let mut file = try_err_from!(std::fs::File::create(&path), format!("Failed to create the volume \"{}\"", path.display()));
+			
+			// Reserve the space for the volume-header (it is written by `finish`)
+			try_err_from!(file.write_all(&vec![0u8; header_length as usize]), format!("Failed to write to the volume \"{}\"", path.display()));
+			self.volume = Some((file, header_length));
+		}
+		
+		// Write the data
+		if let Some((ref mut file, ref mut length)) = self.volume {
+			try_err_from!(file.write_all(data), "Failed to write to the volume".to_owned());
+			*length += data.len() as u64;
+		}
+		Ok(())
+	}
+	
+	/// Seals and writes the volume-headers of all volumes and returns the amount of volumes
+	pub fn finish(mut self, stream_instance: &crypto::StreamInstance, volume_key: &crypto::Key) -> Result<u64, Error<CpError>> {
+		if let Some((ref mut file, _)) = self.volume { try_err_from!(file.flush()) }
+		self.volume = None;
+		
+		// Every volume-key is used exactly once, because the headers are only sealed once the total is
+		// known
+		let total = self.header.index + 1;
+		for index in 0..total {
+			let mut header = VolumeHeader{ index, total, ..self.header.clone() };
+			try_err!(header.seal(stream_instance, volume_key));
+			
+			let path = volume_path(&self.prefix, index);
+			let mut file = try_err_from!(std::fs::OpenOptions::new().write(true).open(&path), format!("Failed to open the volume \"{}\"", path.display()));
+			try_err_from!(file.write_all(&header.serialize()), format!("Failed to write the header of the volume \"{}\"", path.display()));
+			try_err_from!(file.sync_all());
+		}
+		Ok(total)
+	}
+}
+
+/// An `Io`-implementation that reads the plaintext from `reader` and writes the sealed stream into
+/// volumes
+pub struct VolumeIo<R: Read> {
+	input: io::StreamIo<R, std::io::Sink>,
+	writer: VolumeWriter
+}
+impl<R: Read> VolumeIo<R> {
+	/// Creates a new `Io`-instance that reads from `reader` and writes to `writer`
+	pub fn new(reader: R, writer: VolumeWriter) -> Self {
+		VolumeIo{ input: io::StreamIo::new(reader, std::io::sink()), writer }
+	}
+	
+	/// Returns the volume-writer
+	pub fn into_writer(self) -> VolumeWriter {
+		self.writer
+	}
+}
+impl<R: Read> Io for VolumeIo<R> {
+	fn read_chunk(&mut self, chunk_buffer: &mut[u8]) -> Result<(usize, bool), Error<CpError>> {
+		self.input.read_chunk(chunk_buffer)
+	}
+	fn write_chunk(&mut self, data: &[u8]) -> Result<(), Error<CpError>> {
+		self.writer.write(data)
+	}
+	
+	fn read_exact(&mut self, buffer: &mut[u8]) -> Result<(), Error<CpError>> {
+		self.input.read_exact(buffer)
+	}
+	fn write_exact(&mut self, data: &[u8]) -> Result<(), Error<CpError>> {
+		self.writer.write(data)
+	}
+}
+
+
+
+/// A complete set of volumes ordered by their volume-index
+#[derive(Debug, Clone)]
+pub struct VolumeSet {
+	volumes: Vec<(PathBuf, VolumeHeader, u64)>
+}
+impl VolumeSet {
+	/// Reads the headers of the volumes at `paths` (in any order) and validates that they form a
+	/// complete set
+	pub fn from_paths<P: AsRef<Path>>(paths: &[P]) -> Result<Self, Error<CpError>> {
+		let mut volumes = Vec::new();
+		for path in paths.iter() {
+			let mut file = try_err_from!(std::fs::File::open(path.as_ref()), format!("Failed to open the volume \"{}\"", path.as_ref().display()));
+			let (header, header_length) = try_err!(VolumeHeader::read(&mut file));
+			volumes.push((path.as_ref().to_path_buf(), header, header_length));
+		}
+		volumes.sort_by_key(|volume| volume.1.index);
+		
+		// Validate the set
+		let (stream_id, total) = match volumes.first() {
+			Some((_, header, _)) => (header.stream_id, header.total),
+			None => throw_err!(CpError::InvalidParameter, "No volumes given")
+		};
+		for (expected, (path, header, _)) in volumes.iter().enumerate() {
+			if header.stream_id != stream_id || header.total != total {
+				throw_err!(CpError::InvalidData, format!("The volume \"{}\" belongs to another stream", path.display()))
+			}
+			if header.index >= total { throw_err!(CpError::InvalidData, format!("Invalid volume-index in \"{}\"", path.display())) }
+			if header.index < expected as u64 { throw_err!(CpError::InvalidData, format!("Volume #{} exists twice", header.index)) }
+			if header.index > expected as u64 { throw_err!(CpError::InvalidData, format!("Volume #{} of {} is missing", expected, total)) }
+		}
+		if (volumes.len() as u64) < total { throw_err!(CpError::InvalidData, format!("Volume #{} of {} is missing", volumes.len(), total)) }
+		if volumes.len() as u64 > total { throw_err!(CpError::InvalidData, "The volume-set contains too many volumes") }
+		Ok(VolumeSet{ volumes })
+	}
+	
+	/// Finds the volumes `<prefix>.<number>` and reads them like `from_paths` (the volume-index is
+	/// taken from the header, so renamed volumes are ordered correctly)
+	pub fn from_prefix<P: AsRef<Path>>(prefix: P) -> Result<Self, Error<CpError>> {
+		let prefix = prefix.as_ref();
+		let name = match prefix.file_name().and_then(|name| name.to_str()) {
+			Some(name) => format!("{}.", name),
+			None => throw_err!(CpError::InvalidParameter, format!("Invalid volume-prefix \"{}\"", prefix.display()))
+		};
+		let dir = match prefix.parent() {
+			Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+			_ => PathBuf::from(".")
+		};
+		
+		// Collect all files with the prefix and a numeric extension
+		let mut paths = Vec::new();
+		for child in try_err_from!(std::fs::read_dir(&dir), format!("Failed to read the directory \"{}\"", dir.display())) {
+			let child = try_err_from!(child, format!("Failed to read the directory \"{}\"", dir.display()));
+			if let Some(file_name) = child.file_name().to_str() {
+				let is_volume = file_name.starts_with(&name) && file_name.len() > name.len() && file_name[name.len() ..].bytes().all(|byte| byte.is_ascii_digit());
+				if is_volume { paths.push(child.path()) }
+			}
+		}
+		Self::from_paths(&paths)
+	}
+	
+	/// Returns the amount of volumes
+	pub fn len(&self) -> usize {
+		self.volumes.len()
+	}
+	/// Returns whether the set is empty (a validated set always contains at least one volume)
+	pub fn is_empty(&self) -> bool {
+		self.volumes.is_empty()
+	}
+	
+	/// Authenticates all volume-headers with the base-key of the stream
+	pub fn verify(&self, stream_instance: &crypto::StreamInstance, base_key: &crypto::Key) -> Result<(), Error<CpError>> {
+		let volume_key = try_err!(derive_volume_key(stream_instance, base_key));
+		for (_, header, _) in self.volumes.iter() { try_err!(header.verify(stream_instance, &volume_key)) }
+		Ok(())
+	}
+	
+	/// Creates a reader over the concatenated stream-parts of all volumes
+	pub fn reader(&self) -> VolumeReader {
+		VolumeReader{ volumes: self.volumes.iter().map(|&(ref path, _, header_length)| (path.clone(), header_length)).collect(), next: 0, file: None }
+	}
+}
+
+/// A `std::io::Read`-adapter that reads the stream-parts of the volumes in order
+pub struct VolumeReader {
+	volumes: Vec<(PathBuf, u64)>,
+	next: usize,
+	file: Option<std::fs::File>
+}
+impl Read for VolumeReader {
+	fn read(&mut self, buffer: &mut[u8]) -> std::io::Result<usize> {
+		loop {
+			// Read from the current volume until EOF
+			if let Some(ref mut file) = self.file {
+				let bytes_read = file.read(buffer)?;
+				if bytes_read > 0 || buffer.is_empty() { return Ok(bytes_read) }
+			}
+			
+			// Open the next volume and skip its header
+			if self.next == self.volumes.len() { return Ok(0) }
+			let (ref path, header_length) = self.volumes[self.next];
+			let mut file = std::fs::File::open(path)?;
+			file.seek(SeekFrom::Start(header_length))?;
+			self.file = Some(file);
+			self.next += 1;
+		}
+	}
+}
\ No newline at end of file
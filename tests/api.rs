@@ -0,0 +1,51 @@
+extern crate crypto_pipe;
+
+use crypto_pipe::{ SealBuilder, OpenBuilder, CpError, crypto };
+use crypto_pipe::io::StreamIo;
+
+const PASSWORD: &str = "Public API password";
+
+
+
+fn seal(plain: &[u8], password: &str) -> Vec<u8> {
+	let mut io = StreamIo::new(plain, Vec::new());
+	SealBuilder::new(password.to_string())
+		.argon2i(4, 8, 1)
+		.kdf(crypto::kdf::HmacSha2512::new())
+		.auth_enc(crypto::auth_enc::ChaCha20Poly1305::new())
+		.seal(&mut io).unwrap();
+	io.into_inner().1
+}
+
+fn open(sealed: &[u8], password: &str) -> Result<Vec<u8>, crypto_pipe::Error<CpError>> {
+	let mut io = StreamIo::new(sealed, Vec::new());
+	OpenBuilder::new(password.to_string()).open(&mut io)?;
+	Ok(io.into_inner().1)
+}
+
+
+
+#[test]
+fn test_roundtrip() {
+	for size in [0, 4096, crypto_pipe::stream::CHUNK_DATA_SIZE, 3 * crypto_pipe::stream::CHUNK_DATA_SIZE + 7].iter() {
+		let mut plain = vec![0u8; *size];
+		crypto::random(&mut plain);
+		
+		let sealed = seal(&plain, PASSWORD);
+		assert_eq!(open(&sealed, PASSWORD).unwrap(), plain)
+	}
+}
+
+#[test]
+fn test_wrong_password() {
+	let sealed = seal(b"Some secret data", PASSWORD);
+	assert_eq!(open(&sealed, "Wrong password").unwrap_err().kind, CpError::WrongPassword)
+}
+
+#[test]
+fn test_tampered_stream() {
+	let mut sealed = seal(b"Some secret data", PASSWORD);
+	let len = sealed.len();
+	sealed[len - 1] ^= 0x01;
+	assert_eq!(open(&sealed, PASSWORD).unwrap_err().kind, CpError::InvalidData)
+}
\ No newline at end of file